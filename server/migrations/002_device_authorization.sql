@@ -0,0 +1,40 @@
+-- Device authorization grant (RFC 8628) for pairing the browser extension
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'device_auth_status') THEN
+        CREATE TYPE device_auth_status AS ENUM ('pending', 'approved', 'denied', 'consumed');
+    END IF;
+END
+$$;
+
+-- Credentials issued to paired devices; revoking a row invalidates its tokens
+CREATE TABLE IF NOT EXISTS device_credentials (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_name VARCHAR(255) NOT NULL,
+    scope VARCHAR(255) NOT NULL,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_credentials_user_id ON device_credentials(user_id);
+
+-- Outstanding device authorization requests
+CREATE TABLE IF NOT EXISTS device_authorizations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    device_code VARCHAR(255) UNIQUE NOT NULL,
+    user_code VARCHAR(16) UNIQUE NOT NULL,
+    client_name VARCHAR(255) NOT NULL,
+    scope VARCHAR(255) NOT NULL,
+    status device_auth_status NOT NULL DEFAULT 'pending',
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    credential_id UUID REFERENCES device_credentials(id) ON DELETE SET NULL,
+    poll_interval INTEGER NOT NULL,
+    last_polled_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_authorizations_device_code ON device_authorizations(device_code);
+CREATE INDEX IF NOT EXISTS idx_device_authorizations_user_code ON device_authorizations(user_code);
@@ -0,0 +1,12 @@
+-- Soft-deleted accounts are purged once their grace period ends
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS purge_after TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_users_purge_after ON users(purge_after) WHERE purge_after IS NOT NULL;
+
+-- Addresses of purged accounts stay reserved so they can never be handed out again
+CREATE TABLE IF NOT EXISTS reserved_addresses (
+    address VARCHAR(255) PRIMARY KEY,
+    reason VARCHAR(64) NOT NULL,
+    reserved_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
@@ -0,0 +1,31 @@
+-- Monotonic position of each log entry, used as the SSE event ID so that
+-- clients can resume a stream with Last-Event-ID
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS seq BIGSERIAL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_email_logs_seq ON email_logs(seq);
+
+-- Announce new log entries to every server instance listening on the channel
+CREATE OR REPLACE FUNCTION notify_email_log_inserted()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify(
+        'email_log_events',
+        json_build_object(
+            'seq', NEW.seq,
+            'user_id', (SELECT user_id FROM aliases WHERE id = NEW.alias_id)
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_trigger WHERE tgname = 'notify_email_logs_inserted'
+    ) THEN
+        CREATE TRIGGER notify_email_logs_inserted AFTER INSERT ON email_logs
+            FOR EACH ROW EXECUTE FUNCTION notify_email_log_inserted();
+    END IF;
+END
+$$;
@@ -0,0 +1,23 @@
+-- Per-alias delivery: forward each message or collect them into a digest
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'alias_delivery_mode') THEN
+        CREATE TYPE alias_delivery_mode AS ENUM ('immediate', 'daily_digest', 'weekly_digest');
+    END IF;
+END
+$$;
+
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS delivery_mode alias_delivery_mode NOT NULL DEFAULT 'immediate';
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS last_digest_at TIMESTAMP WITH TIME ZONE;
+
+-- Messages kept back for a digest until released
+ALTER TYPE email_status ADD VALUE IF NOT EXISTS 'held';
+
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS body_plain TEXT;
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS body_html TEXT;
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS release_token VARCHAR(255);
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS digest_sent_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS released_at TIMESTAMP WITH TIME ZONE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_email_logs_release_token ON email_logs(release_token);
+CREATE INDEX IF NOT EXISTS idx_email_logs_digest_pending ON email_logs(alias_id) WHERE digest_sent_at IS NULL AND released_at IS NULL;
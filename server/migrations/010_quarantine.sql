@@ -0,0 +1,22 @@
+-- Messages kept out of the inbox for the user to review
+ALTER TYPE email_status ADD VALUE IF NOT EXISTS 'quarantined';
+
+-- Full message content is stored encrypted (AES-256-GCM); sender and subject
+-- stay on the email log entry
+CREATE TABLE IF NOT EXISTS quarantined_messages (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    email_log_id UUID NOT NULL REFERENCES email_logs(id) ON DELETE CASCADE,
+    alias_id UUID NOT NULL REFERENCES aliases(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason VARCHAR(32) NOT NULL,
+    nonce BYTEA NOT NULL,
+    content BYTEA NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_quarantined_messages_user_id ON quarantined_messages(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_quarantined_messages_expires_at ON quarantined_messages(expires_at);
+
+-- Whether mail to a paused alias is quarantined rather than dropped
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS quarantine_when_paused BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+-- How forwarded HTML is cleaned of trackers for each alias
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'tracker_protection') THEN
+        CREATE TYPE tracker_protection AS ENUM ('off', 'standard', 'proxy_images');
+    END IF;
+END
+$$;
+
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS tracker_protection tracker_protection NOT NULL DEFAULT 'standard';
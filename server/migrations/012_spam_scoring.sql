@@ -0,0 +1,8 @@
+-- Per-user spam thresholds: messages scoring at least the tag threshold are
+-- forwarded with a subject prefix, at least the quarantine threshold are
+-- quarantined. A NULL quarantine threshold disables quarantining.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS spam_tag_threshold DOUBLE PRECISION NOT NULL DEFAULT 5.0;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS spam_quarantine_threshold DOUBLE PRECISION DEFAULT 10.0;
+
+-- Sender reputation lookups
+CREATE INDEX IF NOT EXISTS idx_email_logs_from_email ON email_logs(from_email, created_at DESC);
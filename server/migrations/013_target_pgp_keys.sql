@@ -0,0 +1,3 @@
+-- OpenPGP public key used to encrypt mail forwarded to the target mailbox
+ALTER TABLE target_emails ADD COLUMN IF NOT EXISTS pgp_public_key TEXT;
+ALTER TABLE target_emails ADD COLUMN IF NOT EXISTS pgp_fingerprint VARCHAR(40);
@@ -0,0 +1,20 @@
+-- User-owned domains for aliases, verified through a DNS TXT challenge
+CREATE TABLE IF NOT EXISTS domains (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    domain VARCHAR(253) NOT NULL,
+    verification_token VARCHAR(64) NOT NULL,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    catch_all BOOLEAN NOT NULL DEFAULT false,
+    last_checked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_domains_user_id ON domains(user_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_domains_user_domain ON domains(user_id, domain);
+-- Any number of users may claim a domain, but only one can verify it
+CREATE UNIQUE INDEX IF NOT EXISTS idx_domains_verified_domain ON domains(domain) WHERE verified_at IS NOT NULL;
+
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS domain_id UUID REFERENCES domains(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_aliases_domain_id ON aliases(domain_id);
@@ -0,0 +1,17 @@
+-- Shared domains offered to every user for aliases. Disabled domains accept
+-- no new aliases but keep delivering to existing ones.
+CREATE TABLE IF NOT EXISTS system_domains (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    domain VARCHAR(253) UNIQUE NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    dkim_selector VARCHAR(63) NOT NULL DEFAULT 'hush',
+    dkim_private_key TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_system_domains_default ON system_domains(is_default) WHERE is_default;
+
+-- Addresses are matched case-insensitively
+UPDATE aliases SET address = LOWER(address) WHERE address <> LOWER(address);
@@ -0,0 +1,15 @@
+-- Subaddress tags (alias+tag@domain) the user no longer accepts mail for
+CREATE TABLE IF NOT EXISTS alias_blocked_tags (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    alias_id UUID NOT NULL REFERENCES aliases(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    -- Blocked automatically because the tag received spam
+    auto_blocked BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE (alias_id, tag)
+);
+
+-- Block a tag as soon as mail to it is tagged or quarantined as spam
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS block_spam_tags BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS idx_email_logs_alias_tag ON email_logs(alias_id, (metadata->>'tag'));
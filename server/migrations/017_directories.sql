@@ -0,0 +1,15 @@
+-- Namespaces on the system domains: mail to name.anything@domain or
+-- anything@name.domain creates an alias for the owner on first delivery
+CREATE TABLE IF NOT EXISTS directories (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(32) NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_directories_user_id ON directories(user_id);
+
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS directory_id UUID REFERENCES directories(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_aliases_directory_created ON aliases(directory_id, created_at) WHERE directory_id IS NOT NULL;
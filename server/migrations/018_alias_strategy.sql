@@ -0,0 +1,10 @@
+-- How random and temporary alias addresses are generated for each user
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'alias_strategy') THEN
+        CREATE TYPE alias_strategy AS ENUM ('hex', 'words', 'syllables');
+    END IF;
+END
+$$;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS alias_strategy alias_strategy NOT NULL DEFAULT 'hex';
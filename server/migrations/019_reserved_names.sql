@@ -0,0 +1,33 @@
+-- Operator-managed names custom aliases and directories may not use
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'reserved_name_kind') THEN
+        CREATE TYPE reserved_name_kind AS ENUM ('reserved', 'profanity');
+    END IF;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS reserved_names (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name VARCHAR(64) NOT NULL UNIQUE,
+    kind reserved_name_kind NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Role accounts (RFC 2142) and infrastructure names
+INSERT INTO reserved_names (name, kind) VALUES
+    ('abuse', 'reserved'), ('admin', 'reserved'), ('administrator', 'reserved'), ('api', 'reserved'),
+    ('billing', 'reserved'), ('help', 'reserved'), ('hostmaster', 'reserved'), ('hush', 'reserved'),
+    ('info', 'reserved'), ('mail', 'reserved'), ('mailer-daemon', 'reserved'), ('marketing', 'reserved'),
+    ('mx', 'reserved'), ('no-reply', 'reserved'), ('noc', 'reserved'), ('noreply', 'reserved'),
+    ('postmaster', 'reserved'), ('root', 'reserved'), ('sales', 'reserved'), ('security', 'reserved'),
+    ('smtp', 'reserved'), ('support', 'reserved'), ('sysadmin', 'reserved'), ('temp', 'reserved'),
+    ('usenet', 'reserved'), ('uucp', 'reserved'), ('webmaster', 'reserved'), ('www', 'reserved')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO reserved_names (name, kind) VALUES
+    ('asshole', 'profanity'), ('bitch', 'profanity'), ('cunt', 'profanity'), ('fuck', 'profanity'),
+    ('shit', 'profanity'), ('whore', 'profanity')
+ON CONFLICT (name) DO NOTHING;
+
+CREATE INDEX IF NOT EXISTS idx_aliases_user_created ON aliases(user_id, created_at);
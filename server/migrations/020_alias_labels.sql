@@ -0,0 +1,3 @@
+-- Free-form label for organising aliases, e.g. set in bulk
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS label VARCHAR(100);
+CREATE INDEX IF NOT EXISTS idx_aliases_user_label ON aliases(user_id, label) WHERE label IS NOT NULL;
@@ -0,0 +1,12 @@
+-- Addresses brought over from other alias services and the alias each became
+CREATE TABLE IF NOT EXISTS alias_imports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    alias_id UUID REFERENCES aliases(id) ON DELETE SET NULL,
+    source VARCHAR(20) NOT NULL,
+    original_address VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE(user_id, original_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alias_imports_alias_id ON alias_imports(alias_id);
@@ -0,0 +1,50 @@
+-- Daily message counts per alias, kept up to date by the stats rollup job
+-- from email_logs
+CREATE TABLE IF NOT EXISTS alias_daily_stats (
+    alias_id UUID NOT NULL REFERENCES aliases(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    status email_status NOT NULL,
+    messages BIGINT NOT NULL,
+    last_received_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (alias_id, day, status)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alias_daily_stats_user_day ON alias_daily_stats(user_id, day);
+
+-- Daily message counts per alias and sender
+CREATE TABLE IF NOT EXISTS alias_daily_senders (
+    alias_id UUID NOT NULL REFERENCES aliases(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    from_email VARCHAR(255) NOT NULL,
+    messages BIGINT NOT NULL,
+    PRIMARY KEY (alias_id, day, from_email)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alias_daily_senders_user_day ON alias_daily_senders(user_id, day);
+
+-- When the rollup job last ran; a single row
+CREATE TABLE IF NOT EXISTS stats_rollup_state (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    rolled_up_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Log entries change status after delivery (digests, quarantine), so the job
+-- rebuilds every alias and day with entries changed since its last run.
+-- Existing entries all get the migration time and are rolled up on the first run.
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS idx_email_logs_updated_at ON email_logs(updated_at);
+CREATE INDEX IF NOT EXISTS idx_email_logs_alias_created_at ON email_logs(alias_id, created_at DESC);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_trigger WHERE tgname = 'update_email_logs_updated_at'
+    ) THEN
+        CREATE TRIGGER update_email_logs_updated_at BEFORE UPDATE ON email_logs
+            FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+    END IF;
+END
+$$;
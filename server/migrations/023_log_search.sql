@@ -0,0 +1,14 @@
+-- Indexes for searching email logs across aliases
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Full-text search on subject. The 'simple' configuration does no stemming,
+-- which suits mail in any language; queries must use the same expression.
+CREATE INDEX IF NOT EXISTS idx_email_logs_subject_fts
+    ON email_logs USING GIN (to_tsvector('simple', COALESCE(subject, '')));
+
+-- Substring matches on the sender
+CREATE INDEX IF NOT EXISTS idx_email_logs_from_email_trgm
+    ON email_logs USING GIN (lower(from_email) gin_trgm_ops);
+
+-- Newest-first pages of a single alias
+CREATE INDEX IF NOT EXISTS idx_email_logs_alias_seq ON email_logs(alias_id, seq DESC);
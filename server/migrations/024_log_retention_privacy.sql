@@ -0,0 +1,32 @@
+-- Per-user email log retention and privacy mode. Without a retention period
+-- logs are kept indefinitely.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS log_retention_days INTEGER;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS log_privacy BOOLEAN NOT NULL DEFAULT false;
+
+-- Keyed hash (HMAC-SHA256) of the lowercased sender, so senders can be
+-- matched once the address itself is no longer stored. Existing entries are
+-- hashed by the log maintenance job.
+ALTER TABLE email_logs ADD COLUMN IF NOT EXISTS sender_hash VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_email_logs_sender_hash ON email_logs(sender_hash, created_at);
+CREATE INDEX IF NOT EXISTS idx_email_logs_alias_sender_hash ON email_logs(alias_id, sender_hash);
+CREATE INDEX IF NOT EXISTS idx_email_logs_unhashed ON email_logs(id) WHERE sender_hash IS NULL;
+
+-- Top senders are keyed by hash so privacy-mode entries are counted too. The
+-- rollups are rebuilt from email_logs on the next run of the stats job.
+DROP TABLE IF EXISTS alias_daily_senders;
+
+CREATE TABLE IF NOT EXISTS alias_daily_senders (
+    alias_id UUID NOT NULL REFERENCES aliases(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    sender_hash VARCHAR(64) NOT NULL,
+    -- NULL when only privacy-mode entries were seen
+    from_email VARCHAR(255),
+    messages BIGINT NOT NULL,
+    PRIMARY KEY (alias_id, day, sender_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alias_daily_senders_user_day ON alias_daily_senders(user_id, day);
+
+DELETE FROM stats_rollup_state;
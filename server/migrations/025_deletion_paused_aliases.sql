@@ -0,0 +1,9 @@
+-- Aliases paused by an account deletion, so cancelling it reactivates
+-- exactly those and leaves aliases the user paused alone
+ALTER TABLE aliases ADD COLUMN IF NOT EXISTS paused_by_deletion BOOLEAN NOT NULL DEFAULT false;
+
+-- Accounts already in their grace period had only active aliases paused
+UPDATE aliases a SET paused_by_deletion = true
+FROM users u
+WHERE a.user_id = u.id AND u.deleted_at IS NOT NULL AND a.status = 'paused'
+AND a.updated_at >= u.deleted_at;
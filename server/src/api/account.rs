@@ -0,0 +1,305 @@
+use axum::{
+    extract::{Extension, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::io::Write;
+
+use crate::api::pages;
+use crate::api::targets::VerifyQuery;
+use crate::auth::{verify_password, AuthenticatedUser};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::generator::AliasGenerator;
+use crate::models::{
+    AliasStrategy, ChangeEmailRequest, DeleteAccountRequest, UpdateAliasGenerationRequest,
+    UpdateLogSettingsRequest, UpdateSpamSettingsRequest,
+};
+use crate::services::account_service::EmailChangeProgress;
+use crate::services::{
+    AccountService, AliasService, EmailService, LogService, SecurityService, SpamService,
+};
+use crate::validation::normalize_email;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+async fn check_password(pool: &PgPool, config: &Config, user_id: uuid::Uuid, password: &str) -> Result<String> {
+    let (email, password_hash): (String, String) =
+        sqlx::query_as("SELECT email, password_hash FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if !verify_password(config, password, &password_hash).await? {
+        return Err(AppError::Auth("Invalid password".to_string()));
+    }
+
+    Ok(email)
+}
+
+/// Start changing the account email. Confirmation links go to both the
+/// current and the new address.
+pub async fn change_email(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<ChangeEmailRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let new_email = normalize_email(&req.new_email)?;
+
+    let current_email = check_password(&pool, &config, user.user_id, &req.password).await?;
+
+    if new_email == current_email {
+        return Err(AppError::Validation("New email is the same as the current one".to_string()));
+    }
+
+    if AccountService::email_in_use(&pool, &new_email).await? {
+        return Err(AppError::Validation("Email address is already in use".to_string()));
+    }
+
+    let request = AccountService::request_email_change(&pool, user.user_id, &new_email).await?;
+
+    EmailService::send_email_change_confirmation(&config, &current_email, &request.old_token, &new_email).await?;
+    EmailService::send_email_change_confirmation(&config, &new_email, &request.new_token, &new_email).await?;
+
+    Ok(Json(serde_json::json!({ "message": "confirmation_sent" })))
+}
+
+/// Confirm an email change via GET (for browser links)
+pub async fn confirm_email_change(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<VerifyQuery>,
+) -> impl IntoResponse {
+    match AccountService::confirm_email_change(&pool, &params.token).await {
+        Ok(EmailChangeProgress::Pending(request)) => (
+            StatusCode::OK,
+            pages::verified_page(
+                "Confirmation Received",
+                "Thanks! The change will take effect once the other address confirms too.",
+                &request.new_email,
+                "Check the other mailbox for the second confirmation link.",
+            ),
+        ),
+        Ok(EmailChangeProgress::Completed(request)) => (
+            StatusCode::OK,
+            pages::verified_page(
+                "Email Changed!",
+                "Your account email address has been updated.",
+                &request.new_email,
+                "Use this address the next time you sign in.",
+            ),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            pages::verification_failed_page(&e.to_string()),
+        ),
+    }
+}
+
+/// Schedule the account for deletion after the grace period
+pub async fn delete_account(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<DeleteAccountRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let email = check_password(&pool, &config, user.user_id, &req.password).await?;
+
+    let purge_after =
+        AccountService::schedule_deletion(&pool, user.user_id, config.account_deletion_grace_days).await?;
+
+    SecurityService::log_event(
+        &pool,
+        Some(user.user_id),
+        "account_deletion_scheduled",
+        Some(&email),
+        None,
+        Some(serde_json::json!({ "purge_after": purge_after })),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "scheduled",
+        "purge_after": purge_after,
+        "message": "Sign in before this date to cancel the deletion"
+    })))
+}
+
+/// Download all data stored for the account as JSON or a ZIP archive
+pub async fn export(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response> {
+    let sections = AccountService::export(&pool, &config, user.user_id).await?;
+    let date = chrono::Utc::now().format("%Y-%m-%d");
+
+    match params.format.as_deref().unwrap_or("json") {
+        "json" => {
+            let body: serde_json::Map<String, serde_json::Value> = sections
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+
+            Ok((
+                [(
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"hush-export-{}.json\"", date),
+                )],
+                Json(serde_json::Value::Object(body)),
+            )
+                .into_response())
+        }
+        "zip" => {
+            let archive = tokio::task::spawn_blocking(move || build_zip(sections))
+                .await
+                .map_err(|e| AppError::Internal(format!("Export task failed: {}", e)))??;
+
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"hush-export-{}.zip\"", date),
+                    ),
+                ],
+                archive,
+            )
+                .into_response())
+        }
+        _ => Err(AppError::Validation("Format must be 'json' or 'zip'".to_string())),
+    }
+}
+
+/// One pretty-printed JSON file per export section
+fn build_zip(sections: Vec<(&'static str, serde_json::Value)>) -> Result<Vec<u8>> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, value) in sections {
+        let json = serde_json::to_vec_pretty(&value)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize export: {}", e)))?;
+
+        writer
+            .start_file(format!("{}.json", name), options)
+            .and_then(|_| writer.write_all(&json).map_err(Into::into))
+            .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+    }
+
+    let cursor = writer
+        .finish()
+        .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+
+    Ok(cursor.into_inner())
+}
+
+/// Current spam thresholds
+pub async fn spam_settings(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let settings = SpamService::settings(&pool, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "tag_threshold": settings.spam_tag_threshold,
+        "quarantine_threshold": settings.spam_quarantine_threshold
+    })))
+}
+
+/// Set the scores at which inbound mail is tagged or quarantined as spam
+pub async fn update_spam_settings(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Json(req): Json<UpdateSpamSettingsRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let settings = SpamService::update_settings(
+        &pool,
+        user.user_id,
+        req.tag_threshold,
+        req.quarantine_threshold,
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "tag_threshold": settings.spam_tag_threshold,
+        "quarantine_threshold": settings.spam_quarantine_threshold
+    })))
+}
+
+/// How long email logs are kept and whether privacy mode is on
+pub async fn log_settings(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let settings = LogService::settings(&pool, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "retention_days": settings.log_retention_days,
+        "privacy_mode": settings.log_privacy
+    })))
+}
+
+/// Set the log retention period and privacy mode
+pub async fn update_log_settings(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Json(req): Json<UpdateLogSettingsRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let settings =
+        LogService::update_settings(&pool, user.user_id, req.retention_days, req.privacy_mode).await?;
+
+    Ok(Json(serde_json::json!({
+        "retention_days": settings.log_retention_days,
+        "privacy_mode": settings.log_privacy
+    })))
+}
+
+/// Default alias address style, with the entropy and an example of each
+pub async fn alias_generation(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let strategy = AliasService::generation_strategy(&pool, user.user_id).await?;
+
+    Ok(Json(alias_generation_json(&config, strategy)))
+}
+
+pub async fn update_alias_generation(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<UpdateAliasGenerationRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let strategy = AliasService::set_generation_strategy(&pool, user.user_id, req.strategy).await?;
+
+    Ok(Json(alias_generation_json(&config, strategy)))
+}
+
+fn alias_generation_json(config: &Config, strategy: AliasStrategy) -> serde_json::Value {
+    let strategies: Vec<serde_json::Value> = [AliasStrategy::Hex, AliasStrategy::Words, AliasStrategy::Syllables]
+        .into_iter()
+        .map(|s| {
+            let generator = AliasGenerator::new(config, s);
+            serde_json::json!({
+                "strategy": s,
+                "entropy_bits": (generator.entropy_bits() * 10.0).round() / 10.0,
+                "example": generator.generate("hush")
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "strategy": strategy,
+        "strategies": strategies
+    })
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use sqlx::PgPool;
+
+use crate::auth::AdminAccess;
+use crate::error::Result;
+use crate::models::{
+    CreateReservedNameRequest, CreateSystemDomainRequest, SystemDomain, UpdateSystemDomainRequest,
+};
+use crate::services::{ReservedNameService, SystemDomainService};
+
+pub async fn list_system_domains(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+) -> Result<Json<serde_json::Value>> {
+    let domains = SystemDomainService::list(&pool).await?;
+
+    let response = domains
+        .iter()
+        .map(system_domain_json)
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(serde_json::json!({ "system_domains": response })))
+}
+
+/// Add a shared domain. The DKIM private key is only returned here.
+pub async fn create_system_domain(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+    Json(req): Json<CreateSystemDomainRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let domain = SystemDomainService::create(
+        &pool,
+        &req.domain,
+        req.enabled,
+        req.is_default,
+        req.dkim_selector.as_deref(),
+        req.dkim_private_key.as_deref(),
+    )
+    .await?;
+
+    let mut response = system_domain_json(&domain)?;
+    response["dkim_private_key"] = serde_json::json!(domain.dkim_private_key);
+
+    Ok(Json(response))
+}
+
+pub async fn update_system_domain(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+    Path(domain): Path<String>,
+    Json(req): Json<UpdateSystemDomainRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let domain = SystemDomainService::update(&pool, &domain.to_ascii_lowercase(), req.enabled, req.is_default).await?;
+
+    Ok(Json(system_domain_json(&domain)?))
+}
+
+/// Names custom aliases may not use
+pub async fn list_reserved_names(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+) -> Result<Json<serde_json::Value>> {
+    let names = ReservedNameService::list(&pool).await?;
+
+    Ok(Json(serde_json::json!({ "reserved_names": names })))
+}
+
+pub async fn add_reserved_name(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+    Json(req): Json<CreateReservedNameRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let name = ReservedNameService::add(&pool, &req.name, req.kind).await?;
+
+    Ok(Json(serde_json::json!(name)))
+}
+
+pub async fn remove_reserved_name(
+    Extension(pool): Extension<PgPool>,
+    _admin: AdminAccess,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    ReservedNameService::remove(&pool, &name).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+fn system_domain_json(domain: &SystemDomain) -> Result<serde_json::Value> {
+    let (dkim_name, dkim_value) = SystemDomainService::dkim_record(domain)?;
+
+    Ok(serde_json::json!({
+        "id": domain.id.to_string(),
+        "domain": domain.domain,
+        "enabled": domain.enabled,
+        "is_default": domain.is_default,
+        "dkim": {
+            "selector": domain.dkim_selector,
+            "name": dkim_name,
+            "value": dkim_value
+        },
+        "created_at": domain.created_at
+    }))
+}
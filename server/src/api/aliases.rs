@@ -1,5 +1,7 @@
 use axum::{
     extract::{Extension, Path, Query},
+    http::header,
+    response::{IntoResponse, Response},
     Json,
 };
 use sqlx::PgPool;
@@ -7,14 +9,36 @@ use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::auth::AuthenticatedUser;
-use crate::error::{AppError, Result};
-use crate::models::{AliasResponse, CreateAliasRequest, ToggleAliasRequest};
-use crate::services::AliasService;
 use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::generator::AliasGenerator;
+use crate::models::{
+    AliasExport, AliasResponse, AliasType, BlockTagRequest, BulkAliasRequest, CreateAliasRequest, ImportFormat,
+    SetBlockSpamTagsRequest, SetDeliveryModeRequest, SetTrackerProtectionRequest, ToggleAliasRequest,
+    UnmanagedImport,
+};
+use crate::services::{AliasService, DirectoryService, DomainService, ImportService, ReservedNameService};
+use crate::validation::{check_alias_policy, normalize_local_part};
 
 #[derive(Deserialize)]
 pub struct LogsQuery {
     pub limit: Option<i64>,
+    /// Only entries for mail sent to this subaddress tag
+    pub tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    pub format: ImportFormat,
+    #[serde(default)]
+    pub unmanaged: UnmanagedImport,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AliasExportQuery {
+    pub format: Option<String>,
 }
 
 pub async fn list(
@@ -29,6 +53,12 @@ pub async fn list(
             id: a.id.to_string(),
             address: a.address,
             status: format!("{:?}", a.status).to_lowercase(),
+            delivery_mode: a.delivery_mode,
+            quarantine_when_paused: a.quarantine_when_paused,
+            tracker_protection: a.tracker_protection,
+            block_spam_tags: a.block_spam_tags,
+            label: a.label,
+            expires_at: a.expires_at,
             created_at: a.created_at,
         })
         .collect();
@@ -42,13 +72,39 @@ pub async fn create(
     user: AuthenticatedUser,
     Json(req): Json<CreateAliasRequest>,
 ) -> Result<Json<AliasResponse>> {
+    AliasService::check_creation_rate(&pool, user.user_id, config.alias_create_limit_per_hour).await?;
+
+    let (domain, domain_id) =
+        DomainService::for_new_alias(&pool, user.user_id, req.domain.as_deref()).await?;
+
+    let custom = match (&req.alias_type, req.custom) {
+        (AliasType::Custom, Some(custom)) => {
+            let custom = normalize_local_part(&custom, "custom")?;
+            let policy = ReservedNameService::policy(&pool, &config).await?;
+            check_alias_policy(&custom, "custom", &policy, domain_id.is_none())?;
+            if domain_id.is_none() {
+                DirectoryService::check_custom(&pool, user.user_id, &custom).await?;
+            }
+            Some(custom)
+        }
+        (_, custom) => custom,
+    };
+
+    let strategy = match req.strategy {
+        Some(strategy) => strategy,
+        None => AliasService::generation_strategy(&pool, user.user_id).await?,
+    };
+    let generator = AliasGenerator::new(&config, strategy);
+
     let alias = AliasService::create(
         &pool,
         user.user_id,
         req.alias_type,
-        req.custom,
+        custom,
         req.ttl_minutes,
-        &config.hush_domain,
+        Some(&generator),
+        &domain,
+        domain_id,
     )
     .await?;
 
@@ -56,6 +112,12 @@ pub async fn create(
         id: alias.id.to_string(),
         address: alias.address,
         status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
         created_at: alias.created_at,
     }))
 }
@@ -69,12 +131,294 @@ pub async fn toggle(
     let alias_id = Uuid::parse_str(&id)
         .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
 
-    let alias = AliasService::toggle(&pool, alias_id, user.user_id, req.enabled).await?;
+    let alias = AliasService::toggle(
+        &pool,
+        alias_id,
+        user.user_id,
+        req.enabled,
+        req.quarantine_when_paused,
+    )
+    .await?;
+
+    Ok(Json(AliasResponse {
+        id: alias.id.to_string(),
+        address: alias.address,
+        status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
+        created_at: alias.created_at,
+    }))
+}
+
+pub async fn set_delivery_mode(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<SetDeliveryModeRequest>,
+) -> Result<Json<AliasResponse>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let alias = AliasService::set_delivery_mode(&pool, alias_id, user.user_id, req.delivery_mode).await?;
 
     Ok(Json(AliasResponse {
         id: alias.id.to_string(),
         address: alias.address,
         status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
+        created_at: alias.created_at,
+    }))
+}
+
+pub async fn set_tracker_protection(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<SetTrackerProtectionRequest>,
+) -> Result<Json<AliasResponse>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let alias =
+        AliasService::set_tracker_protection(&pool, alias_id, user.user_id, req.tracker_protection).await?;
+
+    Ok(Json(AliasResponse {
+        id: alias.id.to_string(),
+        address: alias.address,
+        status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
+        created_at: alias.created_at,
+    }))
+}
+
+/// Automatically block subaddress tags that start receiving spam
+pub async fn set_block_spam_tags(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<SetBlockSpamTagsRequest>,
+) -> Result<Json<AliasResponse>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let alias = AliasService::set_block_spam_tags(&pool, alias_id, user.user_id, req.block_spam_tags).await?;
+
+    Ok(Json(AliasResponse {
+        id: alias.id.to_string(),
+        address: alias.address,
+        status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
+        created_at: alias.created_at,
+    }))
+}
+
+/// Subaddress tags used with the alias and whether they are blocked
+pub async fn tags(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let tags = AliasService::list_tags(&pool, alias_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "tags": tags })))
+}
+
+pub async fn block_tag(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<BlockTagRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    AliasService::block_tag(&pool, alias_id, user.user_id, &req.tag).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+pub async fn unblock_tag(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path((id, tag)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    AliasService::unblock_tag(&pool, alias_id, user.user_id, &tag).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Pause, activate, delete, label or set the expiry of many aliases at once
+pub async fn bulk(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Json(req): Json<BulkAliasRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let results = AliasService::bulk(
+        &pool,
+        user.user_id,
+        req.ids.as_deref(),
+        req.filter.as_ref(),
+        &req.action,
+        req.dry_run,
+    )
+    .await?;
+
+    let updated = results.iter().filter(|r| r.result == "updated").count();
+
+    Ok(Json(serde_json::json!({
+        "dry_run": req.dry_run,
+        "matched": results.iter().filter(|r| r.result != "not_found").count(),
+        "updated": updated,
+        "results": results
+    })))
+}
+
+/// Create aliases from another service's export. The body is the exported
+/// file as is.
+pub async fn import(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Query(params): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<serde_json::Value>> {
+    let results = ImportService::import(
+        &pool,
+        &config,
+        user.user_id,
+        params.format,
+        &body,
+        params.unmanaged,
+        params.dry_run,
+    )
+    .await?;
+
+    let count = |result: &str| results.iter().filter(|r| r.result == result).count();
+
+    Ok(Json(serde_json::json!({
+        "dry_run": params.dry_run,
+        "created": count("created"),
+        "mapped": count("mapped"),
+        "skipped": count("skipped"),
+        "failed": count("failed"),
+        "results": results
+    })))
+}
+
+/// Download the user's aliases as CSV or JSON, in a layout the import
+/// endpoint reads back
+pub async fn export(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Query(params): Query<AliasExportQuery>,
+) -> Result<Response> {
+    let aliases = AliasService::export(&pool, user.user_id).await?;
+    let date = chrono::Utc::now().format("%Y-%m-%d");
+
+    match params.format.as_deref().unwrap_or("csv") {
+        "csv" => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"hush-aliases-{}.csv\"", date),
+                ),
+            ],
+            write_csv(&aliases)?,
+        )
+            .into_response()),
+        "json" => Ok((
+            [(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"hush-aliases-{}.json\"", date),
+            )],
+            Json(aliases),
+        )
+            .into_response()),
+        _ => Err(AppError::Validation("Format must be 'csv' or 'json'".to_string())),
+    }
+}
+
+fn write_csv(aliases: &[AliasExport]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for alias in aliases {
+        writer
+            .serialize(alias)
+            .map_err(|e| AppError::Internal(format!("Failed to write export: {}", e)))?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write export: {}", e)))
+}
+
+/// Deleted aliases; their addresses stay reserved to the user
+pub async fn list_deleted(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let aliases = AliasService::list_deleted(&pool, user.user_id).await?;
+
+    let response: Vec<serde_json::Value> = aliases
+        .into_iter()
+        .map(|a| {
+            serde_json::json!({
+                "id": a.id.to_string(),
+                "address": a.address,
+                "deleted_at": a.updated_at,
+                "created_at": a.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "aliases": response })))
+}
+
+pub async fn restore(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<AliasResponse>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let alias = AliasService::restore(&pool, alias_id, user.user_id).await?;
+
+    Ok(Json(AliasResponse {
+        id: alias.id.to_string(),
+        address: alias.address,
+        status: format!("{:?}", alias.status).to_lowercase(),
+        delivery_mode: alias.delivery_mode,
+        quarantine_when_paused: alias.quarantine_when_paused,
+        tracker_protection: alias.tracker_protection,
+        block_spam_tags: alias.block_spam_tags,
+        label: alias.label,
+        expires_at: alias.expires_at,
         created_at: alias.created_at,
     }))
 }
@@ -103,7 +447,9 @@ pub async fn logs(
 
     let limit = params.limit.unwrap_or(20);
 
-    let logs = AliasService::get_logs(&pool, alias_id, user.user_id, limit).await?;
+    let tag = params.tag.map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+
+    let logs = AliasService::get_logs(&pool, alias_id, user.user_id, tag.as_deref(), limit).await?;
 
     let response: Vec<serde_json::Value> = logs
         .into_iter()
@@ -1,21 +1,36 @@
-use axum::{extract::Extension, Json};
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::Deserialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::api::pages;
+use crate::api::targets::VerifyQuery;
 use crate::auth::{authenticate_user, hash_password, AuthConfig};
 use crate::error::{AppError, Result};
-use crate::models::{LoginRequest, LoginResponse, RefreshRequest};
+use crate::models::{LoginRequest, LoginResponse, RefreshRequest, ResendVerificationRequest};
 use crate::config::Config;
+use crate::rate_limit::ClientIp;
+use crate::services::{AccountService, EmailService};
+use crate::validation::normalize_email;
 
 pub async fn login(
     Extension(pool): Extension<PgPool>,
     Extension(config): Extension<Config>,
+    ClientIp(ip): ClientIp,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>> {
     // Normalize email
     let email = req.email.to_lowercase().trim().to_string();
-    let user = authenticate_user(&pool, &email, &req.password).await?;
+    let user = authenticate_user(&pool, &config, &email, &req.password, ip).await?;
+
+    if config.require_email_verification && !user.email_verified {
+        return Err(AppError::Auth("Email address has not been verified".to_string()));
+    }
 
     let auth_config = AuthConfig::new(config.jwt_secret.clone(), config.jwt_expires_in);
     let access_token = auth_config.encode_token(&user.id.to_string())?;
@@ -35,6 +50,7 @@ pub async fn login(
 }
 
 pub async fn refresh(
+    Extension(pool): Extension<PgPool>,
     Extension(config): Extension<Config>,
     Json(req): Json<RefreshRequest>,
 ) -> Result<Json<LoginResponse>> {
@@ -45,6 +61,20 @@ pub async fn refresh(
 
     let claims = refresh_auth_config.decode_token(&req.refresh_token)?;
 
+    // Device credentials are re-issued by pairing again, never refreshed into full sessions
+    if claims.did.is_some() {
+        return Err(AppError::Auth("Device tokens cannot be refreshed".to_string()));
+    }
+
+    // Accounts scheduled for deletion must sign in again, which cancels it
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Auth("Invalid user ID in token".to_string()))?;
+    if !AccountService::is_active(&pool, user_id).await? {
+        return Err(AppError::Auth(
+            "Account is scheduled for deletion. Sign in again to cancel it.".to_string(),
+        ));
+    }
+
     let auth_config = AuthConfig::new(config.jwt_secret.clone(), config.jwt_expires_in);
     let access_token = auth_config.encode_token(&claims.sub)?;
 
@@ -65,17 +95,8 @@ pub async fn register(
     Extension(pool): Extension<PgPool>,
     Extension(config): Extension<Config>,
     Json(req): Json<RegisterRequest>,
-) -> Result<Json<LoginResponse>> {
-    // Validate email format (basic validation)
-    if !req.email.contains('@') || !req.email.contains('.') {
-        return Err(AppError::Validation("Invalid email format".to_string()));
-    }
-    
-    // Email should be lowercase
-    let email = req.email.to_lowercase().trim().to_string();
-    if email.is_empty() {
-        return Err(AppError::Validation("Email cannot be empty".to_string()));
-    }
+) -> Result<Response> {
+    let email = normalize_email(&req.email)?;
 
     // Validate password length
     if req.password.len() < 8 {
@@ -83,17 +104,24 @@ pub async fn register(
     }
 
     // Check if user already exists
-    let existing = sqlx::query("SELECT id FROM users WHERE email = $1")
-        .bind(&email)
-        .fetch_optional(&pool)
-        .await?;
-
-    if existing.is_some() {
+    if AccountService::email_in_use(&pool, &email).await? {
         return Err(AppError::Validation("User already exists".to_string()));
     }
 
     // Hash password
-    let password_hash = hash_password(&req.password).await?;
+    let password_hash = hash_password(&config, &req.password).await?;
+
+    if config.require_email_verification {
+        // No tokens until the address is confirmed
+        let (_user_id, token) = AccountService::create_unverified(&pool, &email, &password_hash).await?;
+        EmailService::send_account_verification_email(&config, &email, &token).await?;
+
+        return Ok(Json(serde_json::json!({
+            "status": "verification_required",
+            "message": "verification_sent"
+        }))
+        .into_response());
+    }
 
     // Create user
     let user_id: Uuid = sqlx::query_scalar(
@@ -118,7 +146,46 @@ pub async fn register(
         access_token,
         refresh_token,
         expires_in: config.jwt_expires_in,
-    }))
+    })
+    .into_response())
+}
+
+/// Confirm an account email address via GET (for browser links)
+pub async fn verify_email(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<VerifyQuery>,
+) -> impl IntoResponse {
+    match AccountService::verify_email(&pool, &params.token).await {
+        Ok(email) => (
+            StatusCode::OK,
+            pages::verified_page(
+                "Account Confirmed!",
+                "Your account email address has been successfully verified.",
+                &email,
+                "You can now sign in to Hush.",
+            ),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            pages::verification_failed_page(&e.to_string()),
+        ),
+    }
+}
+
+/// Send a new account verification link. The response does not reveal
+/// whether the address is registered.
+pub async fn resend_verification(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    Json(req): Json<ResendVerificationRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let email = normalize_email(&req.email)?;
+
+    if let Some(token) = AccountService::renew_verification(&pool, &email).await? {
+        EmailService::send_account_verification_email(&config, &email, &token).await?;
+    }
+
+    Ok(Json(serde_json::json!({ "message": "verification_sent" })))
 }
 
 pub async fn logout() -> Result<Json<serde_json::Value>> {
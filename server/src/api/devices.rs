@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::{AuthConfig, AuthenticatedUser};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{
+    DeviceApproveRequest, DeviceCodeRequest, DeviceCodeResponse, DeviceTokenRequest,
+    DeviceTokenResponse,
+};
+use crate::services::DeviceService;
+
+/// Start a device authorization request (called by the extension)
+pub async fn request_code(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    Json(req): Json<DeviceCodeRequest>,
+) -> Result<Json<DeviceCodeResponse>> {
+    let client_name = req
+        .client_name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Browser extension".to_string());
+
+    if client_name.len() > 255 {
+        return Err(AppError::Validation("Client name is too long".to_string()));
+    }
+
+    let authorization = DeviceService::start(
+        &pool,
+        &client_name,
+        config.device_code_expires_in,
+        config.device_poll_interval,
+    )
+    .await?;
+
+    let verification_uri = format!("{}/device", config.app_base_url);
+
+    Ok(Json(DeviceCodeResponse {
+        verification_uri_complete: format!("{}?user_code={}", verification_uri, authorization.user_code),
+        verification_uri,
+        device_code: authorization.device_code,
+        user_code: authorization.user_code,
+        expires_in: config.device_code_expires_in,
+        interval: authorization.poll_interval as u64,
+    }))
+}
+
+/// Poll for the device credential once the user has approved the request
+pub async fn token(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    Json(req): Json<DeviceTokenRequest>,
+) -> Result<Json<DeviceTokenResponse>> {
+    let credential = DeviceService::poll(&pool, &req.device_code).await?;
+
+    let auth_config = AuthConfig::new(config.jwt_secret.clone(), config.device_token_expires_in);
+    let access_token = auth_config.encode_device_token(
+        &credential.user_id.to_string(),
+        &credential.id.to_string(),
+        &credential.scope,
+    )?;
+
+    Ok(Json(DeviceTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: config.device_token_expires_in,
+        scope: credential.scope,
+    }))
+}
+
+/// Approve or deny a pending device from the web dashboard
+pub async fn approve(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Json(req): Json<DeviceApproveRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let authorization = DeviceService::decide(&pool, &req.user_code, user.user_id, req.approve).await?;
+
+    Ok(Json(serde_json::json!({
+        "status": authorization.status,
+        "client_name": authorization.client_name,
+        "scope": authorization.scope
+    })))
+}
+
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let credentials = DeviceService::list_credentials(&pool, user.user_id).await?;
+
+    let response: Vec<serde_json::Value> = credentials
+        .into_iter()
+        .map(|c| {
+            serde_json::json!({
+                "id": c.id.to_string(),
+                "client_name": c.client_name,
+                "scope": c.scope,
+                "last_used_at": c.last_used_at,
+                "created_at": c.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "devices": response })))
+}
+
+pub async fn revoke(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    // A device may revoke its own credential (e.g. "unpair" in the extension)
+    let device_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid device ID".to_string()))?;
+
+    if user.device_id.is_some() && user.device_id != Some(device_id) {
+        return Err(AppError::Forbidden(
+            "This action requires a full login session".to_string(),
+        ));
+    }
+
+    DeviceService::revoke(&pool, device_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{CreateDirectoryRequest, Directory, SystemDomain, ToggleDirectoryRequest};
+use crate::services::{DirectoryService, SystemDomainService};
+
+/// Reserve a namespace. Mail to addresses inside it creates aliases.
+pub async fn create(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<CreateDirectoryRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let directory = DirectoryService::create(&pool, &config, user.user_id, &req.name).await?;
+
+    let domains = SystemDomainService::list_enabled(&pool).await?;
+
+    Ok(Json(directory_json(&directory, &domains)))
+}
+
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let directories = DirectoryService::list(&pool, user.user_id).await?;
+    let domains = SystemDomainService::list_enabled(&pool).await?;
+
+    let response: Vec<serde_json::Value> = directories.iter().map(|d| directory_json(d, &domains)).collect();
+
+    Ok(Json(serde_json::json!({ "directories": response })))
+}
+
+pub async fn toggle(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<ToggleDirectoryRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let directory = DirectoryService::toggle(&pool, parse_id(&id)?, user.user_id, req.enabled).await?;
+
+    let domains = SystemDomainService::list_enabled(&pool).await?;
+
+    Ok(Json(directory_json(&directory, &domains)))
+}
+
+/// Release the namespace. Aliases already created in it are kept.
+pub async fn delete(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    DirectoryService::delete(&pool, parse_id(&id)?, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Directory with the address patterns it accepts on each system domain
+fn directory_json(directory: &Directory, domains: &[SystemDomain]) -> serde_json::Value {
+    let addresses: Vec<String> = domains
+        .iter()
+        .flat_map(|d| {
+            [
+                format!("{}.*@{}", directory.name, d.domain),
+                format!("*@{}.{}", directory.name, d.domain),
+            ]
+        })
+        .collect();
+
+    serde_json::json!({
+        "id": directory.id.to_string(),
+        "name": directory.name,
+        "enabled": directory.enabled,
+        "addresses": addresses,
+        "created_at": directory.created_at
+    })
+}
+
+fn parse_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|_| AppError::Validation("Invalid directory ID".to_string()))
+}
@@ -0,0 +1,120 @@
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::config::Config;
+use crate::dns;
+use crate::error::{AppError, Result};
+use crate::models::{CreateDomainRequest, Domain, SetCatchAllRequest};
+use crate::services::{DomainService, SystemDomainService};
+
+/// Shared domains available for new aliases
+pub async fn list_system(
+    Extension(pool): Extension<PgPool>,
+    _user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let domains = SystemDomainService::list_enabled(&pool).await?;
+
+    let response: Vec<serde_json::Value> = domains
+        .into_iter()
+        .map(|d| serde_json::json!({ "domain": d.domain, "is_default": d.is_default }))
+        .collect();
+
+    Ok(Json(serde_json::json!({ "system_domains": response })))
+}
+
+/// Register a domain. The response lists the DNS records to publish.
+pub async fn create(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<CreateDomainRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let domain = DomainService::create(&pool, &config, user.user_id, &req.domain, req.catch_all).await?;
+
+    Ok(Json(domain_json(&config, &domain)))
+}
+
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let domains = DomainService::list(&pool, user.user_id).await?;
+
+    let response: Vec<serde_json::Value> = domains.iter().map(|d| domain_json(&config, d)).collect();
+
+    Ok(Json(serde_json::json!({ "domains": response })))
+}
+
+pub async fn get(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let domain = DomainService::get_by_id(&pool, parse_id(&id)?, user.user_id).await?;
+
+    Ok(Json(domain_json(&config, &domain)))
+}
+
+/// Check the domain's DNS records and verify ownership
+pub async fn verify(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let resolver = dns::resolver(&config);
+    let (domain, check) =
+        DomainService::verify(&pool, &config, resolver.as_ref(), parse_id(&id)?, user.user_id).await?;
+
+    let mut response = domain_json(&config, &domain);
+    response["checks"] = serde_json::json!(check);
+
+    Ok(Json(response))
+}
+
+pub async fn set_catch_all(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Json(req): Json<SetCatchAllRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let domain = DomainService::set_catch_all(&pool, parse_id(&id)?, user.user_id, req.catch_all).await?;
+
+    Ok(Json(domain_json(&config, &domain)))
+}
+
+/// Remove the domain. Its aliases are deleted.
+pub async fn delete(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    DomainService::delete(&pool, parse_id(&id)?, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+fn domain_json(config: &Config, domain: &Domain) -> serde_json::Value {
+    serde_json::json!({
+        "id": domain.id.to_string(),
+        "domain": domain.domain,
+        "verified": domain.verified_at.is_some(),
+        "verified_at": domain.verified_at,
+        "catch_all": domain.catch_all,
+        "last_checked_at": domain.last_checked_at,
+        "records": DomainService::records(config, domain),
+        "created_at": domain.created_at
+    })
+}
+
+fn parse_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|_| AppError::Validation("Invalid domain ID".to_string()))
+}
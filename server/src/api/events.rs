@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Extension, Query},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, Result};
+use crate::events::{EventBus, LogNotification};
+use crate::models::LogEvent;
+use crate::services::AliasService;
+
+/// Entries fetched per query when catching up
+const BATCH_SIZE: i64 = 100;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Streams re-check the database this often in case a notification was
+/// missed, e.g. while the listener connection was re-established
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sequence numbers are assigned when an entry is inserted but it only
+/// becomes visible at commit, so a slow transaction can commit an entry below
+/// a stream's position. Entries created this recently are looked for again.
+const RESCAN_WINDOW_SECS: i64 = 120;
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Comma-separated alias IDs; defaults to all of the user's aliases
+    pub aliases: Option<String>,
+    /// For clients that cannot set the `Last-Event-ID` header
+    pub last_event_id: Option<i64>,
+}
+
+struct StreamState {
+    pool: PgPool,
+    user_id: Uuid,
+    alias_ids: Option<Vec<Uuid>>,
+    /// Highest sequence number sent so far
+    last_seq: i64,
+    /// Entries within the rescan window already sent, or already committed
+    /// when the stream started
+    recent: Vec<(i64, DateTime<Utc>)>,
+    pending: VecDeque<LogEvent>,
+    /// The last fetch was a full batch, so more entries may be waiting
+    more: bool,
+    receiver: broadcast::Receiver<LogNotification>,
+    catch_up: Interval,
+}
+
+/// Parse a comma-separated list of alias IDs; empty means no restriction
+pub fn parse_alias_ids(list: Option<&str>) -> Result<Option<Vec<Uuid>>> {
+    match list.filter(|s| !s.trim().is_empty()) {
+        Some(list) => Ok(Some(
+            list.split(',')
+                .map(|id| {
+                    Uuid::parse_str(id.trim())
+                        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Server-Sent Events stream of new email log entries. Each event's ID is
+/// the stream position; reconnecting with `Last-Event-ID` replays everything
+/// written since, plus recent entries that may have committed late, so
+/// clients should ignore entry IDs they have already seen.
+pub async fn stream(
+    Extension(pool): Extension<PgPool>,
+    Extension(bus): Extension<EventBus>,
+    user: AuthenticatedUser,
+    headers: HeaderMap,
+    Query(params): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let alias_ids = parse_alias_ids(params.aliases.as_deref())?;
+
+    let resume_from = headers
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.trim().parse::<i64>().ok())
+        .or(params.last_event_id);
+
+    // Subscribe before reading the current position so nothing written in
+    // between is lost
+    let receiver = bus.subscribe();
+    let (last_seq, recent, more) = match resume_from {
+        Some(seq) => (seq, Vec::new(), true),
+        None => {
+            let seq = AliasService::latest_log_seq(&pool).await?;
+            let recent =
+                AliasService::recent_log_seqs(&pool, user.user_id, alias_ids.as_deref(), rescan_since(), seq)
+                    .await?;
+            (seq, recent, false)
+        }
+    };
+
+    let mut catch_up = tokio::time::interval(CATCH_UP_INTERVAL);
+    catch_up.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    catch_up.reset();
+
+    let state = StreamState {
+        pool,
+        user_id: user.user_id,
+        alias_ids,
+        last_seq,
+        recent,
+        pending: VecDeque::new(),
+        more,
+        receiver,
+        catch_up,
+    };
+
+    let events = stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(entry) = state.pending.pop_front() {
+                state.last_seq = state.last_seq.max(entry.seq);
+                state.recent.push((entry.seq, entry.created_at));
+                return Some((Ok(to_event(&entry, state.last_seq)), state));
+            }
+
+            if !state.more && !wait_for_entries(&mut state).await {
+                return None;
+            }
+
+            let since = rescan_since();
+            state.recent.retain(|(_, created_at)| *created_at > since);
+            let exclude: Vec<i64> = state.recent.iter().map(|(seq, _)| *seq).collect();
+
+            match AliasService::get_logs_since(
+                &state.pool,
+                state.user_id,
+                state.alias_ids.as_deref(),
+                state.last_seq,
+                since,
+                &exclude,
+                BATCH_SIZE,
+            )
+            .await
+            {
+                Ok(entries) => {
+                    state.more = entries.len() as i64 == BATCH_SIZE;
+                    state.pending.extend(entries);
+                }
+                Err(e) => {
+                    // End the stream; the client reconnects with Last-Event-ID
+                    warn!("Event stream for user {} failed: {}", state.user_id, e);
+                    return None;
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL)))
+}
+
+/// Wait until there may be new entries for this user. Returns false when the
+/// event bus has shut down.
+async fn wait_for_entries(state: &mut StreamState) -> bool {
+    loop {
+        tokio::select! {
+            received = state.receiver.recv() => match received {
+                // Notifications are sent at commit, including for entries
+                // below the stream position
+                Ok(notification) => {
+                    if notification.user_id == Some(state.user_id)
+                        && !state.recent.iter().any(|(seq, _)| *seq == notification.seq)
+                    {
+                        return true;
+                    }
+                }
+                // Dropped notifications may have been ours
+                Err(RecvError::Lagged(_)) => return true,
+                Err(RecvError::Closed) => return false,
+            },
+            _ = state.catch_up.tick() => return true,
+        }
+    }
+}
+
+fn rescan_since() -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::seconds(RESCAN_WINDOW_SECS)
+}
+
+fn to_event(entry: &LogEvent, position: i64) -> Event {
+    let data = serde_json::json!({
+        "id": entry.id.to_string(),
+        "alias_id": entry.alias_id.to_string(),
+        "alias": entry.alias_address,
+        "from": entry.from_email,
+        "subject": entry.subject,
+        "status": format!("{:?}", entry.status).to_lowercase(),
+        "time": entry.created_at,
+        "metadata": entry.metadata
+    });
+
+    Event::default()
+        .id(position.to_string())
+        .event("email_log")
+        .data(data.to_string())
+}
@@ -0,0 +1,35 @@
+use axum::{
+    extract::{Extension, Query},
+    http::header,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::services::ImageProxyService;
+
+#[derive(Deserialize)]
+pub struct ImageProxyQuery {
+    pub url: String,
+    pub sig: String,
+}
+
+/// Serve a remote image referenced by a forwarded message. Only URLs signed
+/// by this server are fetched.
+pub async fn proxy(
+    Extension(config): Extension<Config>,
+    Query(params): Query<ImageProxyQuery>,
+) -> Result<impl IntoResponse> {
+    let image = ImageProxyService::fetch(&config, &params.url, &params.sig).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, image.content_type),
+            (header::CACHE_CONTROL, "private, max-age=86400".to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; sandbox".to_string()),
+        ],
+        image.body,
+    ))
+}
@@ -8,9 +8,18 @@ use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::crypto::sender_hash;
 use crate::error::Result;
-use crate::models::EmailStatus;
-use crate::services::{AliasService, EmailService, TargetService};
+use crate::models::{Alias, DeliveryMode, EmailStatus};
+use crate::services::push_service::PushEvent;
+use crate::services::alias_service::split_subaddress;
+use crate::services::quarantine_service::{dmarc_failed, QuarantineReason, StoredMessage};
+use crate::services::spam_service::SpamAction;
+use crate::services::{
+    AliasService, DigestService, DirectoryService, DomainService, EmailService, LogService, PushService,
+    QuarantineService, SpamService, TargetService, WebhookService,
+};
+use crate::spam::{InboundMessage, SenderReputation};
 
 /// Webhook payload from email provider (Mailgun/SendGrid format)
 #[derive(Debug, Deserialize)]
@@ -45,6 +54,7 @@ pub struct IncomingEmailWebhook {
     
     /// Attachment count
     #[serde(rename = "attachment-count", default)]
+    #[allow(dead_code)]
     pub attachment_count: Option<u32>,
 }
 
@@ -93,20 +103,164 @@ async fn process_incoming_email(
     let recipient = payload.recipient.to_lowercase().trim().to_string();
     let sender = payload.sender.to_lowercase().trim().to_string();
 
+    // alias+tag@domain is delivered to alias@domain unless an alias owns the
+    // full address
+    let (address, tag) = match config
+        .subaddress_separator
+        .as_deref()
+        .and_then(|separator| split_subaddress(&recipient, separator))
+    {
+        Some((base, tag)) if AliasService::find_by_address(&pool, &recipient).await?.is_none() => {
+            (base, Some(tag))
+        }
+        _ => (recipient.clone(), None),
+    };
+
     // Find alias by recipient address
-    let alias = match AliasService::find_by_address(&pool, &recipient).await? {
+    let alias = match AliasService::find_by_address(&pool, &address).await? {
         Some(a) => a,
         None => {
-            warn!("No active alias found for: {}", recipient);
-            return Ok(Json(serde_json::json!({
-                "status": "ignored",
-                "reason": "alias_not_found"
-            })));
+            if let Some(paused) =
+                AliasService::find_quarantining_by_address(&pool, &address).await?
+            {
+                return quarantine_incoming(
+                    &pool,
+                    &config,
+                    &paused,
+                    &sender,
+                    &payload,
+                    QuarantineReason::PausedAlias,
+                    serde_json::json!({ "tag": tag }),
+                )
+                .await;
+            }
+
+            let created = match DomainService::catch_all_alias(&pool, &config, &address).await? {
+                Some(created) => Some(created),
+                None => DirectoryService::directory_alias(&pool, &config, &address).await?,
+            };
+
+            match created {
+                Some(created) => created,
+                None => {
+                    warn!("No active alias found for: {}", recipient);
+                    return Ok(Json(serde_json::json!({
+                        "status": "ignored",
+                        "reason": "alias_not_found"
+                    })));
+                }
+            }
         }
     };
 
     info!("Found alias: {} (user_id: {})", alias.address, alias.user_id);
 
+    if let Some(tag) = tag.as_deref() {
+        if AliasService::is_tag_blocked(&pool, alias.id, tag).await? {
+            info!("Rejected mail to blocked tag {} on {}", tag, alias.address);
+            log_email(
+                &pool,
+                &config,
+                &alias,
+                &sender,
+                &payload.subject,
+                EmailStatus::Rejected,
+                Some(serde_json::json!({
+                    "reason": "tag_blocked",
+                    "tag": tag
+                })),
+            )
+            .await?;
+
+            emit_mail_event(
+                &pool,
+                &alias,
+                "email.rejected",
+                &sender,
+                &payload.subject,
+                Some("tag_blocked"),
+            )
+            .await;
+
+            return Ok(Json(serde_json::json!({
+                "status": "rejected",
+                "reason": "tag_blocked"
+            })));
+        }
+    }
+
+    if config.quarantine_auth_failures && dmarc_failed(payload.message_headers.as_deref()) {
+        return quarantine_incoming(
+            &pool,
+            &config,
+            &alias,
+            &sender,
+            &payload,
+            QuarantineReason::AuthFailure,
+            serde_json::json!({ "tag": tag }),
+        )
+        .await;
+    }
+
+    let headers: Vec<(String, String)> = payload
+        .message_headers
+        .as_deref()
+        .and_then(|h| serde_json::from_str(h).ok())
+        .unwrap_or_default();
+    let verdict = SpamService::evaluate(
+        &pool,
+        &config,
+        &alias,
+        InboundMessage {
+            sender: &sender,
+            recipient: &recipient,
+            subject: &payload.subject,
+            body_plain: payload.body_plain.as_deref(),
+            body_html: payload.body_html.as_deref(),
+            message_id: payload.message_id.as_deref(),
+            headers: &headers,
+            reputation: SenderReputation::default(),
+        },
+    )
+    .await?;
+    info!(
+        "Spam score for {}: {:.1} ({})",
+        recipient,
+        verdict.score,
+        verdict.action.as_str()
+    );
+
+    // Spam to a tag usually means the address leaked from the site it was given to
+    if let Some(tag) = tag.as_deref() {
+        if alias.block_spam_tags
+            && verdict.action != SpamAction::Forward
+            && AliasService::auto_block_tag(&pool, alias.id, tag).await?
+        {
+            info!("Blocked tag {} on {} after spam", tag, alias.address);
+        }
+    }
+
+    if verdict.action == SpamAction::Quarantine {
+        return quarantine_incoming(
+            &pool,
+            &config,
+            &alias,
+            &sender,
+            &payload,
+            QuarantineReason::Spam,
+            serde_json::json!({ "spam": verdict, "tag": tag }),
+        )
+        .await;
+    }
+
+    // Tagged spam is still delivered, but marked for the user's mail filters
+    let tagged = verdict.action == SpamAction::Tag;
+    let forward_subject = if tagged {
+        format!("[SPAM] {}", payload.subject)
+    } else {
+        payload.subject.clone()
+    };
+
     // Get target email for the user
     let target = match TargetService::get_current(&pool, alias.user_id).await? {
         Some(t) if t.verified => t,
@@ -115,16 +269,28 @@ async fn process_incoming_email(
             // Log as rejected
             log_email(
                 &pool,
-                alias.id,
+                &config,
+                &alias,
                 &sender,
                 &payload.subject,
                 EmailStatus::Rejected,
                 Some(serde_json::json!({
-                    "reason": "target_email_not_verified"
+                    "reason": "target_email_not_verified",
+                    "tag": tag
                 })),
             )
             .await?;
 
+            emit_mail_event(
+                &pool,
+                &alias,
+                "email.rejected",
+                &sender,
+                &payload.subject,
+                Some("target_email_not_verified"),
+            )
+            .await;
+
             return Ok(Json(serde_json::json!({
                 "status": "rejected",
                 "reason": "target_email_not_verified"
@@ -135,16 +301,28 @@ async fn process_incoming_email(
             // Log as rejected
             log_email(
                 &pool,
-                alias.id,
+                &config,
+                &alias,
                 &sender,
                 &payload.subject,
                 EmailStatus::Rejected,
                 Some(serde_json::json!({
-                    "reason": "no_target_email"
+                    "reason": "no_target_email",
+                    "tag": tag
                 })),
             )
             .await?;
 
+            emit_mail_event(
+                &pool,
+                &alias,
+                "email.rejected",
+                &sender,
+                &payload.subject,
+                Some("no_target_email"),
+            )
+            .await;
+
             return Ok(Json(serde_json::json!({
                 "status": "rejected",
                 "reason": "no_target_email"
@@ -152,15 +330,42 @@ async fn process_incoming_email(
         }
     };
 
+    // Digest aliases keep the message for the next scheduled summary
+    if alias.delivery_mode != DeliveryMode::Immediate {
+        DigestService::hold(
+            &pool,
+            &config,
+            &alias,
+            &sender,
+            &forward_subject,
+            payload.body_plain.as_deref(),
+            payload.body_html.as_deref(),
+            payload.message_id.as_deref(),
+            tag.as_deref(),
+        )
+        .await?;
+
+        info!("Held email for {} digest: {}", alias.delivery_mode.as_str(), recipient);
+        return Ok(Json(serde_json::json!({
+            "status": "held",
+            "delivery_mode": alias.delivery_mode.as_str()
+        })));
+    }
+
+    // Remove trackers before anything reaches the user's mail client
+    let (body_html, trackers) =
+        EmailService::sanitize_html(&config, alias.tracker_protection, payload.body_html.as_deref());
+
     // Forward email to target address
     let forward_result = EmailService::forward_email(
         &config,
         &sender,
-        &target.email,
-        &payload.subject,
+        &target,
+        &forward_subject,
         payload.body_plain.as_deref(),
-        payload.body_html.as_deref(),
+        body_html.as_deref(),
         Some(&sender), // Reply-To should be original sender
+        tagged.then(|| verdict.header()).as_deref(),
     )
     .await;
 
@@ -168,20 +373,46 @@ async fn process_incoming_email(
         Ok(_) => {
             info!("Email forwarded successfully: {} -> {}", recipient, target.email);
 
+            if is_new_sender(&pool, &config, alias.id, &sender).await? {
+                PushService::spawn_notify(
+                    &pool,
+                    &config,
+                    alias.user_id,
+                    PushEvent::NewSender,
+                    "New sender".to_string(),
+                    format!("{} wrote to {} for the first time", sender, alias.address),
+                    serde_json::json!({ "alias_id": alias.id, "from": sender, "subject": payload.subject }),
+                );
+            }
+
             // Log as forwarded
             log_email(
                 &pool,
-                alias.id,
+                &config,
+                &alias,
                 &sender,
                 &payload.subject,
                 EmailStatus::Forwarded,
                 Some(serde_json::json!({
                     "target_email": target.email,
-                    "message_id": payload.message_id
+                    "message_id": payload.message_id,
+                    "trackers": trackers,
+                    "spam": verdict,
+                    "tag": tag
                 })),
             )
             .await?;
 
+            emit_mail_event(
+                &pool,
+                &alias,
+                "email.forwarded",
+                &sender,
+                &payload.subject,
+                None,
+            )
+            .await;
+
             Ok(Json(serde_json::json!({
                 "status": "forwarded",
                 "target": target.email
@@ -190,20 +421,43 @@ async fn process_incoming_email(
         Err(e) => {
             error!("Failed to forward email: {}", e);
 
+            PushService::spawn_notify(
+                &pool,
+                &config,
+                alias.user_id,
+                PushEvent::Bounce,
+                "Delivery failed".to_string(),
+                format!("A message from {} to {} could not be delivered", sender, alias.address),
+                serde_json::json!({ "alias_id": alias.id, "from": sender, "subject": payload.subject }),
+            );
+
             // Log as pending (will retry later)
             log_email(
                 &pool,
-                alias.id,
+                &config,
+                &alias,
                 &sender,
                 &payload.subject,
                 EmailStatus::Pending,
                 Some(serde_json::json!({
                     "error": e.to_string(),
-                    "target_email": target.email
+                    "target_email": target.email,
+                    "spam": verdict,
+                    "tag": tag
                 })),
             )
             .await?;
 
+            emit_mail_event(
+                &pool,
+                &alias,
+                "email.bounced",
+                &sender,
+                &payload.subject,
+                None,
+            )
+            .await;
+
             Err(e)
         }
     }
@@ -227,80 +481,108 @@ pub async fn handle_sendgrid_webhook(
         attachment_count: None,
     };
 
-    // Use form extractor by converting to Form
-    // For now, we'll process it directly
-    let recipient = incoming.recipient.to_lowercase().trim().to_string();
-    let sender = incoming.sender.to_lowercase().trim().to_string();
-
-    let alias = match AliasService::find_by_address(&pool, &recipient).await? {
-        Some(a) => a,
-        None => {
-            return Ok(Json(serde_json::json!({
-                "status": "ignored",
-                "reason": "alias_not_found"
-            })));
-        }
-    };
+    process_incoming_email(pool, config, incoming).await
+}
 
-    let target = match TargetService::get_current(&pool, alias.user_id).await? {
-        Some(t) if t.verified => t,
-        _ => {
-            return Ok(Json(serde_json::json!({
-                "status": "rejected",
-                "reason": "target_not_verified"
-            })));
-        }
+/// Store the message in quarantine instead of forwarding it
+async fn quarantine_incoming(
+    pool: &PgPool,
+    config: &Config,
+    alias: &Alias,
+    sender: &str,
+    payload: &IncomingEmailWebhook,
+    reason: QuarantineReason,
+    metadata: serde_json::Value,
+) -> Result<Json<serde_json::Value>> {
+    let message = StoredMessage {
+        body_plain: payload.body_plain.clone(),
+        body_html: payload.body_html.clone(),
+        headers: payload.message_headers.clone(),
+        message_id: payload.message_id.clone(),
     };
 
-    EmailService::forward_email(
-        &config,
-        &sender,
-        &target.email,
-        &incoming.subject,
-        incoming.body_plain.as_deref(),
-        incoming.body_html.as_deref(),
-        Some(&sender),
-    )
-    .await?;
-
-    log_email(
-        &pool,
-        alias.id,
-        &sender,
-        &incoming.subject,
-        EmailStatus::Forwarded,
-        Some(serde_json::json!({
-            "target_email": target.email
-        })),
+    QuarantineService::quarantine(
+        pool,
+        config,
+        alias,
+        sender,
+        &payload.subject,
+        &message,
+        reason,
+        metadata,
     )
     .await?;
 
     Ok(Json(serde_json::json!({
-        "status": "forwarded",
-        "target": target.email
+        "status": "quarantined",
+        "reason": reason.as_str()
     })))
 }
 
-/// Log email event to database
+/// Whether the alias has never received mail from this sender before
+async fn is_new_sender(pool: &PgPool, config: &Config, alias_id: Uuid, sender: &str) -> Result<bool> {
+    let seen = sqlx::query("SELECT id FROM email_logs WHERE alias_id = $1 AND sender_hash = $2 LIMIT 1")
+        .bind(alias_id)
+        .bind(sender_hash(config, sender))
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(seen.is_none())
+}
+
+/// Queue a user webhook event for a processed message. Failing to queue it
+/// must not affect mail handling, so errors are only logged.
+async fn emit_mail_event(
+    pool: &PgPool,
+    alias: &Alias,
+    event_type: &str,
+    from_email: &str,
+    subject: &str,
+    reason: Option<&str>,
+) {
+    let data = serde_json::json!({
+        "alias_id": alias.id,
+        "alias": alias.address,
+        "from": from_email,
+        "subject": subject,
+        "reason": reason
+    });
+
+    if let Err(e) = WebhookService::emit(pool, alias.user_id, event_type, data).await {
+        error!("Failed to queue {} webhook: {}", event_type, e);
+    }
+}
+
+/// Log email event to database. With privacy mode on, only a keyed hash of
+/// the sender is stored and no subject.
 async fn log_email(
     pool: &PgPool,
-    alias_id: Uuid,
+    config: &Config,
+    alias: &Alias,
     from_email: &str,
     subject: &str,
     status: EmailStatus,
     metadata: Option<serde_json::Value>,
 ) -> Result<()> {
+    let hash = sender_hash(config, from_email);
+    let (from_email, subject) = if LogService::privacy_mode(pool, alias.user_id).await? {
+        ("", "")
+    } else {
+        (from_email, subject)
+    };
+
     sqlx::query(
         r#"
-        INSERT INTO email_logs (alias_id, from_email, subject, status, metadata)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO email_logs (alias_id, from_email, subject, status, metadata, sender_hash)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
     )
-    .bind(alias_id)
+    .bind(alias.id)
     .bind(from_email)
     .bind(subject)
     .bind(&status)
     .bind(metadata)
+    .bind(hash)
     .execute(pool)
     .await?;
 
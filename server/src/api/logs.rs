@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Extension, Query},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::api::events::parse_alias_ids;
+use crate::auth::AuthenticatedUser;
+use crate::config::Config;
+use crate::crypto::sender_hash;
+use crate::error::{AppError, Result};
+use crate::models::{EmailStatus, LogFilter};
+use crate::services::AliasService;
+
+#[derive(Deserialize)]
+pub struct LogSearchQuery {
+    /// Comma-separated alias IDs; defaults to all of the user's aliases
+    pub aliases: Option<String>,
+    pub status: Option<EmailStatus>,
+    pub sender: Option<String>,
+    pub tag: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Full-text search on the subject
+    pub q: Option<String>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Email logs across all of the user's aliases, newest first
+pub async fn search(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Query(params): Query<LogSearchQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let before_seq = match params.cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(cursor) => Some(
+            cursor
+                .parse::<i64>()
+                .map_err(|_| AppError::Validation("Invalid cursor".to_string()))?,
+        ),
+        None => None,
+    };
+
+    if let (Some(since), Some(until)) = (params.since, params.until) {
+        if since >= until {
+            return Err(AppError::Validation("'since' must be before 'until'".to_string()));
+        }
+    }
+
+    let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let sender = non_empty(params.sender);
+    let filter = LogFilter {
+        alias_ids: parse_alias_ids(params.aliases.as_deref())?,
+        status: params.status,
+        sender_hash: sender
+            .as_deref()
+            .filter(|s| s.contains('@'))
+            .map(|s| sender_hash(&config, s)),
+        sender,
+        tag: non_empty(params.tag).map(|t| t.to_lowercase()),
+        since: params.since,
+        until: params.until,
+        query: non_empty(params.q),
+    };
+
+    let (logs, next) =
+        AliasService::search_logs(&pool, user.user_id, &filter, before_seq, params.limit.unwrap_or(50)).await?;
+
+    let response: Vec<serde_json::Value> = logs
+        .into_iter()
+        .map(|entry| {
+            serde_json::json!({
+                "id": entry.id.to_string(),
+                "alias_id": entry.alias_id.to_string(),
+                "alias": entry.alias_address,
+                "from": entry.from_email,
+                "subject": entry.subject,
+                "status": format!("{:?}", entry.status).to_lowercase(),
+                "time": entry.created_at,
+                "metadata": entry.metadata
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "logs": response,
+        "next_cursor": next.map(|seq| seq.to_string())
+    })))
+}
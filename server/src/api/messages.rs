@@ -0,0 +1,70 @@
+use axum::{
+    extract::{Extension, Form, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::api::pages;
+use crate::config::Config;
+use crate::services::DigestService;
+
+#[derive(Deserialize)]
+pub struct ReleaseQuery {
+    pub token: String,
+}
+
+/// Page behind the link in a digest email, asking to confirm the delivery
+pub async fn confirm_release(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<ReleaseQuery>,
+) -> impl IntoResponse {
+    match DigestService::held_by_token(&pool, &params.token).await {
+        Ok(message) => (
+            StatusCode::OK,
+            pages::confirm_page(
+                "Deliver Message",
+                &format!("Deliver \"{}\" from {} to your inbox?", message.subject, message.from_email),
+                "/api/v1/messages/release",
+                &params.token,
+                "Deliver",
+            ),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            pages::failed_page(
+                "Delivery Failed",
+                &e.to_string(),
+                "The message may already have been delivered or is no longer stored.",
+            ),
+        ),
+    }
+}
+
+/// Deliver a held message once the digest link was confirmed
+pub async fn release(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    Form(params): Form<ReleaseQuery>,
+) -> impl IntoResponse {
+    match DigestService::release(&pool, &config, &params.token).await {
+        Ok((message, target_email)) => (
+            StatusCode::OK,
+            pages::verified_page(
+                "Message Delivered",
+                &format!("\"{}\" from {} has been forwarded to your inbox.", message.subject, message.from_email),
+                &target_email,
+                "Other messages in your digest are still held until you deliver them.",
+            ),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            pages::failed_page(
+                "Delivery Failed",
+                &e.to_string(),
+                "The message may already have been delivered or is no longer stored.",
+            ),
+        ),
+    }
+}
@@ -1,6 +1,19 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
 pub mod aliases;
 pub mod targets;
 pub mod notifications;
+pub mod quarantine;
 pub mod incoming;
+pub mod devices;
+pub mod directories;
+pub mod domains;
+pub mod events;
+pub mod images;
+pub mod logs;
+pub mod messages;
+pub mod stats;
+pub mod webhooks;
 
+pub mod pages;
@@ -1,29 +1,138 @@
-use axum::{extract::Extension, Json};
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
 use serde::Deserialize;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::auth::AuthenticatedUser;
-use crate::error::Result;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::services::push_service::{PushEvent, SubscriptionInput};
+use crate::services::PushService;
+use crate::webpush::{self, VapidKey};
 
+/// Browser `PushSubscription` keys
+#[derive(Deserialize)]
+pub struct SubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// Body mirrors `PushSubscription.toJSON()` plus device details
 #[derive(Deserialize)]
-#[allow(dead_code)]
 pub struct SubscribeRequest {
-    pub push_token: Option<String>,
+    pub endpoint: String,
+    pub keys: SubscriptionKeys,
     pub platform: String,
+    pub device_name: Option<String>,
+    /// Events to notify about; defaults to all
+    pub events: Option<Vec<String>>,
 }
 
 pub async fn subscribe(
-    Extension(_pool): Extension<PgPool>,
-    _user: AuthenticatedUser,
-    Json(_req): Json<SubscribeRequest>,
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Json(req): Json<SubscribeRequest>,
 ) -> Result<Json<serde_json::Value>> {
-    // TODO: Implement push notification subscription
-    // This would typically store the push token in the database
-    // and integrate with a push notification service (e.g., Firebase Cloud Messaging)
-    
+    // Push services are public HTTPS endpoints; anything else would let a
+    // user point server-side requests at the internal network
+    let endpoint = reqwest::Url::parse(&req.endpoint)
+        .map_err(|_| AppError::Validation("Invalid push endpoint".to_string()))?;
+    if endpoint.scheme() != "https" {
+        return Err(AppError::Validation("Push endpoint must be an HTTPS URL".to_string()));
+    }
+    if crate::http::is_private_host(&endpoint) {
+        return Err(AppError::Validation("Push endpoint must be publicly reachable".to_string()));
+    }
+
+    // Reject keys we could never encrypt to
+    let p256dh = webpush::decode_base64(&req.keys.p256dh)
+        .map_err(|_| AppError::Validation("Invalid p256dh key".to_string()))?;
+    let auth = webpush::decode_base64(&req.keys.auth)
+        .map_err(|_| AppError::Validation("Invalid auth secret".to_string()))?;
+    if p256dh.len() != 65 || auth.len() != 16 {
+        return Err(AppError::Validation("Invalid subscription keys".to_string()));
+    }
+
+    let events = match req.events {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                PushEvent::parse(name)
+                    .ok_or_else(|| AppError::Validation(format!("Unknown event: {}", name)))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => PushEvent::ALL.to_vec(),
+    };
+
+    let subscription = PushService::subscribe(
+        &pool,
+        user.user_id,
+        SubscriptionInput {
+            endpoint: req.endpoint,
+            p256dh: req.keys.p256dh,
+            auth: req.keys.auth,
+            platform: req.platform,
+            device_name: req.device_name,
+            events,
+        },
+    )
+    .await?;
+
     Ok(Json(serde_json::json!({
         "message": "Subscription saved",
-        "status": "success"
+        "status": "success",
+        "id": subscription.id.to_string()
     })))
 }
 
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let subscriptions = PushService::list(&pool, user.user_id).await?;
+
+    let response: Vec<serde_json::Value> = subscriptions
+        .into_iter()
+        .map(|s| {
+            serde_json::json!({
+                "id": s.id.to_string(),
+                "platform": s.platform,
+                "device_name": s.device_name,
+                "events": s.events,
+                "last_success_at": s.last_success_at,
+                "created_at": s.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "subscriptions": response })))
+}
+
+pub async fn unsubscribe(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let subscription_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid subscription ID".to_string()))?;
+
+    PushService::unsubscribe(&pool, subscription_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Application server key for `pushManager.subscribe()`
+pub async fn vapid_public_key(
+    Extension(config): Extension<Config>,
+) -> Result<Json<serde_json::Value>> {
+    let private_key = config
+        .vapid_private_key
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("Push notifications are not configured".to_string()))?;
+    let vapid = VapidKey::from_base64(private_key)?;
+
+    Ok(Json(serde_json::json!({ "public_key": vapid.public_key() })))
+}
@@ -0,0 +1,227 @@
+use axum::response::Html;
+
+/// Simple HTML escaping function
+pub fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#x27;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Page shown after successfully following an email verification link
+pub fn verified_page(heading: &str, message: &str, email: &str, note: &str) -> Html<String> {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{} - Hush</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Oxygen, Ubuntu, Cantarell, sans-serif;
+            display: flex;
+            justify-content: center;
+            align-items: center;
+            min-height: 100vh;
+            margin: 0;
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: #333;
+        }}
+        .container {{
+            background: white;
+            padding: 2rem;
+            border-radius: 12px;
+            box-shadow: 0 10px 40px rgba(0,0,0,0.2);
+            text-align: center;
+            max-width: 500px;
+            width: 90%;
+        }}
+        .success-icon {{
+            font-size: 4rem;
+            margin-bottom: 1rem;
+        }}
+        h1 {{
+            color: #667eea;
+            margin-bottom: 1rem;
+        }}
+        p {{
+            color: #666;
+            line-height: 1.6;
+            margin-bottom: 0.5rem;
+        }}
+        .email {{
+            font-weight: bold;
+            color: #333;
+            word-break: break-all;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="success-icon">✓</div>
+        <h1>{}</h1>
+        <p>{}</p>
+        <p>Email: <span class="email">{}</span></p>
+        <p>{}</p>
+    </div>
+</body>
+</html>"#,
+        escape_html(heading),
+        escape_html(heading),
+        escape_html(message),
+        escape_html(email),
+        escape_html(note)
+    );
+    Html(html)
+}
+
+/// Page shown when a verification link is invalid or expired
+pub fn verification_failed_page(error: &str) -> Html<String> {
+    failed_page(
+        "Verification Failed",
+        error,
+        "The verification link may have expired or is invalid.",
+    )
+}
+
+/// Page shown when an emailed action link could not be completed
+pub fn failed_page(heading: &str, error: &str, hint: &str) -> Html<String> {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{} - Hush</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Oxygen, Ubuntu, Cantarell, sans-serif;
+            display: flex;
+            justify-content: center;
+            align-items: center;
+            min-height: 100vh;
+            margin: 0;
+            background: linear-gradient(135deg, #f093fb 0%, #f5576c 100%);
+            color: #333;
+        }}
+        .container {{
+            background: white;
+            padding: 2rem;
+            border-radius: 12px;
+            box-shadow: 0 10px 40px rgba(0,0,0,0.2);
+            text-align: center;
+            max-width: 500px;
+            width: 90%;
+        }}
+        .error-icon {{
+            font-size: 4rem;
+            margin-bottom: 1rem;
+        }}
+        h1 {{
+            color: #f5576c;
+            margin-bottom: 1rem;
+        }}
+        p {{
+            color: #666;
+            line-height: 1.6;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="error-icon">✗</div>
+        <h1>{}</h1>
+        <p>{}</p>
+        <p>{}</p>
+    </div>
+</body>
+</html>"#,
+        escape_html(heading),
+        escape_html(heading),
+        escape_html(error),
+        escape_html(hint)
+    );
+    Html(html)
+}
+
+/// Page asking the reader to confirm an emailed action. Links in mail are
+/// fetched by security scanners, so the action itself needs a POST of
+/// `token` to `action`.
+pub fn confirm_page(heading: &str, message: &str, action: &str, token: &str, button: &str) -> Html<String> {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <meta name="robots" content="noindex">
+    <title>{} - Hush</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, Oxygen, Ubuntu, Cantarell, sans-serif;
+            display: flex;
+            justify-content: center;
+            align-items: center;
+            min-height: 100vh;
+            margin: 0;
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: #333;
+        }}
+        .container {{
+            background: white;
+            padding: 2rem;
+            border-radius: 12px;
+            box-shadow: 0 10px 40px rgba(0,0,0,0.2);
+            text-align: center;
+            max-width: 500px;
+            width: 90%;
+        }}
+        h1 {{
+            color: #667eea;
+            margin-bottom: 1rem;
+        }}
+        p {{
+            color: #666;
+            line-height: 1.6;
+            word-break: break-word;
+        }}
+        button {{
+            background: #667eea;
+            color: white;
+            border: none;
+            border-radius: 6px;
+            padding: 0.75rem 1.5rem;
+            font-size: 1rem;
+            cursor: pointer;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>{}</h1>
+        <p>{}</p>
+        <form method="post" action="{}">
+            <input type="hidden" name="token" value="{}">
+            <button type="submit">{}</button>
+        </form>
+    </div>
+</body>
+</html>"#,
+        escape_html(heading),
+        escape_html(heading),
+        escape_html(message),
+        escape_html(action),
+        escape_html(token),
+        escape_html(button)
+    );
+    Html(html)
+}
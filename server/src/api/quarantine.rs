@@ -0,0 +1,116 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::services::QuarantineService;
+
+#[derive(Deserialize)]
+pub struct QuarantineQuery {
+    pub limit: Option<i64>,
+}
+
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Query(params): Query<QuarantineQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 200);
+
+    let messages = QuarantineService::list(&pool, user.user_id, limit).await?;
+
+    let response: Vec<serde_json::Value> = messages
+        .into_iter()
+        .map(|m| {
+            serde_json::json!({
+                "id": m.id.to_string(),
+                "alias_id": m.alias_id.to_string(),
+                "alias": m.alias_address,
+                "from": m.from_email,
+                "subject": m.subject,
+                "reason": m.reason,
+                "time": m.created_at,
+                "expires_at": m.expires_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "messages": response })))
+}
+
+/// Safe preview: the text content only. HTML bodies are reduced to their
+/// visible text so nothing is rendered or fetched by the client.
+pub async fn preview(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let message_id = parse_id(&id)?;
+
+    let message = QuarantineService::get_by_id(&pool, message_id, user.user_id).await?;
+    let stored = QuarantineService::open(&config, &message)?;
+
+    let text = match (&stored.body_plain, &stored.body_html) {
+        (Some(text), _) if !text.trim().is_empty() => text.clone(),
+        (_, Some(html)) => crate::html::to_text(html),
+        _ => String::new(),
+    };
+
+    let headers: Vec<(String, String)> = stored
+        .headers
+        .as_deref()
+        .and_then(|h| serde_json::from_str(h).ok())
+        .unwrap_or_default();
+
+    Ok(Json(serde_json::json!({
+        "id": message.id.to_string(),
+        "alias": message.alias_address,
+        "from": message.from_email,
+        "subject": message.subject,
+        "reason": message.reason,
+        "time": message.created_at,
+        "expires_at": message.expires_at,
+        "text": text,
+        "has_html": stored.body_html.is_some(),
+        "headers": headers
+    })))
+}
+
+pub async fn release(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let message_id = parse_id(&id)?;
+
+    let target = QuarantineService::release(&pool, &config, message_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Message released",
+        "target": target
+    })))
+}
+
+pub async fn delete(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let message_id = parse_id(&id)?;
+
+    QuarantineService::delete(&pool, message_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+fn parse_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|_| AppError::Validation("Invalid message ID".to_string()))
+}
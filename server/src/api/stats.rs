@@ -0,0 +1,65 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, Result};
+use crate::models::StatsBucket;
+use crate::services::{AliasService, StatsService};
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    pub days: Option<i64>,
+    #[serde(default)]
+    pub bucket: StatsBucket,
+}
+
+/// Usage of one alias
+pub async fn alias_stats(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Query(params): Query<StatsQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let alias_id = Uuid::parse_str(&id)
+        .map_err(|_| AppError::Validation("Invalid alias ID".to_string()))?;
+
+    let alias = AliasService::get_by_id(&pool, alias_id, user.user_id).await?;
+    let stats = StatsService::usage(
+        &pool,
+        user.user_id,
+        Some(alias.id),
+        params.days.unwrap_or(30),
+        params.bucket,
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "alias_id": alias.id.to_string(),
+        "address": alias.address,
+        "stats": stats
+    })))
+}
+
+/// Usage of all the user's aliases, for the dashboard
+pub async fn account_stats(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Query(params): Query<StatsQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let days = params.days.unwrap_or(30);
+
+    let stats = StatsService::usage(&pool, user.user_id, None, days, params.bucket).await?;
+    let top_aliases = StatsService::top_aliases(&pool, user.user_id, days).await?;
+    let aliases = StatsService::alias_counts(&pool, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "aliases": aliases,
+        "top_aliases": top_aliases,
+        "stats": stats
+    })))
+}
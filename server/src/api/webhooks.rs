@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::AuthenticatedUser;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::CreateWebhookRequest;
+use crate::services::WebhookService;
+
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
+/// Register an endpoint. The signing secret is only returned here.
+pub async fn create(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+    user: AuthenticatedUser,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let endpoint = WebhookService::create(
+        &pool,
+        &config,
+        user.user_id,
+        req.url.trim(),
+        req.events,
+        req.description,
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "id": endpoint.id.to_string(),
+        "url": endpoint.url,
+        "events": endpoint.events,
+        "description": endpoint.description,
+        "secret": endpoint.secret,
+        "created_at": endpoint.created_at
+    })))
+}
+
+pub async fn list(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<Json<serde_json::Value>> {
+    let endpoints = WebhookService::list(&pool, user.user_id).await?;
+
+    let response: Vec<serde_json::Value> = endpoints
+        .into_iter()
+        .map(|e| {
+            serde_json::json!({
+                "id": e.id.to_string(),
+                "url": e.url,
+                "events": e.events,
+                "description": e.description,
+                "enabled": e.enabled,
+                "created_at": e.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "webhooks": response })))
+}
+
+pub async fn delete(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let endpoint_id = parse_id(&id)?;
+    WebhookService::delete(&pool, endpoint_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+pub async fn deliveries(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+    Query(params): Query<DeliveriesQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let endpoint_id = parse_id(&id)?;
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+
+    let deliveries = WebhookService::deliveries(&pool, endpoint_id, user.user_id, limit).await?;
+
+    let response: Vec<serde_json::Value> = deliveries
+        .into_iter()
+        .map(|d| {
+            serde_json::json!({
+                "id": d.id.to_string(),
+                "event": d.event_type,
+                "status": d.status,
+                "attempts": d.attempts,
+                "next_attempt_at": d.next_attempt_at,
+                "last_status_code": d.last_status_code,
+                "last_error": d.last_error,
+                "delivered_at": d.delivered_at,
+                "created_at": d.created_at
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({ "deliveries": response })))
+}
+
+/// Queue a `webhook.test` event for the endpoint
+pub async fn send_test(
+    Extension(pool): Extension<PgPool>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>> {
+    let endpoint_id = parse_id(&id)?;
+    let delivery = WebhookService::send_test(&pool, endpoint_id, user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Test event queued",
+        "delivery_id": delivery.id.to_string()
+    })))
+}
+
+fn parse_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|_| AppError::Validation("Invalid webhook ID".to_string()))
+}
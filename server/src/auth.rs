@@ -1,19 +1,37 @@
+use crate::config::{Config, PasswordHashScheme};
 use crate::error::{AppError, Result};
 use crate::models::User;
+use crate::services::{AccountService, EmailService, SecurityService};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
 use axum::{
-    extract::FromRequestParts,
+    extract::{FromRequestParts, Request},
     http::request::Parts,
+    middleware::Next,
+    response::Response,
     Extension,
 };
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use tokio::sync::{OnceCell, Semaphore};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user_id
     pub exp: usize,
     pub iat: usize,
+    /// Device credential ID, set only on tokens issued through the device flow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub did: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
 pub struct AuthConfig {
@@ -34,11 +52,33 @@ impl AuthConfig {
             sub: user_id.to_string(),
             exp,
             iat: now,
+            did: None,
+            scope: None,
         };
 
+        self.sign(&claims)
+    }
+
+    /// Encode a scoped token bound to a revocable device credential
+    pub fn encode_device_token(&self, user_id: &str, device_id: &str, scope: &str) -> Result<String> {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let exp = now + self.expires_in as usize;
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp,
+            iat: now,
+            did: Some(device_id.to_string()),
+            scope: Some(scope.to_string()),
+        };
+
+        self.sign(&claims)
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String> {
         encode(
             &Header::default(),
-            &claims,
+            claims,
             &EncodingKey::from_secret(self.secret.as_ref()),
         )
         .map_err(|e| AppError::Auth(format!("Failed to encode token: {}", e)))
@@ -55,40 +95,270 @@ impl AuthConfig {
     }
 }
 
-pub async fn verify_password(password: &str, hash: &str) -> Result<bool> {
-    bcrypt::verify(password, hash)
-        .map_err(|e| AppError::Auth(format!("Password verification failed: {}", e)))
+/// Limits how many hashing jobs run at once on the blocking thread pool,
+/// so a burst of logins cannot starve other blocking work.
+static HASH_PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+async fn run_hashing<T, F>(config: &Config, job: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let permits = HASH_PERMITS.get_or_init(|| Semaphore::new(config.password_hash_concurrency.max(1)));
+    let _permit = permits
+        .acquire()
+        .await
+        .map_err(|e| AppError::Internal(format!("Password hashing pool closed: {}", e)))?;
+
+    tokio::task::spawn_blocking(job)
+        .await
+        .map_err(|e| AppError::Internal(format!("Password hashing task failed: {}", e)))?
+}
+
+fn argon2_hasher(config: &Config) -> Result<Argon2<'static>> {
+    let params = Params::new(
+        config.argon2_memory_kib,
+        config.argon2_iterations,
+        config.argon2_parallelism,
+        None,
+    )
+    .map_err(|e| AppError::Internal(format!("Invalid Argon2 parameters: {}", e)))?;
+
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+pub async fn verify_password(config: &Config, password: &str, hash: &str) -> Result<bool> {
+    let password = password.to_string();
+    let hash = hash.to_string();
+
+    run_hashing(config, move || {
+        if hash.starts_with("$argon2") {
+            let parsed = PasswordHash::new(&hash)
+                .map_err(|e| AppError::Auth(format!("Password verification failed: {}", e)))?;
+            // Parameters are read from the stored hash, not the current config
+            Ok(Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok())
+        } else {
+            bcrypt::verify(&password, &hash)
+                .map_err(|e| AppError::Auth(format!("Password verification failed: {}", e)))
+        }
+    })
+    .await
+}
+
+pub async fn hash_password(config: &Config, password: &str) -> Result<String> {
+    let password = password.to_string();
+    let scheme = config.password_hash_scheme;
+    let bcrypt_cost = config.bcrypt_cost;
+    let argon2 = argon2_hasher(config)?;
+
+    run_hashing(config, move || match scheme {
+        PasswordHashScheme::Argon2id => {
+            let salt = SaltString::generate(&mut OsRng);
+            argon2
+                .hash_password(password.as_bytes(), &salt)
+                .map(|h| h.to_string())
+                .map_err(|e| AppError::Internal(format!("Password hashing failed: {}", e)))
+        }
+        PasswordHashScheme::Bcrypt => bcrypt::hash(&password, bcrypt_cost)
+            .map_err(|e| AppError::Internal(format!("Password hashing failed: {}", e))),
+    })
+    .await
 }
 
-pub async fn hash_password(password: &str) -> Result<String> {
-    bcrypt::hash(password, bcrypt::DEFAULT_COST)
-        .map_err(|e| AppError::Internal(format!("Password hashing failed: {}", e)))
+/// Hash checked for unknown emails, so a failed login costs the same
+/// whether or not the account exists
+static DUMMY_HASH: OnceCell<String> = OnceCell::const_new();
+
+async fn dummy_hash(config: &Config) -> Result<&'static str> {
+    let hash = DUMMY_HASH
+        .get_or_try_init(|| hash_password(config, "hush-dummy-password"))
+        .await?;
+    Ok(hash)
+}
+
+/// Whether a stored hash should be replaced with one using the configured
+/// scheme and parameters. Argon2 hashes are never downgraded to bcrypt.
+pub fn needs_rehash(config: &Config, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+
+        parsed.algorithm != Algorithm::Argon2id.ident()
+            || params.m_cost() < config.argon2_memory_kib
+            || params.t_cost() < config.argon2_iterations
+            || params.p_cost() < config.argon2_parallelism
+    } else {
+        match config.password_hash_scheme {
+            PasswordHashScheme::Argon2id => true,
+            PasswordHashScheme::Bcrypt => bcrypt_cost(hash)
+                .map(|cost| cost < config.bcrypt_cost)
+                .unwrap_or(true),
+        }
+    }
 }
 
+/// Cost factor from a `$2b$12$...` style bcrypt hash
+fn bcrypt_cost(hash: &str) -> Option<u32> {
+    hash.split('$').nth(2).and_then(|c| c.parse().ok())
+}
+
+/// Check credentials, applying per-IP and per-account brute-force protection.
+/// Failures are delayed progressively and lock the account after
+/// `login_max_failures` attempts.
 pub async fn authenticate_user(
     pool: &PgPool,
+    config: &Config,
     email: &str,
     password: &str,
+    ip: Option<IpAddr>,
 ) -> Result<User> {
+    if let Some(ip) = ip {
+        let failures =
+            SecurityService::recent_ip_failures(pool, ip, config.login_failure_window_secs).await?;
+        if failures >= config.login_ip_max_failures {
+            SecurityService::log_event(pool, None, "login_blocked_ip", Some(email), Some(ip), None).await?;
+            return Err(AppError::RateLimit);
+        }
+    }
+
     let user = sqlx::query_as::<_, User>(
-        "SELECT id, email, password_hash, created_at, updated_at FROM users WHERE email = $1",
+        "SELECT id, email, password_hash, failed_login_count, locked_until, email_verified, deleted_at, created_at, updated_at FROM users WHERE email = $1",
     )
     .bind(email)
     .fetch_optional(pool)
-    .await?
-    .ok_or_else(|| AppError::Auth("Invalid email or password".to_string()))?;
+    .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            verify_password(config, password, dummy_hash(config).await?).await?;
+            SecurityService::log_event(
+                pool,
+                None,
+                "login_failed",
+                Some(email),
+                ip,
+                Some(serde_json::json!({ "reason": "unknown_email" })),
+            )
+            .await?;
+            return Err(AppError::Auth("Invalid email or password".to_string()));
+        }
+    };
+
+    if let Some(locked_until) = user.locked_until {
+        if locked_until > chrono::Utc::now() {
+            SecurityService::log_event(pool, Some(user.id), "login_blocked_locked", Some(email), ip, None).await?;
+            return Err(AppError::Auth(
+                "Account is temporarily locked. Please try again later.".to_string(),
+            ));
+        }
+    }
+
+    // Progressive delay: 250ms, 500ms, 1s, ... capped at login_delay_max_ms
+    if user.failed_login_count > 0 {
+        let exponent = (user.failed_login_count - 1).min(16) as u32;
+        let delay_ms = (250u64 << exponent).min(config.login_delay_max_ms);
+        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+    }
 
-    let is_valid = verify_password(password, &user.password_hash).await?;
+    let is_valid = verify_password(config, password, &user.password_hash).await?;
     if !is_valid {
+        SecurityService::log_event(
+            pool,
+            Some(user.id),
+            "login_failed",
+            Some(email),
+            ip,
+            Some(serde_json::json!({ "reason": "bad_password" })),
+        )
+        .await?;
+
+        let locked_until = SecurityService::record_failure(
+            pool,
+            user.id,
+            config.login_max_failures,
+            config.login_lockout_secs,
+        )
+        .await?;
+
+        if let Some(locked_until) = locked_until {
+            warn!("Account locked after repeated failed logins: {}", user.id);
+            SecurityService::log_event(
+                pool,
+                Some(user.id),
+                "account_locked",
+                Some(email),
+                ip,
+                Some(serde_json::json!({ "locked_until": locked_until })),
+            )
+            .await?;
+
+            // The notice is best-effort; a mail outage must not change the login response
+            let config = config.clone();
+            let to = user.email.clone();
+            tokio::spawn(async move {
+                if let Err(e) = EmailService::send_lockout_notice(&config, &to, locked_until, ip).await {
+                    error!("Failed to send lockout notice: {}", e);
+                }
+            });
+        }
+
         return Err(AppError::Auth("Invalid email or password".to_string()));
     }
 
+    if user.failed_login_count > 0 || user.locked_until.is_some() {
+        SecurityService::reset_failures(pool, user.id).await?;
+    }
+
+    // Signing in during the deletion grace period cancels the deletion
+    if user.deleted_at.is_some() {
+        AccountService::cancel_deletion(pool, user.id).await?;
+        SecurityService::log_event(pool, Some(user.id), "account_deletion_cancelled", Some(email), ip, None).await?;
+        info!("Account deletion cancelled by login: {}", user.id);
+    }
+
+    // Upgrade legacy or weaker hashes now that we have the plaintext
+    if needs_rehash(config, &user.password_hash) {
+        match hash_password(config, password).await {
+            Ok(new_hash) => {
+                sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+                    .bind(&new_hash)
+                    .bind(user.id)
+                    .execute(pool)
+                    .await?;
+                info!("Upgraded password hash for user: {}", user.id);
+            }
+            Err(e) => warn!("Failed to rehash password for user {}: {}", user.id, e),
+        }
+    }
+    SecurityService::log_event(pool, Some(user.id), "login_succeeded", Some(email), ip, None).await?;
+
     Ok(user)
 }
 
 #[derive(Clone)]
 pub struct AuthenticatedUser {
     pub user_id: uuid::Uuid,
+    /// Device credential the request was made with, if any
+    pub device_id: Option<uuid::Uuid>,
+}
+
+impl AuthenticatedUser {
+    /// Reject scoped device credentials on account-level endpoints
+    pub fn require_session(&self) -> Result<()> {
+        if self.device_id.is_some() {
+            return Err(AppError::Forbidden(
+                "This action requires a full login session".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[axum::async_trait]
@@ -99,7 +369,7 @@ where
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
-        let Extension(config) = Extension::<crate::config::Config>::from_request_parts(parts, state)
+        let Extension(config) = Extension::<Config>::from_request_parts(parts, state)
             .await
             .map_err(|_| AppError::Auth("Config not found".to_string()))?;
 
@@ -120,7 +390,72 @@ where
         let user_id = uuid::Uuid::parse_str(&claims.sub)
             .map_err(|_| AppError::Auth("Invalid user ID in token".to_string()))?;
 
-        Ok(AuthenticatedUser { user_id })
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AppError::Internal("Database pool not found".to_string()))?;
+
+        if !AccountService::is_active(&pool, user_id).await? {
+            return Err(AppError::Auth(
+                "Account is scheduled for deletion. Sign in again to cancel it.".to_string(),
+            ));
+        }
+
+        let device_id = match &claims.did {
+            Some(did) => {
+                let device_id = uuid::Uuid::parse_str(did)
+                    .map_err(|_| AppError::Auth("Invalid device ID in token".to_string()))?;
+
+                if !crate::services::DeviceService::touch_credential(&pool, device_id, user_id).await? {
+                    return Err(AppError::Auth("Device credential has been revoked".to_string()));
+                }
+
+                Some(device_id)
+            }
+            None => None,
+        };
+
+        Ok(AuthenticatedUser { user_id, device_id })
     }
 }
 
+/// Route layer that turns away scoped device credentials. Every protected
+/// route sits behind it unless it is part of the device scope.
+pub async fn session_only(user: AuthenticatedUser, request: Request, next: Next) -> Result<Response> {
+    user.require_session()?;
+    Ok(next.run(request).await)
+}
+
+/// Operator access to the admin API, granted by the `ADMIN_TOKEN` bearer token
+pub struct AdminAccess;
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for AdminAccess
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let Extension(config) = Extension::<Config>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AppError::Auth("Config not found".to_string()))?;
+
+        let Some(expected) = config.admin_token.as_deref() else {
+            return Err(AppError::Forbidden("Admin API is disabled".to_string()));
+        };
+
+        let token = parts
+            .headers
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::Auth("Missing Authorization header".to_string()))?;
+
+        // Compare digests so timing does not reveal a matching prefix
+        if Sha256::digest(token.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+            return Err(AppError::Auth("Invalid admin token".to_string()));
+        }
+
+        Ok(AdminAccess)
+    }
+}
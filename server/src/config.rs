@@ -14,6 +14,63 @@ pub struct Config {
     pub smtp_from: String,
     pub hush_domain: String,
     pub api_base_url: String,
+    pub app_base_url: String,
+    pub require_email_verification: bool,
+    pub account_deletion_grace_days: i64,
+    pub vapid_private_key: Option<String>,
+    pub vapid_subject: String,
+    pub push_ttl_secs: u32,
+    pub alias_expiry_warning_minutes: i64,
+    pub webhook_max_attempts: i32,
+    pub webhook_allow_private_targets: bool,
+    pub held_message_retention_days: i64,
+    pub quarantine_key: Option<String>,
+    pub quarantine_retention_days: i64,
+    pub log_hash_key: Option<String>,
+    pub quarantine_auth_failures: bool,
+    pub spamd_address: Option<String>,
+    pub spamd_timeout_ms: u64,
+    pub dns_resolver_url: String,
+    pub domain_mx_host: Option<String>,
+    pub domain_spf_include: Option<String>,
+    pub admin_token: Option<String>,
+    pub subaddress_separator: Option<String>,
+    pub directory_limit: i64,
+    pub directory_daily_alias_limit: i64,
+    pub catch_all_daily_alias_limit: i64,
+    pub alias_word_count: usize,
+    pub alias_hex_length: usize,
+    pub alias_syllable_count: usize,
+    pub alias_min_entropy_bits: f64,
+    pub alias_min_length: usize,
+    pub alias_create_limit_per_hour: i64,
+    pub device_code_expires_in: u64,
+    pub device_poll_interval: u64,
+    pub device_token_expires_in: u64,
+    pub trust_proxy_headers: bool,
+    pub rate_limit_auth_period_secs: u64,
+    pub rate_limit_auth_burst: u32,
+    pub rate_limit_webhook_period_ms: u64,
+    pub rate_limit_webhook_burst: u32,
+    pub login_max_failures: i32,
+    pub login_ip_max_failures: i64,
+    pub login_failure_window_secs: u64,
+    pub login_lockout_secs: u64,
+    pub login_delay_max_ms: u64,
+    pub password_hash_scheme: PasswordHashScheme,
+    pub bcrypt_cost: u32,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub password_hash_concurrency: usize,
+}
+
+/// Scheme used for new password hashes. Existing hashes in another scheme
+/// (or with weaker parameters) are upgraded on the next successful login.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordHashScheme {
+    Argon2id,
+    Bcrypt,
 }
 
 impl Config {
@@ -51,6 +108,183 @@ impl Config {
                 .unwrap_or_else(|_| "hush.example".to_string()),
             api_base_url: env::var("API_BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:3001".to_string()),
+            app_base_url: env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:5173".to_string()),
+            require_email_verification: env::var("REQUIRE_EMAIL_VERIFICATION")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            account_deletion_grace_days: env::var("ACCOUNT_DELETION_GRACE_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            vapid_private_key: env::var("VAPID_PRIVATE_KEY")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            vapid_subject: env::var("VAPID_SUBJECT")
+                .unwrap_or_else(|_| "mailto:admin@hush.example".to_string()),
+            push_ttl_secs: env::var("PUSH_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(86400),
+            alias_expiry_warning_minutes: env::var("ALIAS_EXPIRY_WARNING_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            webhook_max_attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            webhook_allow_private_targets: env::var("WEBHOOK_ALLOW_PRIVATE_TARGETS")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            held_message_retention_days: env::var("HELD_MESSAGE_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            quarantine_key: env::var("QUARANTINE_KEY")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            quarantine_retention_days: env::var("QUARANTINE_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            log_hash_key: env::var("LOG_HASH_KEY")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            quarantine_auth_failures: env::var("QUARANTINE_AUTH_FAILURES")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            spamd_address: env::var("SPAMD_ADDRESS")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            spamd_timeout_ms: env::var("SPAMD_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3000),
+            dns_resolver_url: env::var("DNS_RESOLVER_URL")
+                .unwrap_or_else(|_| "https://cloudflare-dns.com/dns-query".to_string()),
+            domain_mx_host: env::var("DOMAIN_MX_HOST")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            domain_spf_include: env::var("DOMAIN_SPF_INCLUDE")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            subaddress_separator: Some(env::var("SUBADDRESS_SEPARATOR").unwrap_or_else(|_| "+".to_string()))
+                .filter(|v| !v.trim().is_empty()),
+            directory_limit: env::var("DIRECTORY_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            directory_daily_alias_limit: env::var("DIRECTORY_DAILY_ALIAS_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+            catch_all_daily_alias_limit: env::var("CATCH_ALL_DAILY_ALIAS_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+            alias_word_count: env::var("ALIAS_WORD_COUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            alias_hex_length: env::var("ALIAS_HEX_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            alias_syllable_count: env::var("ALIAS_SYLLABLE_COUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+            alias_min_entropy_bits: env::var("ALIAS_MIN_ENTROPY_BITS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20.0),
+            alias_min_length: env::var("ALIAS_MIN_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            alias_create_limit_per_hour: env::var("ALIAS_CREATE_LIMIT_PER_HOUR")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            device_code_expires_in: env::var("DEVICE_CODE_EXPIRES_IN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600), // 10 minutes
+            device_poll_interval: env::var("DEVICE_POLL_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            device_token_expires_in: env::var("DEVICE_TOKEN_EXPIRES_IN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7776000), // 90 days
+            trust_proxy_headers: env::var("TRUST_PROXY_HEADERS")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            rate_limit_auth_period_secs: env::var("RATE_LIMIT_AUTH_PERIOD_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+            rate_limit_auth_burst: env::var("RATE_LIMIT_AUTH_BURST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            rate_limit_webhook_period_ms: env::var("RATE_LIMIT_WEBHOOK_PERIOD_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+            rate_limit_webhook_burst: env::var("RATE_LIMIT_WEBHOOK_BURST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            login_max_failures: env::var("LOGIN_MAX_FAILURES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            login_ip_max_failures: env::var("LOGIN_IP_MAX_FAILURES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            login_failure_window_secs: env::var("LOGIN_FAILURE_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(900), // 15 minutes
+            login_lockout_secs: env::var("LOGIN_LOCKOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(900), // 15 minutes
+            login_delay_max_ms: env::var("LOGIN_DELAY_MAX_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5000),
+            password_hash_scheme: match env::var("PASSWORD_HASH_SCHEME").as_deref() {
+                Ok("bcrypt") => PasswordHashScheme::Bcrypt,
+                _ => PasswordHashScheme::Argon2id,
+            },
+            bcrypt_cost: env::var("BCRYPT_COST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(bcrypt::DEFAULT_COST),
+            argon2_memory_kib: env::var("ARGON2_MEMORY_KIB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(19456), // 19 MiB (OWASP recommendation)
+            argon2_iterations: env::var("ARGON2_ITERATIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            argon2_parallelism: env::var("ARGON2_PARALLELISM")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
+            password_hash_concurrency: env::var("PASSWORD_HASH_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
         })
     }
 }
@@ -0,0 +1,89 @@
+//! Encryption at rest for stored message content (AES-256-GCM) and keyed
+//! hashing of sender addresses in email logs.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::webpush::decode_base64;
+
+pub struct MessageCipher {
+    cipher: Aes256Gcm,
+}
+
+impl MessageCipher {
+    /// Use `QUARANTINE_KEY` (base64, 32 bytes) when set, otherwise a key
+    /// derived from `JWT_SECRET`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut key = [0u8; 32];
+
+        match config.quarantine_key.as_deref() {
+            Some(encoded) => {
+                let bytes = decode_base64(encoded)
+                    .map_err(|_| AppError::Internal("QUARANTINE_KEY is not valid base64".to_string()))?;
+                if bytes.len() != key.len() {
+                    return Err(AppError::Internal("QUARANTINE_KEY must be 32 bytes".to_string()));
+                }
+                key.copy_from_slice(&bytes);
+            }
+            None => {
+                Hkdf::<Sha256>::new(None, config.jwt_secret.as_bytes())
+                    .expand(b"hush quarantine v1", &mut key)
+                    .map_err(|_| AppError::Internal("HKDF expand failed".to_string()))?;
+            }
+        }
+
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| AppError::Internal("Invalid quarantine key".to_string()))?;
+
+        Ok(Self { cipher })
+    }
+
+    /// Encrypt with a fresh random nonce, returned alongside the ciphertext
+    pub fn seal(&self, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let nonce: [u8; 12] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| AppError::Internal("Message encryption failed".to_string()))?;
+
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    pub fn open(&self, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        if nonce.len() != 12 {
+            return Err(AppError::Internal("Invalid message nonce".to_string()));
+        }
+
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| AppError::Internal("Message decryption failed".to_string()))
+    }
+}
+
+/// Hex HMAC-SHA256 of the lowercased sender under `LOG_HASH_KEY`, or a key
+/// derived from `JWT_SECRET` when unset. Changing the key breaks matching
+/// against entries hashed before.
+pub fn sender_hash(config: &Config, sender: &str) -> String {
+    let mut derived = [0u8; 32];
+    let key = match config.log_hash_key.as_deref() {
+        Some(key) => key.as_bytes(),
+        None => {
+            Hkdf::<Sha256>::new(None, config.jwt_secret.as_bytes())
+                .expand(b"hush sender hash v1", &mut derived)
+                .expect("32 bytes is a valid HKDF-SHA256 output length");
+            &derived[..]
+        }
+    };
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(sender.trim().to_lowercase().as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
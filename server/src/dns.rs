@@ -0,0 +1,174 @@
+//! DNS lookups for domain verification. Lookups go through the
+//! [`DnsResolver`] trait so they can be replaced with fixed answers in tests.
+
+use serde::Deserialize;
+#[cfg(test)]
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    Txt,
+    Mx,
+    Cname,
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::Txt => "TXT",
+            RecordType::Mx => "MX",
+            RecordType::Cname => "CNAME",
+        }
+    }
+
+    fn code(&self) -> u16 {
+        match self {
+            RecordType::Txt => 16,
+            RecordType::Mx => 15,
+            RecordType::Cname => 5,
+        }
+    }
+}
+
+#[axum::async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Record data for `name`, normalised: TXT strings joined and unquoted,
+    /// host names lower-cased without the trailing dot, MX as `priority host`.
+    /// A name without records of the type yields an empty list.
+    async fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<String>>;
+}
+
+/// Resolver configured by `DNS_RESOLVER_URL`
+pub fn resolver(config: &Config) -> Box<dyn DnsResolver> {
+    Box::new(DohResolver {
+        url: config.dns_resolver_url.clone(),
+    })
+}
+
+/// DNS-over-HTTPS using the JSON API offered by Cloudflare and Google
+pub struct DohResolver {
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+/// NXDOMAIN
+const RCODE_NAME_ERROR: u32 = 3;
+
+#[axum::async_trait]
+impl DnsResolver for DohResolver {
+    async fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<String>> {
+        let response = crate::http::internal_client()
+            .get(&self.url)
+            .query(&[("name", name), ("type", record_type.as_str())])
+            .header("Accept", "application/dns-json")
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("DNS lookup failed: {}", e)))?
+            .error_for_status()
+            .map_err(|e| AppError::Internal(format!("DNS lookup failed: {}", e)))?
+            .json::<DohResponse>()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid DNS response: {}", e)))?;
+
+        match response.status {
+            0 => {}
+            RCODE_NAME_ERROR => return Ok(Vec::new()),
+            rcode => return Err(AppError::Internal(format!("DNS lookup failed with rcode {}", rcode))),
+        }
+
+        Ok(response
+            .answer
+            .into_iter()
+            .filter(|answer| answer.record_type == record_type.code())
+            .map(|answer| match record_type {
+                RecordType::Txt => unquote_txt(&answer.data),
+                RecordType::Mx | RecordType::Cname => normalize_host(&answer.data),
+            })
+            .collect())
+    }
+}
+
+/// Resolver answering from a fixed table, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct StaticResolver {
+    records: HashMap<(String, RecordType), Vec<String>>,
+    failing: Vec<(String, RecordType)>,
+}
+
+#[cfg(test)]
+impl StaticResolver {
+    /// Add a record, with data normalised as [`DnsResolver::lookup`] returns it
+    pub fn with(mut self, name: &str, record_type: RecordType, data: &str) -> Self {
+        self.records
+            .entry((normalize_host(name), record_type))
+            .or_default()
+            .push(data.to_string());
+        self
+    }
+
+    /// Make lookups of `name` fail, as when the resolver is unreachable
+    pub fn failing(mut self, name: &str, record_type: RecordType) -> Self {
+        self.failing.push((normalize_host(name), record_type));
+        self
+    }
+}
+
+#[cfg(test)]
+#[axum::async_trait]
+impl DnsResolver for StaticResolver {
+    async fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<String>> {
+        let key = (normalize_host(name), record_type);
+        if self.failing.contains(&key) {
+            return Err(AppError::Internal(format!("DNS lookup failed for {}", name)));
+        }
+        Ok(self.records.get(&key).cloned().unwrap_or_default())
+    }
+}
+
+/// Join the quoted character strings of a TXT record
+fn unquote_txt(data: &str) -> String {
+    let data = data.trim();
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+
+    let mut out = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            _ if quoted => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Lower-case host names and drop the trailing root dot
+pub fn normalize_host(data: &str) -> String {
+    data.trim().trim_end_matches('.').to_ascii_lowercase()
+}
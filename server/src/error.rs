@@ -17,11 +17,28 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// Validation failure the client can act on: which field was rejected
+    /// and a stable code for why
+    #[error("Validation error: {message}")]
+    InvalidField {
+        field: &'static str,
+        code: &'static str,
+        message: String,
+    },
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Device authorization error: {0}")]
+    DeviceFlow(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Rate limit exceeded")]
-    #[allow(dead_code)]
     RateLimit,
 
     #[error("Internal server error: {0}")]
@@ -37,7 +54,11 @@ impl IntoResponse for AppError {
             }
             AppError::Auth(msg) => msg.clone(),
             AppError::Validation(msg) => msg.clone(),
+            AppError::InvalidField { message, .. } => message.clone(),
+            AppError::Forbidden(msg) => msg.clone(),
+            AppError::DeviceFlow(code) => code.clone(),
             AppError::NotFound(msg) => msg.clone(),
+            AppError::Conflict(msg) => msg.clone(),
             AppError::RateLimit => "Rate limit exceeded".to_string(),
             AppError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
@@ -49,15 +70,24 @@ impl IntoResponse for AppError {
             AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Auth(_) => StatusCode::UNAUTHORIZED,
             AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidField { .. } => StatusCode::BAD_REQUEST,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::DeviceFlow(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
             AppError::RateLimit => StatusCode::TOO_MANY_REQUESTS,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": error_message,
             "message": self.to_string()
-        }));
+        });
+        if let AppError::InvalidField { field, code, .. } = &self {
+            body["field"] = json!(field);
+            body["code"] = json!(code);
+        }
+        let body = Json(body);
 
         (status, body).into_response()
     }
@@ -0,0 +1,70 @@
+//! Fan-out of new email log entries to live event streams. Every instance
+//! LISTENs on a Postgres channel fed by a trigger on `email_logs`, so entries
+//! written by any instance reach subscribers connected to any other.
+
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Channel notified by the `notify_email_logs_inserted` trigger
+const CHANNEL: &str = "email_log_events";
+
+/// Notifications buffered per subscriber before it is told it lagged
+const BUFFER_SIZE: usize = 1024;
+
+/// Payload of a channel notification; streams fetch the entry itself
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct LogNotification {
+    pub seq: i64,
+    pub user_id: Option<Uuid>,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<LogNotification>,
+}
+
+impl EventBus {
+    /// Create the bus and start listening for notifications
+    pub fn start(pool: PgPool) -> Self {
+        let (sender, _) = broadcast::channel(BUFFER_SIZE);
+
+        let bus_sender = sender.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = listen(&pool, &bus_sender).await {
+                    error!("Event listener failed: {}", e);
+                }
+                // Anything missed while disconnected is picked up by the
+                // streams' periodic catch-up
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LogNotification> {
+        self.sender.subscribe()
+    }
+}
+
+async fn listen(pool: &PgPool, sender: &broadcast::Sender<LogNotification>) -> sqlx::Result<()> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    info!("Listening for email log events");
+
+    loop {
+        let notification = listener.recv().await?;
+        match serde_json::from_str::<LogNotification>(notification.payload()) {
+            // Sending only fails when nobody is subscribed
+            Ok(event) => {
+                let _ = sender.send(event);
+            }
+            Err(e) => warn!("Ignoring malformed email log notification: {}", e),
+        }
+    }
+}
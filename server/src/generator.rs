@@ -0,0 +1,234 @@
+//! Local parts for random and temporary aliases. Each strategy is lengthened
+//! as needed to reach `ALIAS_MIN_ENTROPY_BITS`.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::config::Config;
+use crate::models::AliasStrategy;
+
+/// Number appended to `words` and `syllables` aliases, 00 to 99
+const SUFFIX_RANGE: u32 = 100;
+
+/// Upper bounds keeping local parts well under 64 characters
+const MAX_HEX_LENGTH: usize = 32;
+const MAX_WORD_COUNT: usize = 6;
+const MAX_SYLLABLE_COUNT: usize = 16;
+
+const CONSONANTS: &[u8] = b"bdfghjklmnprstvz";
+const VOWELS: &[u8] = b"aeiou";
+
+pub struct AliasGenerator {
+    strategy: AliasStrategy,
+    /// Hex digits, words or syllables
+    length: usize,
+}
+
+impl AliasGenerator {
+    pub fn new(config: &Config, strategy: AliasStrategy) -> Self {
+        let (configured, unit_bits, suffix_bits, max) = match strategy {
+            AliasStrategy::Hex => (config.alias_hex_length, 4.0, 0.0, MAX_HEX_LENGTH),
+            AliasStrategy::Words => (
+                config.alias_word_count,
+                bits(ADJECTIVES.len().min(NOUNS.len())),
+                bits(SUFFIX_RANGE as usize),
+                MAX_WORD_COUNT,
+            ),
+            AliasStrategy::Syllables => (
+                config.alias_syllable_count,
+                bits(CONSONANTS.len() * VOWELS.len()),
+                bits(SUFFIX_RANGE as usize),
+                MAX_SYLLABLE_COUNT,
+            ),
+        };
+
+        let needed = ((config.alias_min_entropy_bits - suffix_bits) / unit_bits).ceil().max(1.0) as usize;
+
+        AliasGenerator {
+            strategy,
+            length: configured.max(needed).clamp(1, max),
+        }
+    }
+
+    /// Bits of randomness in each generated local part
+    pub fn entropy_bits(&self) -> f64 {
+        let n = self.length;
+        match self.strategy {
+            AliasStrategy::Hex => 4.0 * n as f64,
+            AliasStrategy::Words => {
+                (n - 1) as f64 * bits(ADJECTIVES.len()) + bits(NOUNS.len()) + bits(SUFFIX_RANGE as usize)
+            }
+            AliasStrategy::Syllables => {
+                n as f64 * bits(CONSONANTS.len() * VOWELS.len()) + bits(SUFFIX_RANGE as usize)
+            }
+        }
+    }
+
+    /// A new local part. Hex aliases start with `prefix` (`hush` or `temp`).
+    pub fn generate(&self, prefix: &str) -> String {
+        let mut rng = rand::thread_rng();
+
+        match self.strategy {
+            AliasStrategy::Hex => {
+                let hex: String = (0..self.length)
+                    .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap_or('0'))
+                    .collect();
+                format!("{}-{}", prefix, hex)
+            }
+            AliasStrategy::Words => {
+                let mut words: Vec<&str> = (1..self.length)
+                    .map(|_| *ADJECTIVES.choose(&mut rng).unwrap_or(&"quiet"))
+                    .collect();
+                words.push(NOUNS.choose(&mut rng).unwrap_or(&"otter"));
+                format!("{}{:02}", words.join("."), rng.gen_range(0..SUFFIX_RANGE))
+            }
+            AliasStrategy::Syllables => {
+                let mut out = String::with_capacity(self.length * 2 + 2);
+                for _ in 0..self.length {
+                    out.push(CONSONANTS[rng.gen_range(0..CONSONANTS.len())] as char);
+                    out.push(VOWELS[rng.gen_range(0..VOWELS.len())] as char);
+                }
+                format!("{}{:02}", out, rng.gen_range(0..SUFFIX_RANGE))
+            }
+        }
+    }
+}
+
+fn bits(choices: usize) -> f64 {
+    (choices as f64).log2()
+}
+
+/// First words of a `words` alias
+const ADJECTIVES: &[&str] = &[
+    "able", "acid", "aged", "airy", "alert", "alive", "amber", "ample", "angry", "arid", "avid",
+    "awake", "azure", "bald", "basic", "beige", "best", "big", "bitter", "black", "bland", "blank",
+    "blond", "blue", "blunt", "bold", "bony", "bossy", "brave", "brief", "bright", "brisk", "broad",
+    "brown", "bumpy", "busy", "calm", "candid", "carefree", "casual", "chief", "chilly", "civil",
+    "clean", "clear", "clever", "close", "cloudy", "coarse", "cold", "cool", "copper", "cosmic",
+    "cozy", "crisp", "cubic", "curly", "curved", "cute", "daily", "damp", "dapper", "dark", "dear",
+    "decent", "deep", "dense", "dewy", "dim", "direct", "dizzy", "double", "dry", "dual", "dusky",
+    "dusty", "eager", "early", "earthy", "easy", "elder", "elite", "empty", "epic", "equal", "even",
+    "exact", "extra", "faint", "fair", "false", "famous", "fancy", "far", "fast", "fierce", "final",
+    "fine", "firm", "first", "fit", "flat", "fleet", "fluffy", "fond", "free", "fresh", "frosty",
+    "full", "funny", "fuzzy", "gentle", "giant", "giddy", "glad", "glossy", "golden", "good",
+    "grand", "gray", "great", "green", "grim", "handy", "happy", "hardy", "hasty", "hazy", "heavy",
+    "hidden", "high", "hollow", "honest", "huge", "humble", "icy", "ideal", "idle", "inner",
+    "ivory", "jade", "jolly", "jumbo", "just", "keen", "kind", "large", "last", "late", "lazy",
+    "lean", "level", "light", "little", "live", "lively", "local", "lofty", "lone", "long", "loud",
+    "lovely", "loyal", "lucky", "lunar", "magic", "major", "mellow", "merry", "mild", "minor",
+    "misty", "modern", "modest", "moist", "molten", "mossy", "muddy", "murky", "narrow", "native",
+    "neat", "new", "nice", "nimble", "noble", "north", "novel", "odd", "open", "orange", "outer",
+    "pale", "past", "patient", "plain", "plush", "polar", "polite", "proud", "pure", "purple",
+    "quick", "quiet", "rapid", "rare", "raw", "ready", "real", "regal", "rich", "right", "rigid",
+    "ripe", "rosy", "rough", "round", "royal", "ruby", "rural", "rustic", "safe", "salty", "sandy",
+    "scarlet", "secret", "shady", "sharp", "shiny", "short", "shy", "silent", "silky", "silver",
+    "simple", "sleek", "slim", "slow", "small", "smart", "smooth", "snowy", "soft", "solar",
+    "solid", "sonic", "sound", "spare", "spicy", "stable", "steady", "steep", "still", "stony",
+    "stout", "sunny", "super", "sure", "sweet", "swift", "tall", "tame", "tart", "teal", "tender",
+    "thick", "tidy", "tiny", "tough", "tribal", "true", "urban", "usual", "valid", "vast", "velvet",
+    "vivid", "warm", "wavy", "wild", "windy", "wise", "witty", "young", "zesty",
+];
+
+/// Last word of a `words` alias
+const NOUNS: &[&str] = &[
+    "acacia", "acorn", "adder", "agate", "alder", "almond", "alpaca", "anchor", "ant", "apple",
+    "arch", "arrow", "aspen", "aster", "badger", "bagel", "bamboo", "banjo", "barn", "basil", "bay",
+    "beacon", "beagle", "bean", "bear", "beaver", "bee", "beetle", "bell", "berry", "birch",
+    "bison", "blossom", "boat", "bobcat", "bonsai", "boulder", "branch", "breeze", "brook",
+    "buffalo", "bunny", "cabin", "cactus", "camel", "canal", "candle", "canoe", "canyon",
+    "cardinal", "carrot", "castle", "cedar", "cello", "chalk", "cherry", "chess", "cider", "clam",
+    "cliff", "clover", "cobra", "comet", "coral", "cougar", "cove", "coyote", "crane", "creek",
+    "cricket", "crow", "cub", "daisy", "deer", "delta", "desert", "dingo", "dolphin", "dove",
+    "dragon", "drum", "duck", "dune", "eagle", "echo", "eel", "elk", "elm", "ember", "emu",
+    "falcon", "fern", "ferret", "field", "finch", "fjord", "flame", "flint", "fog", "forest", "fox",
+    "frog", "galaxy", "gecko", "geyser", "ginger", "glacier", "goat", "goose", "grape", "gull",
+    "harbor", "hare", "hawk", "hazel", "heron", "hill", "hippo", "honey", "horizon", "horse",
+    "husky", "ibis", "iguana", "island", "jackal", "jaguar", "jay", "jelly", "kayak", "kelp",
+    "kettle", "kiwi", "koala", "lagoon", "lake", "lark", "lemon", "lemur", "leopard", "lily",
+    "lime", "lion", "lizard", "llama", "lobster", "lotus", "lynx", "magpie", "mango", "maple",
+    "marble", "marsh", "meadow", "melon", "mesa", "mink", "mint", "mole", "moose", "moss", "moth",
+    "mountain", "mule", "newt", "nutmeg", "oak", "oasis", "ocean", "octopus", "olive", "orca",
+    "osprey", "otter", "owl", "oyster", "panda", "panther", "parrot", "peach", "peak", "pear",
+    "pebble", "pelican", "penguin", "pepper", "pine", "plum", "pond", "poppy", "prairie", "puffin",
+    "puma", "quail", "quartz", "rabbit", "raccoon", "raven", "reef", "ridge", "river", "robin",
+    "rock", "rose", "sage", "salmon", "sand", "seal", "shark", "sheep", "shell", "shore", "shrimp",
+    "sierra", "skunk", "sloth", "snail", "sparrow", "spruce", "squid", "stork", "stream", "summit",
+    "swan", "tapir", "thistle", "thrush", "tiger", "toad", "topaz", "trail", "trout", "tulip",
+    "tundra", "turtle", "valley", "violet", "viper", "vole", "walnut", "walrus", "wasp", "water",
+    "whale", "willow", "wolf", "wombat", "wren", "yak", "zebra",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(strategy: AliasStrategy, length: usize, min_entropy_bits: f64) -> AliasGenerator {
+        let mut config = Config::from_env().unwrap();
+        config.alias_hex_length = length;
+        config.alias_word_count = length;
+        config.alias_syllable_count = length;
+        config.alias_min_entropy_bits = min_entropy_bits;
+        AliasGenerator::new(&config, strategy)
+    }
+
+    /// Split off the two-digit suffix of `words` and `syllables` aliases
+    fn suffixed(local: &str) -> &str {
+        let (body, suffix) = local.split_at(local.len() - 2);
+        assert!(suffix.chars().all(|c| c.is_ascii_digit()), "{} has no number suffix", local);
+        body
+    }
+
+    #[test]
+    fn hex_aliases_have_the_prefix_and_length() {
+        let local = generator(AliasStrategy::Hex, 12, 0.0).generate("temp");
+        let hex = local.strip_prefix("temp-").unwrap();
+        assert_eq!(hex.len(), 12);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn word_aliases_end_with_a_noun_and_number() {
+        let local = generator(AliasStrategy::Words, 3, 0.0).generate("hush");
+        let words: Vec<&str> = suffixed(&local).split('.').collect();
+        assert_eq!(words.len(), 3);
+        assert!(words[..2].iter().all(|w| ADJECTIVES.contains(w)), "{}", local);
+        assert!(NOUNS.contains(&words[2]), "{}", local);
+    }
+
+    #[test]
+    fn syllable_aliases_alternate_consonants_and_vowels() {
+        let local = generator(AliasStrategy::Syllables, 4, 0.0).generate("hush");
+        let body = suffixed(&local).as_bytes();
+        assert_eq!(body.len(), 8);
+        for pair in body.chunks(2) {
+            assert!(CONSONANTS.contains(&pair[0]) && VOWELS.contains(&pair[1]), "{}", local);
+        }
+    }
+
+    #[test]
+    fn length_grows_to_reach_the_minimum_entropy() {
+        for strategy in [AliasStrategy::Hex, AliasStrategy::Words, AliasStrategy::Syllables] {
+            let generator = generator(strategy, 1, 48.0);
+            assert!(generator.entropy_bits() >= 48.0, "{:?}: {}", strategy, generator.entropy_bits());
+        }
+        assert_eq!(generator(AliasStrategy::Hex, 1, 48.0).generate("hush").len(), "hush-".len() + 12);
+    }
+
+    #[test]
+    fn length_is_capped() {
+        assert_eq!(generator(AliasStrategy::Hex, 100, 0.0).generate("hush").len(), "hush-".len() + MAX_HEX_LENGTH);
+        let local = generator(AliasStrategy::Words, 1, 1000.0).generate("hush");
+        assert_eq!(suffixed(&local).split('.').count(), MAX_WORD_COUNT);
+        let local = generator(AliasStrategy::Syllables, 100, 0.0).generate("hush");
+        assert_eq!(suffixed(&local).len(), MAX_SYLLABLE_COUNT * 2);
+    }
+
+    #[test]
+    fn aliases_are_valid_local_parts() {
+        for strategy in [AliasStrategy::Hex, AliasStrategy::Words, AliasStrategy::Syllables] {
+            let local = generator(strategy, MAX_WORD_COUNT, 0.0).generate("hush");
+            assert!(local.len() <= 64, "{}", local);
+            assert_eq!(crate::validation::normalize_local_part(&local, "alias").unwrap(), local);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Helpers for handling HTML from incoming mail.
+
+/// Reduce an HTML body to its visible text. Tags are dropped (each replaced
+/// by a space so words don't run together) along with the contents of
+/// `<script>` and `<style>` elements; common entities are decoded.
+pub fn to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let end = match after.find('>') {
+            Some(end) => end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+
+        let tag = after[1..end].trim_start().to_ascii_lowercase();
+        rest = &after[end + 1..];
+        text.push(' ');
+
+        // Skip over the contents of elements that are never displayed
+        for hidden in ["script", "style"] {
+            if tag.starts_with(hidden) {
+                let closing = format!("</{}", hidden);
+                rest = match rest.to_ascii_lowercase().find(&closing) {
+                    Some(pos) => &rest[pos..],
+                    None => "",
+                };
+            }
+        }
+    }
+    text.push_str(rest);
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Hosts that only serve open-tracking pixels or click redirects
+const TRACKER_HOSTS: &[&str] = &[
+    "list-manage.com",
+    "mailtrack.io",
+    "mixmax.com",
+    "ct.sendgrid.net",
+    "mandrillapp.com",
+    "hubspotlinks.com",
+    "hubspotemail.net",
+    "t.yesware.com",
+    "getnotify.com",
+    "bananatag.com",
+    "mailstat.us",
+    "pixel.mathtag.com",
+];
+
+/// Path fragments used by common open-tracking endpoints
+const TRACKER_PATHS: &[&str] = &["/track/open", "/wf/open", "/open.php", "/pixel.gif", "/beacon"];
+
+/// Query parameters that identify the recipient or campaign
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "_hsenc", "_hsmi", "mkt_tok"];
+
+/// Hosts whose links only redirect to a destination carried in the query
+const REDIRECT_HOSTS: &[&str] = &[
+    "safelinks.protection.outlook.com",
+    "l.facebook.com",
+    "lm.facebook.com",
+    "l.instagram.com",
+    "out.reddit.com",
+    "click.linksynergy.com",
+    "clicks.aweber.com",
+    "redirect.viglink.com",
+];
+
+/// Path segments of redirect endpoints, e.g. `/url` or `/track/click`
+const REDIRECT_PATHS: &[&str] = &["url", "redirect", "redir", "click", "goto", "away"];
+
+/// Query parameters redirect services use to carry the destination
+const REDIRECT_PARAMS: &[&str] = &["url", "u", "q", "redirect", "redirect_url", "target", "dest", "destination", "link"];
+
+pub struct SanitizeOptions<'a> {
+    /// When set, remaining remote images are loaded through this function's URL
+    pub proxy_image: Option<&'a dyn Fn(&str) -> String>,
+}
+
+/// What the sanitizer changed in a message
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct TrackerReport {
+    pub pixels_removed: u32,
+    pub links_unwrapped: u32,
+    pub params_removed: u32,
+    pub images_proxied: u32,
+}
+
+impl TrackerReport {
+    pub fn trackers_removed(&self) -> u32 {
+        self.pixels_removed + self.links_unwrapped + self.params_removed
+    }
+}
+
+/// Remove tracking pixels, unwrap redirect links and strip tracking
+/// parameters from an HTML body. A short notice is added at the top of the
+/// body when anything was removed.
+pub fn strip_trackers(html: &str, options: &SanitizeOptions) -> (String, TrackerReport) {
+    let mut report = TrackerReport::default();
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(end) = tag_end(after) else {
+            output.push_str(after);
+            rest = "";
+            break;
+        };
+        let tag = &after[..=end];
+        rest = &after[end + 1..];
+
+        match tag_name(tag).as_deref() {
+            Some("img") => {
+                let src = attribute(tag, "src");
+                if is_tracking_pixel(tag, src.as_deref()) {
+                    report.pixels_removed += 1;
+                    continue;
+                }
+                match (src, options.proxy_image) {
+                    (Some(src), Some(proxy)) if is_remote(&src) => {
+                        output.push_str(&set_attribute(tag, "src", &proxy(&src)));
+                        report.images_proxied += 1;
+                    }
+                    _ => output.push_str(tag),
+                }
+            }
+            // Copy script and style contents through untouched
+            Some(hidden @ ("script" | "style")) => {
+                output.push_str(tag);
+                let closing = format!("</{}", hidden);
+                let end = rest.to_ascii_lowercase().find(&closing).unwrap_or(rest.len());
+                output.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            Some("a") => match attribute(tag, "href") {
+                Some(href) if is_remote(&href) => {
+                    let cleaned = clean_link(&href, &mut report);
+                    if cleaned == href {
+                        output.push_str(tag);
+                    } else {
+                        output.push_str(&set_attribute(tag, "href", &cleaned));
+                    }
+                }
+                _ => output.push_str(tag),
+            },
+            _ => output.push_str(tag),
+        }
+    }
+    output.push_str(rest);
+
+    if report.trackers_removed() > 0 {
+        output = insert_banner(&output, report.trackers_removed());
+    }
+
+    (output, report)
+}
+
+/// Index of the `>` closing the tag at the start of `input`, skipping over
+/// quoted attribute values
+fn tag_end(input: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in input.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> Option<String> {
+    let name: String = tag[1..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    (!name.is_empty()).then(|| name.to_ascii_lowercase())
+}
+
+/// Byte range of an attribute's value within the tag, and the value itself
+fn find_attribute(tag: &str, name: &str) -> Option<(usize, usize, String)> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+
+    while let Some(pos) = lower[search..].find(name) {
+        let start = search + pos;
+        search = start + name.len();
+
+        // Must be a whole attribute name followed by `=`
+        let preceded = lower[..start].chars().last().is_some_and(|c| c.is_whitespace());
+        let after = lower[search..].trim_start();
+        if !preceded || !after.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - after.len() + 1;
+        let value_part = &tag[value_start..];
+        let trimmed = value_part.trim_start();
+        let offset = value_start + (value_part.len() - trimmed.len());
+
+        let (begin, end) = match trimmed.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let close = trimmed[1..].find(q)?;
+                (offset + 1, offset + 1 + close)
+            }
+            Some(_) => {
+                let len = trimmed
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(trimmed.len());
+                (offset, offset + len)
+            }
+            None => return None,
+        };
+
+        return Some((begin, end, decode_entities(&tag[begin..end])));
+    }
+
+    None
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    find_attribute(tag, name).map(|(_, _, value)| value)
+}
+
+fn set_attribute(tag: &str, name: &str, value: &str) -> String {
+    match find_attribute(tag, name) {
+        Some((begin, end, _)) => {
+            let quoted = tag[..begin].ends_with('"') || tag[..begin].ends_with('\'');
+            let encoded = value
+                .replace('&', "&amp;")
+                .replace('"', "&quot;")
+                .replace('\'', "&#39;");
+            if quoted {
+                format!("{}{}{}", &tag[..begin], encoded, &tag[end..])
+            } else {
+                format!("{}\"{}\"{}", &tag[..begin], encoded, &tag[end..])
+            }
+        }
+        None => tag.to_string(),
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    value.replace("&amp;", "&").replace("&quot;", "\"").replace("&#39;", "'")
+}
+
+fn is_remote(url: &str) -> bool {
+    let lower = url.trim_start().to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("//")
+}
+
+fn is_tracker_host(host: &str) -> bool {
+    TRACKER_HOSTS
+        .iter()
+        .any(|tracker| host == *tracker || host.ends_with(&format!(".{}", tracker)))
+}
+
+fn is_tracking_pixel(tag: &str, src: Option<&str>) -> bool {
+    let tiny = |value: Option<String>| {
+        value
+            .map(|v| v.trim().trim_end_matches("px").parse::<u32>().is_ok_and(|n| n <= 1))
+            .unwrap_or(false)
+    };
+    if tiny(attribute(tag, "width")) && tiny(attribute(tag, "height")) {
+        return true;
+    }
+
+    let style = attribute(tag, "style")
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .replace(' ', "");
+    if style.contains("display:none") || (style.contains("width:1px") && style.contains("height:1px")) {
+        return true;
+    }
+
+    let Some(src) = src.filter(|s| is_remote(s)) else {
+        return false;
+    };
+    let absolute = if src.starts_with("//") { format!("https:{}", src) } else { src.to_string() };
+    match reqwest::Url::parse(&absolute) {
+        Ok(url) => {
+            let path = url.path().to_ascii_lowercase();
+            url.host_str().is_some_and(is_tracker_host)
+                || TRACKER_PATHS.iter().any(|p| path.contains(p))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether the link belongs to a click tracker or redirect service, so a
+/// URL in its query is where it leads. Other links keep such parameters,
+/// e.g. the page an unsubscribe link returns to.
+fn is_redirector(url: &reqwest::Url) -> bool {
+    let host_matches = url.host_str().is_some_and(|host| {
+        is_tracker_host(host)
+            || REDIRECT_HOSTS.iter().any(|redirector| host == *redirector || host.ends_with(&format!(".{}", redirector)))
+    });
+    let path = url.path().to_ascii_lowercase();
+    let path_matches = path.split('/').any(|segment| {
+        let segment = segment.split('.').next().unwrap_or_default();
+        REDIRECT_PATHS.contains(&segment)
+    });
+
+    host_matches || path_matches
+}
+
+/// Follow redirect wrappers to the real destination and drop tracking
+/// parameters from it
+fn clean_link(href: &str, report: &mut TrackerReport) -> String {
+    let Ok(mut url) = reqwest::Url::parse(href.trim()) else {
+        return href.to_string();
+    };
+
+    // Unwrap nested redirects, e.g. a click tracker wrapping another
+    for _ in 0..3 {
+        if !is_redirector(&url) {
+            break;
+        }
+        let destination = url
+            .query_pairs()
+            .find(|(key, value)| {
+                REDIRECT_PARAMS.contains(&key.to_ascii_lowercase().as_str()) && is_remote(value)
+            })
+            .and_then(|(_, value)| reqwest::Url::parse(&value).ok());
+
+        match destination {
+            Some(destination) => {
+                url = destination;
+                report.links_unwrapped += 1;
+            }
+            None => break,
+        }
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let kept: Vec<&(String, String)> = pairs
+        .iter()
+        .filter(|(key, _)| {
+            let key = key.to_ascii_lowercase();
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+        })
+        .collect();
+
+    if kept.len() < pairs.len() {
+        report.params_removed += (pairs.len() - kept.len()) as u32;
+        if kept.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(kept.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        }
+    }
+
+    url.to_string()
+}
+
+fn insert_banner(html: &str, removed: u32) -> String {
+    let banner = format!(
+        r#"<div style="font-family: sans-serif; font-size: 12px; color: #555; background: #f3f0ff; border: 1px solid #ddd6fe; border-radius: 6px; padding: 6px 10px; margin: 0 0 12px 0;">Hush removed {} tracker{} from this message.</div>"#,
+        removed,
+        if removed == 1 { "" } else { "s" }
+    );
+
+    let lower = html.to_ascii_lowercase();
+    match lower.find("<body").and_then(|start| tag_end(&html[start..]).map(|end| start + end + 1)) {
+        Some(insert_at) => format!("{}{}{}", &html[..insert_at], banner, &html[insert_at..]),
+        None => format!("{}{}", banner, html),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(href: &str) -> (String, TrackerReport) {
+        let mut report = TrackerReport::default();
+        (clean_link(href, &mut report), report)
+    }
+
+    fn strip(html: &str) -> (String, TrackerReport) {
+        strip_trackers(html, &SanitizeOptions { proxy_image: None })
+    }
+
+    #[test]
+    fn unwraps_links_of_redirect_services() {
+        for href in [
+            "https://www.google.com/url?q=https://example.com/page",
+            "https://nam02.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2Fpage&data=abc",
+            "https://news.us1.list-manage.com/track/click?u=123&id=456&url=https://example.com/page",
+            "https://mailer.example/redirect.php?target=https://example.com/page",
+            "https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2Fpage",
+        ] {
+            let (cleaned, report) = clean(href);
+            assert_eq!(cleaned, "https://example.com/page", "{}", href);
+            assert_eq!(report.links_unwrapped, 1, "{}", href);
+        }
+
+        // A tracker wrapping another redirect
+        let (cleaned, report) =
+            clean("https://t.example/click?url=https%3A%2F%2Fwww.google.com%2Furl%3Fq%3Dhttps%3A%2F%2Fexample.com%2F");
+        assert_eq!(cleaned, "https://example.com/");
+        assert_eq!(report.links_unwrapped, 2);
+    }
+
+    #[test]
+    fn keeps_links_that_only_carry_a_url() {
+        for href in [
+            "https://list.example/unsubscribe?token=abc&redirect=https://example.com/done",
+            "https://app.example/auth/magic?token=xyz&next=https://app.example/home",
+            "https://app.example/login?redirect_url=https://app.example/settings",
+            "https://search.example/search?q=https://example.com/",
+            "https://share.example/share?link=https://example.com/post",
+        ] {
+            let (cleaned, report) = clean(href);
+            assert_eq!(cleaned, href);
+            assert_eq!(report, TrackerReport::default());
+        }
+
+        // Not a URL at all
+        assert_eq!(clean("https://[broken").0, "https://[broken");
+    }
+
+    #[test]
+    fn removes_tracking_parameters() {
+        let (cleaned, report) = clean("https://example.com/a?id=7&utm_source=news&UTM_medium=mail&fbclid=x");
+        assert_eq!(cleaned, "https://example.com/a?id=7");
+        assert_eq!(report.params_removed, 3);
+
+        let (cleaned, _) = clean("https://example.com/a?utm_campaign=spring");
+        assert_eq!(cleaned, "https://example.com/a");
+    }
+
+    #[test]
+    fn finds_attributes_in_any_quoting() {
+        let tag = r#"<a class="x" data-href="no" HREF = 'https://a.example/?x=1&amp;y=2' title=plain>"#;
+        let (begin, end, value) = find_attribute(tag, "href").unwrap();
+        assert_eq!(value, "https://a.example/?x=1&y=2");
+        assert_eq!(&tag[begin..end], "https://a.example/?x=1&amp;y=2");
+        assert_eq!(attribute(tag, "title").as_deref(), Some("plain"));
+
+        // Part of another attribute's name, or without a value
+        assert_eq!(attribute(r#"<a data-href="x">"#, "href"), None);
+        assert_eq!(attribute("<input disabled>", "disabled"), None);
+        assert_eq!(attribute(r#"<a href="unterminated>"#, "href"), None);
+    }
+
+    #[test]
+    fn sets_attributes_with_encoding() {
+        assert_eq!(
+            set_attribute(r#"<a href="old" id=1>"#, "href", "https://a.example/?x=1&y=\"2\""),
+            r#"<a href="https://a.example/?x=1&amp;y=&quot;2&quot;" id=1>"#
+        );
+        assert_eq!(set_attribute("<a href=old>", "href", "new"), r#"<a href="new">"#);
+        assert_eq!(set_attribute("<a>", "href", "new"), "<a>");
+    }
+
+    #[test]
+    fn strips_pixels_and_rewrites_links() {
+        let html = concat!(
+            r#"<html><body class="main"><p>Hi</p>"#,
+            r#"<img src="https://cdn.example/logo.png" width="120">"#,
+            r#"<img src="https://cdn.example/x.gif" width="1" height="1">"#,
+            r#"<img src="https://mailtrack.io/trace/abc.png">"#,
+            r#"<a href="https://example.com/?utm_source=x">shop</a>"#,
+            r#"<a href="https://list.example/unsubscribe?redirect=https://example.com/done">unsubscribe</a>"#,
+            r#"<style>a > img { border: 0 }</style>"#,
+            "</body></html>",
+        );
+
+        let (output, report) = strip(html);
+        assert_eq!(report.pixels_removed, 2);
+        assert_eq!(report.params_removed, 1);
+        assert_eq!(report.links_unwrapped, 0);
+        assert!(output.contains(r#"<img src="https://cdn.example/logo.png" width="120">"#));
+        assert!(!output.contains("x.gif") && !output.contains("mailtrack"));
+        assert!(output.contains(r#"<a href="https://example.com/">shop</a>"#));
+        assert!(output.contains(r#"href="https://list.example/unsubscribe?redirect=https://example.com/done""#));
+        assert!(output.contains("<style>a > img { border: 0 }</style>"));
+        assert!(output.starts_with(r#"<html><body class="main"><div"#));
+        assert!(output.contains("Hush removed 3 trackers"));
+    }
+
+    #[test]
+    fn leaves_clean_messages_unchanged() {
+        let html = r#"<p>Hello <a href="https://example.com/a?id=1">there</a><img src="cid:logo"></p>"#;
+        let (output, report) = strip(html);
+        assert_eq!(output, html);
+        assert_eq!(report, TrackerReport::default());
+
+        // Unterminated tags are copied as they are
+        assert_eq!(strip("<p>broken <a href='x").0, "<p>broken <a href='x");
+    }
+
+    #[test]
+    fn proxies_remaining_remote_images() {
+        let proxy = |src: &str| format!("https://hush.example/proxy/{}", src.trim_start_matches("https://"));
+        let options = SanitizeOptions { proxy_image: Some(&proxy) };
+        let (output, report) = strip_trackers(r#"<img src="https://cdn.example/a.png"><img src="cid:x">"#, &options);
+        assert_eq!(output, r#"<img src="https://hush.example/proxy/cdn.example/a.png"><img src="cid:x">"#);
+        assert_eq!(report.images_proxied, 1);
+        assert_eq!(report.trackers_removed(), 0);
+    }
+
+    #[test]
+    fn inserts_the_banner_after_the_body_tag() {
+        let output = insert_banner(r#"<HTML><Body style="a>b">text</Body>"#, 1);
+        assert!(output.starts_with(r#"<HTML><Body style="a>b"><div"#));
+        assert!(output.contains("Hush removed 1 tracker from"));
+        assert!(output.ends_with("</div>text</Body>"));
+
+        let output = insert_banner("<p>fragment</p>", 2);
+        assert!(output.starts_with("<div"));
+        assert!(output.contains("2 trackers"));
+        assert!(output.ends_with("<p>fragment</p>"));
+    }
+}
@@ -0,0 +1,207 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Shared HTTP client for outbound calls to addresses chosen by users or
+/// senders (push services, user webhooks, image proxy). Host names are
+/// resolved by [`PublicResolver`], so a name pointing at the server's own
+/// network is refused at connect time. Proxies are ignored, since they would
+/// resolve names on our behalf.
+pub fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .no_proxy()
+            .build()
+            .expect("failed to build HTTP client")
+    })
+}
+
+/// Client for addresses the operator configured, which may be internal
+/// (DNS-over-HTTPS resolver, webhooks with `WEBHOOK_ALLOW_PRIVATE_TARGETS`)
+pub fn internal_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("failed to build HTTP client")
+    })
+}
+
+/// Whether the URL points at this host or a private network. Literal
+/// addresses and well-known local names are checked here; other names are
+/// checked when [`client`] resolves them.
+pub fn is_private_host(url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return true;
+    };
+
+    match host.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>() {
+        Ok(ip) => is_private_ip(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost") || host.ends_with(".internal")
+        }
+    }
+}
+
+/// Addresses that are not on the public internet: loopback, private,
+/// shared (CGNAT), link-local (including cloud metadata services),
+/// multicast, reserved and documentation ranges. IPv6 forms that embed an
+/// IPv4 address are judged by that address.
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    if let Some(v4) = embedded_ipv4(ip) {
+        return is_private_ipv4(v4);
+    }
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+}
+
+/// IPv4 address carried by an IPv4-mapped (`::ffff:0:0/96`), IPv4-compatible
+/// (`::/96`), NAT64 (`64:ff9b::/96`) or 6to4 (`2002::/16`) address
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let s = ip.segments();
+    let from = |hi: u16, lo: u16| Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8);
+
+    match s {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] => Some(from(hi, lo)),
+        // `::` and `::1` are handled as IPv6
+        [0, 0, 0, 0, 0, 0, hi, lo] if hi != 0 => Some(from(hi, lo)),
+        [0x64, 0xff9b, 0, 0, 0, 0, hi, lo] => Some(from(hi, lo)),
+        [0x2002, hi, lo, ..] => Some(from(hi, lo)),
+        _ => None,
+    }
+}
+
+/// Resolver for [`client`] that refuses names with any non-public address.
+/// reqwest connects to the addresses returned here, so a name cannot be
+/// re-pointed between the check and the connection.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+
+            if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+                return Err(format!("{} resolves to a non-public address", host).into());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private(ip: &str) -> bool {
+        is_private_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn blocks_non_public_ipv4() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.255",
+            "0.0.0.0",
+            "0.1.2.3",
+            "192.0.0.8",
+            "198.18.0.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(private(ip), "{} should be blocked", ip);
+        }
+    }
+
+    #[test]
+    fn blocks_non_public_ipv6() {
+        for ip in [
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "febf::1",
+            "fec0::1",
+            "ff02::1",
+            "2001:db8::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:7f00:1::",
+        ] {
+            assert!(private(ip), "{} should be blocked", ip);
+        }
+    }
+
+    #[test]
+    fn allows_public_addresses() {
+        for ip in ["1.1.1.1", "8.8.8.8", "100.128.0.1", "2606:4700:4700::1111", "::ffff:1.1.1.1", "2002:101:101::"] {
+            assert!(!private(ip), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn checks_literal_and_local_hosts() {
+        for url in ["http://[::ffff:7f00:1]/", "http://localhost./", "http://api.internal/", "http://0x7f000001/"] {
+            assert!(is_private_host(&reqwest::Url::parse(url).unwrap()), "{} should be blocked", url);
+        }
+        assert!(!is_private_host(&reqwest::Url::parse("https://example.com/").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn resolver_refuses_names_of_local_addresses() {
+        let name: Name = "localhost".parse().unwrap();
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::services::{
+    AccountService, DigestService, LogService, PushService, QuarantineService, StatsService, WebhookService,
+};
+
+/// Start the periodic background jobs
+pub fn spawn(pool: PgPool, config: Config) {
+    let purge_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match AccountService::purge_due_accounts(&purge_pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} deleted accounts", count),
+                Err(e) => error!("Account purge failed: {}", e),
+            }
+        }
+    });
+
+    let digest_pool = pool.clone();
+    let digest_config = config.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match DigestService::send_due_digests(&digest_pool, &digest_config).await {
+                Ok(0) => {}
+                Ok(count) => info!("Sent {} digest emails", count),
+                Err(e) => error!("Digest delivery failed: {}", e),
+            }
+            match DigestService::purge_expired(&digest_pool, &digest_config).await {
+                Ok(0) => {}
+                Ok(count) => info!("Discarded {} expired held messages", count),
+                Err(e) => error!("Held message cleanup failed: {}", e),
+            }
+            match QuarantineService::purge_expired(&digest_pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("Discarded {} expired quarantined messages", count),
+                Err(e) => error!("Quarantine cleanup failed: {}", e),
+            }
+        }
+    });
+
+    // Hash before scrubbing, which leaves entries without a hash alone
+    let log_pool = pool.clone();
+    let log_config = config.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match LogService::backfill_sender_hashes(&log_pool, &log_config).await {
+                Ok(0) => {}
+                Ok(count) => info!("Hashed the senders of {} email log entries", count),
+                Err(e) => error!("Sender hash backfill failed: {}", e),
+            }
+            match LogService::scrub_private(&log_pool, None).await {
+                Ok(0) => {}
+                Ok(count) => info!("Removed senders and subjects from {} email log entries", count),
+                Err(e) => error!("Email log scrubbing failed: {}", e),
+            }
+            match LogService::prune_expired(&log_pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {} email log entries past retention", count),
+                Err(e) => error!("Email log pruning failed: {}", e),
+            }
+        }
+    });
+
+    let stats_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(600));
+        loop {
+            interval.tick().await;
+            match StatsService::rollup(&stats_pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("Rolled up stats for {} alias days", count),
+                Err(e) => error!("Stats rollup failed: {}", e),
+            }
+        }
+    });
+
+    let webhook_pool = pool.clone();
+    let webhook_config = config.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            if let Err(e) = WebhookService::deliver_due(&webhook_pool, &webhook_config).await {
+                error!("Webhook delivery failed: {}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(300));
+        loop {
+            interval.tick().await;
+            match PushService::notify_expiring_aliases(&pool, &config).await {
+                Ok(0) => {}
+                Ok(count) => info!("Sent expiry warnings for {} aliases", count),
+                Err(e) => error!("Alias expiry notifications failed: {}", e),
+            }
+            match WebhookService::emit_expired_aliases(&pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("Queued alias.expired events for {} aliases", count),
+                Err(e) => error!("Alias expiry webhooks failed: {}", e),
+            }
+        }
+    });
+}
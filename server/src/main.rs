@@ -1,10 +1,22 @@
 mod api;
 mod auth;
 mod config;
+mod crypto;
 mod db;
+mod dns;
 mod error;
+mod events;
+mod generator;
+mod html;
+mod http;
+mod jobs;
 mod models;
+mod pgp;
+mod rate_limit;
 mod services;
+mod spam;
+mod validation;
+mod webpush;
 
 use axum::{
     http::StatusCode,
@@ -12,6 +24,7 @@ use axum::{
     Router,
 };
 use std::net::SocketAddr;
+use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
     cors::{Any, CorsLayer},
@@ -37,6 +50,11 @@ async fn main() -> anyhow::Result<()> {
     let pool = db::init_db(&config.database_url).await?;
     info!("Database connection established");
 
+    services::SystemDomainService::ensure_default(&pool, &config).await?;
+
+    // Start background jobs
+    jobs::spawn(pool.clone(), config.clone());
+
     // Build application
     let app = create_app(pool, config.clone()).await?;
 
@@ -45,64 +63,257 @@ async fn main() -> anyhow::Result<()> {
     info!("Server starting on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
 async fn create_app(pool: sqlx::PgPool, config: Config) -> anyhow::Result<Router> {
+    // Live email log events, fanned out across instances via LISTEN/NOTIFY
+    let event_bus = events::EventBus::start(pool.clone());
+
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any)
         .expose_headers(Any);
 
+    // Rate limits, keyed by client IP
+    let auth_limit = rate_limit::layer(
+        Duration::from_secs(config.rate_limit_auth_period_secs),
+        config.rate_limit_auth_burst,
+        config.trust_proxy_headers,
+    );
+    let webhook_limit = rate_limit::layer(
+        Duration::from_millis(config.rate_limit_webhook_period_ms),
+        config.rate_limit_webhook_burst,
+        config.trust_proxy_headers,
+    );
+
+    // Rate limited endpoints that also require a full login session
+    let session_auth_routes = Router::new()
+        .route(
+            "/api/v1/targets/request_verify",
+            post(api::targets::request_verify),
+        )
+        .route("/api/v1/account/email", post(api::account::change_email))
+        .route("/api/v1/targets/pgp_key/test", post(api::targets::test_pgp_key))
+        .route_layer(axum::middleware::from_fn(auth::session_only));
+
+    // Credential and verification endpoints (rate limited)
+    let auth_routes = Router::new()
+        .route("/api/v1/auth/login", post(api::auth::login))
+        .route("/api/v1/auth/register", post(api::auth::register))
+        .route(
+            "/api/v1/auth/resend_verification",
+            post(api::auth::resend_verification),
+        )
+        .route("/api/v1/device/code", post(api::devices::request_code))
+        .route(
+            "/api/v1/messages/release",
+            get(api::messages::confirm_release).post(api::messages::release),
+        )
+        .merge(session_auth_routes)
+        .route_layer(auth_limit);
+
+    // Email forwarding webhooks (public, but should be secured with webhook secret in production)
+    let webhook_routes = Router::new()
+        .route("/api/v1/incoming/mailgun", post(api::incoming::handle_incoming_email))
+        .route("/api/v1/incoming/mailgun/json", post(api::incoming::handle_incoming_email_json))
+        .route("/api/v1/incoming/sendgrid", post(api::incoming::handle_sendgrid_webhook))
+        .route_layer(webhook_limit);
+
     // Public routes
     let public_routes = Router::new()
         .route("/health", get(health_check))
-        .route("/api/v1/auth/login", post(api::auth::login))
         .route("/api/v1/auth/refresh", post(api::auth::refresh))
-        .route("/api/v1/auth/register", post(api::auth::register))
+        .route("/api/v1/device/token", post(api::devices::token))
+        .route("/api/v1/auth/verify", get(api::auth::verify_email))
+        .route(
+            "/api/v1/notifications/vapid_public_key",
+            get(api::notifications::vapid_public_key),
+        )
         .route("/api/v1/targets/verify", get(api::targets::verify_get).post(api::targets::verify_post))
-        // Email forwarding webhook (public, but should be secured with webhook secret in production)
-        .route("/api/v1/incoming/mailgun", post(api::incoming::handle_incoming_email))
-        .route("/api/v1/incoming/mailgun/json", post(api::incoming::handle_incoming_email_json))
-        .route("/api/v1/incoming/sendgrid", post(api::incoming::handle_sendgrid_webhook));
+        .route("/api/v1/image_proxy", get(api::images::proxy))
+        .route(
+            "/api/v1/account/email/confirm",
+            get(api::account::confirm_email_change),
+        );
 
-    // Protected routes (require authentication)
-    let protected_routes = Router::new()
+    // Routes open to device credentials (the `aliases` scope) as well as sessions
+    let device_routes = Router::new()
         .route("/api/v1/auth/logout", post(api::auth::logout))
         .route("/api/v1/aliases", get(api::aliases::list).post(api::aliases::create))
+        .route("/api/v1/aliases/deleted", get(api::aliases::list_deleted))
         .route(
             "/api/v1/aliases/:id",
             axum::routing::delete(api::aliases::delete),
         )
+        .route(
+            "/api/v1/aliases/:id/restore",
+            post(api::aliases::restore),
+        )
         .route(
             "/api/v1/aliases/:id/toggle",
             post(api::aliases::toggle),
         )
+        .route("/api/v1/system_domains", get(api::domains::list_system))
+        .route(
+            "/api/v1/devices/:id",
+            axum::routing::delete(api::devices::revoke),
+        );
+
+    // Protected routes (require a full login session)
+    let protected_routes = Router::new()
+        .route("/api/v1/aliases/bulk", post(api::aliases::bulk))
+        .route("/api/v1/aliases/import", post(api::aliases::import))
+        .route("/api/v1/aliases/export", get(api::aliases::export))
+        .route(
+            "/api/v1/aliases/:id/delivery_mode",
+            post(api::aliases::set_delivery_mode),
+        )
+        .route(
+            "/api/v1/aliases/:id/tracker_protection",
+            post(api::aliases::set_tracker_protection),
+        )
+        .route(
+            "/api/v1/aliases/:id/block_spam_tags",
+            post(api::aliases::set_block_spam_tags),
+        )
+        .route(
+            "/api/v1/aliases/:id/tags",
+            get(api::aliases::tags).post(api::aliases::block_tag),
+        )
+        .route(
+            "/api/v1/aliases/:id/tags/:tag",
+            axum::routing::delete(api::aliases::unblock_tag),
+        )
         .route("/api/v1/aliases/:id/logs", get(api::aliases::logs))
+        .route("/api/v1/aliases/:id/stats", get(api::stats::alias_stats))
         .route(
-            "/api/v1/targets/request_verify",
-            post(api::targets::request_verify),
+            "/api/v1/directories",
+            get(api::directories::list).post(api::directories::create),
+        )
+        .route(
+            "/api/v1/directories/:id",
+            axum::routing::delete(api::directories::delete),
+        )
+        .route(
+            "/api/v1/directories/:id/toggle",
+            post(api::directories::toggle),
+        )
+        .route("/api/v1/domains", get(api::domains::list).post(api::domains::create))
+        .route(
+            "/api/v1/domains/:id",
+            get(api::domains::get).delete(api::domains::delete),
+        )
+        .route("/api/v1/domains/:id/verify", post(api::domains::verify))
+        .route(
+            "/api/v1/domains/:id/catch_all",
+            post(api::domains::set_catch_all),
+        )
+        .route("/api/v1/events", get(api::events::stream))
+        .route("/api/v1/logs", get(api::logs::search))
+        .route("/api/v1/stats", get(api::stats::account_stats))
+        .route("/api/v1/quarantine", get(api::quarantine::list))
+        .route(
+            "/api/v1/quarantine/:id",
+            get(api::quarantine::preview).delete(api::quarantine::delete),
+        )
+        .route(
+            "/api/v1/quarantine/:id/release",
+            post(api::quarantine::release),
         )
         .route(
             "/api/v1/targets",
             get(api::targets::get_current),
         )
+        .route(
+            "/api/v1/targets/pgp_key",
+            axum::routing::put(api::targets::set_pgp_key).delete(api::targets::remove_pgp_key),
+        )
         .route(
             "/api/v1/notifications/subscribe",
             post(api::notifications::subscribe),
+        )
+        .route(
+            "/api/v1/notifications/subscriptions",
+            get(api::notifications::list),
+        )
+        .route(
+            "/api/v1/notifications/subscriptions/:id",
+            axum::routing::delete(api::notifications::unsubscribe),
+        )
+        .route(
+            "/api/v1/webhooks",
+            get(api::webhooks::list).post(api::webhooks::create),
+        )
+        .route(
+            "/api/v1/webhooks/:id",
+            axum::routing::delete(api::webhooks::delete),
+        )
+        .route(
+            "/api/v1/webhooks/:id/deliveries",
+            get(api::webhooks::deliveries),
+        )
+        .route("/api/v1/webhooks/:id/test", post(api::webhooks::send_test))
+        .route(
+            "/api/v1/account",
+            axum::routing::delete(api::account::delete_account),
+        )
+        .route("/api/v1/account/export", get(api::account::export))
+        .route(
+            "/api/v1/account/alias_generation",
+            get(api::account::alias_generation).put(api::account::update_alias_generation),
+        )
+        .route(
+            "/api/v1/account/spam",
+            get(api::account::spam_settings).put(api::account::update_spam_settings),
+        )
+        .route(
+            "/api/v1/account/logs",
+            get(api::account::log_settings).put(api::account::update_log_settings),
+        )
+        .route("/api/v1/device/approve", post(api::devices::approve))
+        .route("/api/v1/devices", get(api::devices::list))
+        .route_layer(axum::middleware::from_fn(auth::session_only));
+
+    // Operator endpoints (require ADMIN_TOKEN)
+    let admin_routes = Router::new()
+        .route(
+            "/api/v1/admin/system_domains",
+            get(api::admin::list_system_domains).post(api::admin::create_system_domain),
+        )
+        .route(
+            "/api/v1/admin/system_domains/:domain",
+            axum::routing::put(api::admin::update_system_domain),
+        )
+        .route(
+            "/api/v1/admin/reserved_names",
+            get(api::admin::list_reserved_names).post(api::admin::add_reserved_name),
+        )
+        .route(
+            "/api/v1/admin/reserved_names/:name",
+            axum::routing::delete(api::admin::remove_reserved_name),
         );
 
     let router = Router::new()
+        .merge(auth_routes)
+        .merge(webhook_routes)
         .merge(public_routes)
+        .merge(device_routes)
         .merge(protected_routes)
+        .merge(admin_routes)
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
                 .layer(cors)
                 .layer(axum::Extension(pool))
+                .layer(axum::Extension(event_bus))
                 .layer(axum::Extension(config)),
         );
 
@@ -8,6 +8,10 @@ pub struct User {
     pub id: Uuid,
     pub email: String,
     pub password_hash: String,
+    pub failed_login_count: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub email_verified: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -19,6 +23,16 @@ pub struct Alias {
     pub address: String,
     pub status: AliasStatus,
     pub alias_type: AliasType,
+    pub delivery_mode: DeliveryMode,
+    pub quarantine_when_paused: bool,
+    pub tracker_protection: TrackerProtection,
+    /// Custom domain the alias belongs to; `None` for the system domain
+    pub domain_id: Option<Uuid>,
+    /// Directory the alias was created in by incoming mail
+    pub directory_id: Option<Uuid>,
+    /// Block subaddress tags that receive spam
+    pub block_spam_tags: bool,
+    pub label: Option<String>,
     pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -26,6 +40,7 @@ pub struct Alias {
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "alias_status", rename_all = "lowercase")]
+#[serde(rename_all(deserialize = "lowercase"))]
 pub enum AliasStatus {
     Active,
     Paused,
@@ -41,6 +56,50 @@ pub enum AliasType {
     Temporary,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "alias_delivery_mode", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    Immediate,
+    DailyDigest,
+    WeeklyDigest,
+}
+
+impl DeliveryMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryMode::Immediate => "immediate",
+            DeliveryMode::DailyDigest => "daily_digest",
+            DeliveryMode::WeeklyDigest => "weekly_digest",
+        }
+    }
+}
+
+/// Cleaning applied to forwarded HTML
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "tracker_protection", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerProtection {
+    /// Forward HTML untouched
+    Off,
+    /// Remove tracking pixels and unwrap tracking links
+    Standard,
+    /// Also load remaining remote images through the image proxy
+    ProxyImages,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "alias_strategy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AliasStrategy {
+    /// `hush-` followed by hex digits
+    Hex,
+    /// Dictionary words and a number, e.g. `quiet.otter42`
+    Words,
+    /// Pronounceable syllables and a number, e.g. `tavokemi42`
+    Syllables,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct TargetEmail {
     pub id: Uuid,
@@ -49,10 +108,69 @@ pub struct TargetEmail {
     pub verified: bool,
     pub verification_token: Option<String>,
     pub verification_expires_at: Option<DateTime<Utc>>,
+    pub pgp_public_key: Option<String>,
+    pub pgp_fingerprint: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Domain registered by a user for their own aliases
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Domain {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub domain: String,
+    pub verification_token: String,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub catch_all: bool,
+    pub last_checked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Namespace reserved by a user on the system domains
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Directory {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "reserved_name_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReservedNameKind {
+    /// The alias may not be exactly this name
+    Reserved,
+    /// The alias may not contain this word
+    Profanity,
+}
+
+/// Entry in the operator's list of names custom aliases may not use
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ReservedName {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: ReservedNameKind,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Shared alias domain managed by the operator
+#[derive(Debug, Clone, FromRow)]
+pub struct SystemDomain {
+    pub id: Uuid,
+    pub domain: String,
+    pub enabled: bool,
+    pub is_default: bool,
+    pub dkim_selector: String,
+    /// PKCS#8 PEM
+    pub dkim_private_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct EmailLog {
     pub id: Uuid,
@@ -64,13 +182,285 @@ pub struct EmailLog {
     pub created_at: DateTime<Utc>,
 }
 
+/// Email log entry as delivered on the live event stream
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LogEvent {
+    pub seq: i64,
+    pub id: Uuid,
+    pub alias_id: Uuid,
+    pub alias_address: String,
+    pub from_email: String,
+    pub subject: String,
+    pub status: EmailStatus,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Conditions for searching email logs across a user's aliases
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    pub alias_ids: Option<Vec<Uuid>>,
+    pub status: Option<EmailStatus>,
+    /// Case-insensitive substring of the sender address
+    pub sender: Option<String>,
+    /// Hash of a complete sender address, which also finds entries logged
+    /// in privacy mode
+    pub sender_hash: Option<String>,
+    pub tag: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Full-text search on the subject, in web search syntax
+    pub query: Option<String>,
+}
+
+/// Length of the time buckets in usage statistics
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl StatsBucket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsBucket::Day => "day",
+            StatsBucket::Week => "week",
+            StatsBucket::Month => "month",
+        }
+    }
+}
+
+/// Layout of an alias import
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// SimpleLogin alias CSV export
+    SimpleLogin,
+    /// addy.io (AnonAddy) alias CSV export
+    Addy,
+    /// CSV with a header row, as written by the alias export
+    Csv,
+    /// JSON array of alias objects, as written by the alias export
+    Json,
+}
+
+impl ImportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportFormat::SimpleLogin => "simplelogin",
+            ImportFormat::Addy => "addy",
+            ImportFormat::Csv => "csv",
+            ImportFormat::Json => "json",
+        }
+    }
+}
+
+/// What to do with imported addresses on domains the user does not control
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmanagedImport {
+    /// Create a generated alias and record the original address with it
+    #[default]
+    Map,
+    Skip,
+}
+
+/// Alias with its delivery counts, as exported for backup or migration
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AliasExport {
+    pub address: String,
+    pub status: String,
+    pub label: Option<String>,
+    pub alias_type: String,
+    pub delivery_mode: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub forwarded: i64,
+    /// Rejected or quarantined
+    pub blocked: i64,
+    pub last_received_at: Option<DateTime<Utc>>,
+    /// Address at the service the alias was imported from
+    pub imported_from: Option<String>,
+}
+
+/// Subaddress tag seen on an alias, or blocked before any mail arrived
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SubaddressTag {
+    pub tag: String,
+    pub received: i64,
+    /// Messages tagged or quarantined as spam
+    pub spam: i64,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub blocked: bool,
+    pub auto_blocked: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "email_status", rename_all = "lowercase")]
+#[serde(rename_all(deserialize = "lowercase"))]
 pub enum EmailStatus {
     Forwarded,
     Bounced,
     Rejected,
     Pending,
+    Held,
+    Quarantined,
+}
+
+/// Per-user spam thresholds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromRow)]
+pub struct SpamSettings {
+    pub spam_tag_threshold: f64,
+    pub spam_quarantine_threshold: Option<f64>,
+}
+
+/// How long a user's email logs are kept and whether senders and subjects
+/// are stored
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromRow)]
+pub struct LogSettings {
+    pub log_retention_days: Option<i32>,
+    pub log_privacy: bool,
+}
+
+/// Quarantine entry joined with its email log for listing
+#[derive(Debug, Clone, FromRow)]
+pub struct QuarantinedMessage {
+    pub id: Uuid,
+    pub email_log_id: Uuid,
+    pub alias_id: Uuid,
+    pub alias_address: String,
+    pub tracker_protection: TrackerProtection,
+    pub from_email: String,
+    pub subject: String,
+    pub reason: String,
+    pub nonce: Vec<u8>,
+    pub content: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Message kept back for a digest, with the content needed to release it
+#[derive(Debug, Clone, FromRow)]
+pub struct HeldMessage {
+    pub id: Uuid,
+    pub alias_id: Uuid,
+    pub alias_address: String,
+    pub tracker_protection: TrackerProtection,
+    pub user_id: Uuid,
+    pub from_email: String,
+    pub subject: String,
+    pub body_plain: Option<String>,
+    pub body_html: Option<String>,
+    pub release_token: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceAuthorization {
+    pub id: Uuid,
+    pub device_code: String,
+    pub user_code: String,
+    pub client_name: String,
+    pub scope: String,
+    pub status: DeviceAuthStatus,
+    pub user_id: Option<Uuid>,
+    pub credential_id: Option<Uuid>,
+    pub poll_interval: i32,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "device_auth_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceAuthStatus {
+    Pending,
+    Approved,
+    Denied,
+    Consumed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub client_name: String,
+    pub scope: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PushSubscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub platform: String,
+    pub device_name: Option<String>,
+    pub events: Vec<String>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub endpoint_id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SecurityEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub event_type: String,
+    pub email: Option<String>,
+    pub ip_address: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EmailChangeRequest {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub new_email: String,
+    pub old_token: String,
+    pub new_token: String,
+    pub old_confirmed_at: Option<DateTime<Utc>>,
+    pub new_confirmed_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
 }
 
 // Request/Response DTOs
@@ -99,6 +489,10 @@ pub struct CreateAliasRequest {
     pub alias_type: AliasType,
     pub custom: Option<String>,
     pub ttl_minutes: Option<u64>,
+    /// A verified custom domain; defaults to the system domain
+    pub domain: Option<String>,
+    /// How to generate the address; defaults to the user's setting
+    pub strategy: Option<AliasStrategy>,
 }
 
 fn default_alias_type() -> AliasType {
@@ -110,12 +504,121 @@ pub struct AliasResponse {
     pub id: String,
     pub address: String,
     pub status: String,
+    pub delivery_mode: DeliveryMode,
+    pub quarantine_when_paused: bool,
+    pub tracker_protection: TrackerProtection,
+    pub block_spam_tags: bool,
+    pub label: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ToggleAliasRequest {
     pub enabled: bool,
+    /// Quarantine mail sent to the alias while it is paused
+    pub quarantine_when_paused: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetDeliveryModeRequest {
+    pub delivery_mode: DeliveryMode,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetTrackerProtectionRequest {
+    pub tracker_protection: TrackerProtection,
+}
+
+/// Aliases to change in bulk: explicit IDs or a filter, and the action
+#[derive(Debug, Deserialize)]
+pub struct BulkAliasRequest {
+    pub ids: Option<Vec<Uuid>>,
+    pub filter: Option<BulkAliasFilter>,
+    #[serde(flatten)]
+    pub action: BulkAction,
+    /// Report what would change without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkAliasFilter {
+    /// Defaults to every alias that is not deleted
+    pub status: Option<AliasStatus>,
+    pub label: Option<String>,
+    pub domain: Option<String>,
+    /// Substring of the address
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    Pause,
+    Activate,
+    Delete,
+    SetLabel { label: Option<String> },
+    SetExpiry { expires_at: Option<DateTime<Utc>> },
+    /// Not supported: every alias forwards to the account's target
+    ChangeTarget,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetBlockSpamTagsRequest {
+    pub block_spam_tags: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockTagRequest {
+    pub tag: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDomainRequest {
+    pub domain: String,
+    #[serde(default)]
+    pub catch_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetCatchAllRequest {
+    pub catch_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDirectoryRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToggleDirectoryRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSystemDomainRequest {
+    pub domain: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub is_default: bool,
+    pub dkim_selector: Option<String>,
+    /// PEM private key; a 2048-bit RSA key is generated when omitted
+    pub dkim_private_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateReservedNameRequest {
+    pub name: String,
+    pub kind: ReservedNameKind,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSystemDomainRequest {
+    pub enabled: Option<bool>,
+    /// Only `true` is accepted; make another domain the default instead
+    pub is_default: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -123,6 +626,12 @@ pub struct RequestVerifyRequest {
     pub target: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SetPgpKeyRequest {
+    /// ASCII-armored OpenPGP public key
+    pub public_key: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RequestVerifyResponse {
     pub message: String,
@@ -134,3 +643,84 @@ pub struct VerifyRequest {
     pub token: String,
 }
 
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceCodeRequest {
+    pub client_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceTokenRequest {
+    pub device_code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub scope: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceApproveRequest {
+    pub user_code: String,
+    #[serde(default = "default_true")]
+    pub approve: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailRequest {
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAliasGenerationRequest {
+    pub strategy: AliasStrategy,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSpamSettingsRequest {
+    pub tag_threshold: f64,
+    /// Omit or set to null to never quarantine spam
+    pub quarantine_threshold: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLogSettingsRequest {
+    /// Days to keep email logs; omit or set to null to keep them
+    pub retention_days: Option<i32>,
+    pub privacy_mode: bool,
+}
@@ -0,0 +1,911 @@
+//! Minimal OpenPGP (RFC 4880) support for encrypting forwarded mail to a
+//! user's public key. Only what is needed to encrypt is implemented: reading
+//! transferable public keys and writing a public-key encrypted session key
+//! with an integrity-protected AES-256 data packet. Encryption keys may be
+//! RSA, ECDH over NIST P-256 (RFC 6637) or ECDH over Curve25519 as written
+//! by GnuPG (cv25519).
+
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::{Aes128, Aes192, Aes256};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use p256::ecdh::EphemeralSecret;
+use rand::{rngs::OsRng, Rng};
+use rsa::{BigUint, Pkcs1v15Encrypt, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x25519_dalek::StaticSecret;
+
+use crate::error::{AppError, Result};
+
+const PUBLIC_KEY_BLOCK: &str = "PGP PUBLIC KEY BLOCK";
+const MESSAGE_BLOCK: &str = "PGP MESSAGE";
+
+// Packet tags
+const TAG_PKESK: u8 = 1;
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_LITERAL_DATA: u8 = 11;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_SEIPD: u8 = 18;
+const TAG_MDC: u8 = 19;
+
+// Public key algorithms
+const ALGO_RSA: u8 = 1;
+const ALGO_RSA_ENCRYPT_ONLY: u8 = 2;
+const ALGO_ECDH: u8 = 18;
+
+// Symmetric algorithms
+const CIPHER_AES128: u8 = 7;
+const CIPHER_AES192: u8 = 8;
+const CIPHER_AES256: u8 = 9;
+
+// Hash algorithms
+const HASH_SHA256: u8 = 8;
+const HASH_SHA384: u8 = 9;
+const HASH_SHA512: u8 = 10;
+
+// Key flags allowing encryption of communications or storage
+const FLAGS_ENCRYPT: u8 = 0x04 | 0x08;
+
+/// Object identifier of NIST P-256
+const OID_NIST_P256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+
+/// Object identifier of Curve25519 for ECDH
+const OID_CURVE25519: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x97, 0x55, 0x01, 0x05, 0x01];
+
+/// Prefix of a native Curve25519 point in an MPI
+const CURVE25519_PREFIX: u8 = 0x40;
+
+/// Largest RSA modulus accepted, in bits
+const MAX_RSA_BITS: usize = 8192;
+
+/// A validated OpenPGP public key with a usable encryption key
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    /// Fingerprint of the primary key, upper-case hex
+    pub fingerprint: String,
+    pub user_ids: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    encryption_key: KeyPacket,
+}
+
+#[derive(Debug, Clone)]
+enum KeyMaterial {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    EcdhP256 { point: Vec<u8>, hash: u8, cipher: u8 },
+    EcdhX25519 { point: [u8; 32], hash: u8, cipher: u8 },
+    Unsupported,
+}
+
+#[derive(Debug, Clone)]
+struct KeyPacket {
+    created: u32,
+    algorithm: u8,
+    material: KeyMaterial,
+    fingerprint: [u8; 20],
+    /// From the most recent self-signature
+    flags: Option<u8>,
+    expires_after: Option<u32>,
+    revoked: bool,
+}
+
+impl KeyPacket {
+    fn key_id(&self) -> &[u8] {
+        &self.fingerprint[12..]
+    }
+
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_after
+            .filter(|secs| *secs > 0)
+            .and_then(|secs| Utc.timestamp_opt(self.created as i64 + secs as i64, 0).single())
+    }
+
+    fn usable(&self, now: DateTime<Utc>) -> bool {
+        !self.revoked && self.expires_at().is_none_or(|at| at > now)
+    }
+
+    /// Keys without key flags are assumed to allow any use their algorithm supports
+    fn can_encrypt(&self) -> bool {
+        !matches!(self.material, KeyMaterial::Unsupported)
+            && self.flags.is_none_or(|flags| flags & FLAGS_ENCRYPT != 0)
+    }
+}
+
+impl PublicKey {
+    /// Read an ASCII-armored transferable public key and pick the key to
+    /// encrypt to. Self-signatures are read for key flags, expiry and
+    /// revocation but not verified: the key is supplied by its owner.
+    pub fn parse(armored: &str) -> Result<Self> {
+        let data = dearmor(armored, PUBLIC_KEY_BLOCK)?;
+        let packets = read_packets(&data)?;
+
+        let mut primary: Option<KeyPacket> = None;
+        let mut subkeys: Vec<KeyPacket> = Vec::new();
+        let mut user_ids = Vec::new();
+
+        for (tag, body) in packets {
+            match tag {
+                TAG_PUBLIC_KEY if primary.is_none() => primary = Some(read_key(body)?),
+                TAG_PUBLIC_KEY => return Err(invalid("more than one primary key")),
+                TAG_PUBLIC_SUBKEY => subkeys.push(read_key(body)?),
+                TAG_USER_ID => user_ids.push(String::from_utf8_lossy(body).into_owned()),
+                TAG_SIGNATURE => {
+                    let Some(signature) = read_signature(body) else {
+                        continue;
+                    };
+                    // Binding signatures follow the key they apply to
+                    let target = match signature.kind {
+                        0x18 | 0x28 => subkeys.last_mut(),
+                        0x10..=0x13 | 0x1F | 0x20 => primary.as_mut(),
+                        _ => None,
+                    };
+                    if let Some(key) = target {
+                        if signature.kind == 0x20 || signature.kind == 0x28 {
+                            key.revoked = true;
+                        } else {
+                            key.flags = signature.flags.or(key.flags);
+                            key.expires_after = signature.expires_after.or(key.expires_after);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let primary = primary.ok_or_else(|| invalid("no public key found"))?;
+        if user_ids.is_empty() {
+            return Err(invalid("the key has no user ID"));
+        }
+
+        let now = Utc::now();
+        if !primary.usable(now) {
+            return Err(invalid("the key is expired or revoked"));
+        }
+
+        // Prefer the newest encryption subkey, falling back to the primary key
+        subkeys.sort_by_key(|k| std::cmp::Reverse(k.created));
+        let encryption_key = subkeys
+            .into_iter()
+            .chain(std::iter::once(primary.clone()))
+            .find(|k| k.usable(now) && k.can_encrypt())
+            .ok_or_else(|| {
+                invalid("no usable encryption key (supported: RSA, ECDH on NIST P-256 or Curve25519)")
+            })?;
+
+        Ok(PublicKey {
+            fingerprint: hex(&primary.fingerprint),
+            user_ids,
+            expires_at: primary.expires_at(),
+            encryption_key,
+        })
+    }
+
+    /// Encrypt `data` to this key as an ASCII-armored OpenPGP message
+    pub fn encrypt(&self, data: &[u8]) -> Result<String> {
+        let session_key: [u8; 32] = OsRng.gen();
+
+        let mut message = packet(TAG_PKESK, &self.session_key_packet(&session_key)?);
+        message.extend(packet(TAG_SEIPD, &encrypt_data(&session_key, data)));
+
+        Ok(armor(MESSAGE_BLOCK, &message))
+    }
+
+    fn session_key_packet(&self, session_key: &[u8; 32]) -> Result<Vec<u8>> {
+        let key = &self.encryption_key;
+
+        // Algorithm, key and two-octet checksum, as in RFC 4880 5.1
+        let checksum = session_key.iter().map(|b| *b as u16).fold(0u16, u16::wrapping_add);
+        let mut payload = vec![CIPHER_AES256];
+        payload.extend_from_slice(session_key);
+        payload.extend_from_slice(&checksum.to_be_bytes());
+
+        let mut body = vec![3];
+        body.extend_from_slice(key.key_id());
+        body.push(key.algorithm);
+
+        match &key.material {
+            KeyMaterial::Rsa { n, e } => {
+                let public = RsaPublicKey::new_with_max_size(
+                    BigUint::from_bytes_be(n),
+                    BigUint::from_bytes_be(e),
+                    MAX_RSA_BITS,
+                )
+                .map_err(|e| encryption_failed(&e.to_string()))?;
+                let encrypted = public
+                    .encrypt(&mut OsRng, Pkcs1v15Encrypt, &payload)
+                    .map_err(|e| encryption_failed(&e.to_string()))?;
+                body.extend(mpi(&encrypted));
+            }
+            KeyMaterial::EcdhP256 { point, hash, cipher } => {
+                let recipient = p256::PublicKey::from_sec1_bytes(point)
+                    .map_err(|_| encryption_failed("invalid ECDH point"))?;
+                let ephemeral = EphemeralSecret::random(&mut OsRng);
+                let shared = ephemeral.diffie_hellman(&recipient);
+
+                let wrapped = wrap_session_key(key, OID_NIST_P256, *hash, *cipher, shared.raw_secret_bytes(), payload)?;
+                body.extend(mpi(&ephemeral.public_key().to_sec1_bytes()));
+                body.push(wrapped.len() as u8);
+                body.extend(wrapped);
+            }
+            KeyMaterial::EcdhX25519 { point, hash, cipher } => {
+                let ephemeral = StaticSecret::random_from_rng(OsRng);
+                let shared = ephemeral.diffie_hellman(&x25519_dalek::PublicKey::from(*point));
+                if !shared.was_contributory() {
+                    return Err(encryption_failed("invalid ECDH point"));
+                }
+
+                let wrapped = wrap_session_key(key, OID_CURVE25519, *hash, *cipher, shared.as_bytes(), payload)?;
+                let mut ephemeral_point = vec![CURVE25519_PREFIX];
+                ephemeral_point.extend_from_slice(x25519_dalek::PublicKey::from(&ephemeral).as_bytes());
+                body.extend(mpi(&ephemeral_point));
+                body.push(wrapped.len() as u8);
+                body.extend(wrapped);
+            }
+            KeyMaterial::Unsupported => return Err(encryption_failed("unsupported key")),
+        }
+
+        Ok(body)
+    }
+}
+
+/// Whether a message is already encrypted: its only body is a single
+/// armored OpenPGP message, as when the sender used inline PGP or the
+/// provider extracted the encrypted part of a PGP/MIME message. Any text
+/// outside the block, or an HTML body, would travel in the clear, so such
+/// messages are encrypted again.
+pub fn is_encrypted(text_body: Option<&str>, html_body: Option<&str>) -> bool {
+    let begin = format!("-----BEGIN {}-----", MESSAGE_BLOCK);
+    let end = format!("-----END {}-----", MESSAGE_BLOCK);
+
+    match (text_body.map(str::trim), html_body) {
+        (Some(text), None) => {
+            text.starts_with(&begin)
+                && text.ends_with(&end)
+                && text.matches(&begin).count() == 1
+                && text.matches(&end).count() == 1
+        }
+        _ => false,
+    }
+}
+
+fn invalid(reason: &str) -> AppError {
+    AppError::Validation(format!("Invalid OpenPGP key: {}", reason))
+}
+
+fn encryption_failed(reason: &str) -> AppError {
+    AppError::Internal(format!("OpenPGP encryption failed: {}", reason))
+}
+
+/// Split binary OpenPGP data into `(tag, body)` packets
+fn read_packets(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+    let truncated = || invalid("truncated packet");
+    let mut packets = Vec::new();
+
+    while !data.is_empty() {
+        let header = data[0];
+        if header & 0x80 == 0 {
+            return Err(invalid("malformed packet header"));
+        }
+
+        let (tag, length, offset) = if header & 0x40 != 0 {
+            // New format
+            let first = *data.get(1).ok_or_else(truncated)? as usize;
+            match first {
+                0..=191 => (header & 0x3F, first, 2),
+                192..=223 => {
+                    let second = *data.get(2).ok_or_else(truncated)? as usize;
+                    (header & 0x3F, ((first - 192) << 8) + second + 192, 3)
+                }
+                255 => {
+                    let bytes = data.get(2..6).ok_or_else(truncated)?;
+                    (header & 0x3F, u32::from_be_bytes(bytes.try_into().unwrap()) as usize, 6)
+                }
+                _ => return Err(invalid("partial body lengths are not allowed in keys")),
+            }
+        } else {
+            // Old format
+            let tag = (header >> 2) & 0x0F;
+            match header & 0x03 {
+                0 => (tag, *data.get(1).ok_or_else(truncated)? as usize, 2),
+                1 => {
+                    let bytes = data.get(1..3).ok_or_else(truncated)?;
+                    (tag, u16::from_be_bytes(bytes.try_into().unwrap()) as usize, 3)
+                }
+                2 => {
+                    let bytes = data.get(1..5).ok_or_else(truncated)?;
+                    (tag, u32::from_be_bytes(bytes.try_into().unwrap()) as usize, 5)
+                }
+                _ => (tag, data.len() - 1, 1),
+            }
+        };
+
+        let body = data.get(offset..offset + length).ok_or_else(truncated)?;
+        packets.push((tag, body));
+        data = &data[offset + length..];
+    }
+
+    Ok(packets)
+}
+
+fn read_key(body: &[u8]) -> Result<KeyPacket> {
+    if body.first() != Some(&4) {
+        return Err(invalid("only version 4 keys are supported"));
+    }
+    let header = body.get(..6).ok_or_else(|| invalid("truncated key"))?;
+    let created = u32::from_be_bytes(header[1..5].try_into().unwrap());
+    let algorithm = header[5];
+    let mut rest = &body[6..];
+
+    let material = match algorithm {
+        ALGO_RSA | ALGO_RSA_ENCRYPT_ONLY => {
+            let n = read_mpi(&mut rest)?;
+            let e = read_mpi(&mut rest)?;
+            KeyMaterial::Rsa { n, e }
+        }
+        ALGO_ECDH => {
+            let oid_len = *rest.first().ok_or_else(|| invalid("truncated key"))? as usize;
+            let oid = rest.get(1..1 + oid_len).ok_or_else(|| invalid("truncated key"))?.to_vec();
+            rest = &rest[1 + oid_len..];
+            let point = read_mpi(&mut rest)?;
+            let kdf = rest.get(..4).ok_or_else(|| invalid("truncated key"))?;
+            let (hash, cipher) = (kdf[2], kdf[3]);
+
+            let known_kdf = matches!(hash, HASH_SHA256 | HASH_SHA384 | HASH_SHA512)
+                && matches!(cipher, CIPHER_AES128 | CIPHER_AES192 | CIPHER_AES256);
+            match (oid.as_slice(), point.split_first()) {
+                _ if !known_kdf => KeyMaterial::Unsupported,
+                (OID_NIST_P256, _) if p256::PublicKey::from_sec1_bytes(&point).is_ok() => {
+                    KeyMaterial::EcdhP256 { point, hash, cipher }
+                }
+                (OID_CURVE25519, Some((&CURVE25519_PREFIX, native))) if native.len() == 32 => {
+                    KeyMaterial::EcdhX25519 { point: native.try_into().unwrap(), hash, cipher }
+                }
+                _ => KeyMaterial::Unsupported,
+            }
+        }
+        _ => KeyMaterial::Unsupported,
+    };
+
+    // V4 fingerprint: SHA-1 over 0x99, two-octet length and the packet body
+    let mut hasher = Sha1::new();
+    hasher.update([0x99]);
+    hasher.update((body.len() as u16).to_be_bytes());
+    hasher.update(body);
+
+    Ok(KeyPacket {
+        created,
+        algorithm,
+        material,
+        fingerprint: hasher.finalize().into(),
+        flags: None,
+        expires_after: None,
+        revoked: false,
+    })
+}
+
+struct SignatureInfo {
+    kind: u8,
+    flags: Option<u8>,
+    expires_after: Option<u32>,
+}
+
+/// Read the type and hashed subpackets of a version 4 signature
+fn read_signature(body: &[u8]) -> Option<SignatureInfo> {
+    if body.first() != Some(&4) {
+        return None;
+    }
+    let kind = *body.get(1)?;
+    let hashed_len = u16::from_be_bytes(body.get(4..6)?.try_into().ok()?) as usize;
+    let mut subpackets = body.get(6..6 + hashed_len)?;
+
+    let mut info = SignatureInfo {
+        kind,
+        flags: None,
+        expires_after: None,
+    };
+
+    while !subpackets.is_empty() {
+        let first = subpackets[0] as usize;
+        let (length, offset) = match first {
+            0..=191 => (first, 1),
+            192..=254 => (((first - 192) << 8) + *subpackets.get(1)? as usize + 192, 2),
+            _ => (u32::from_be_bytes(subpackets.get(1..5)?.try_into().ok()?) as usize, 5),
+        };
+        let subpacket = subpackets.get(offset..offset + length)?;
+        let (kind, data) = subpacket.split_first()?;
+
+        match kind & 0x7F {
+            // Key expiration time
+            9 => info.expires_after = Some(u32::from_be_bytes(data.get(..4)?.try_into().ok()?)),
+            // Key flags
+            27 => info.flags = data.first().copied(),
+            _ => {}
+        }
+
+        subpackets = &subpackets[offset + length..];
+    }
+
+    Some(info)
+}
+
+fn read_mpi(data: &mut &[u8]) -> Result<Vec<u8>> {
+    let header = data.get(..2).ok_or_else(|| invalid("truncated key"))?;
+    let bits = u16::from_be_bytes([header[0], header[1]]) as usize;
+    let length = bits.div_ceil(8);
+    let value = data.get(2..2 + length).ok_or_else(|| invalid("truncated key"))?;
+    *data = &data[2 + length..];
+    Ok(value.to_vec())
+}
+
+fn mpi(value: &[u8]) -> Vec<u8> {
+    let start = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    let bits = match value.first() {
+        Some(first) => (value.len() - 1) * 8 + (8 - first.leading_zeros() as usize),
+        None => 0,
+    };
+
+    let mut out = (bits as u16).to_be_bytes().to_vec();
+    out.extend_from_slice(value);
+    out
+}
+
+/// Encode a new-format packet
+fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![0xC0 | tag];
+    match body.len() {
+        0..=191 => out.push(body.len() as u8),
+        192..=8383 => {
+            let length = body.len() - 192;
+            out.push(((length >> 8) + 192) as u8);
+            out.push(length as u8);
+        }
+        _ => {
+            out.push(0xFF);
+            out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// Symmetrically encrypted integrity protected data packet body holding a
+/// literal data packet, encrypted with AES-256 in CFB mode
+fn encrypt_data(session_key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+    // Binary literal data, no file name, no date
+    let mut literal = vec![b'b', 0, 0, 0, 0, 0];
+    literal.extend_from_slice(data);
+
+    let prefix: [u8; 16] = OsRng.gen();
+    let mut plaintext = prefix.to_vec();
+    plaintext.extend_from_slice(&prefix[14..]);
+    plaintext.extend(packet(TAG_LITERAL_DATA, &literal));
+
+    // Modification detection code covers everything up to its own header
+    plaintext.extend_from_slice(&[0xC0 | TAG_MDC, 20]);
+    let mdc = Sha1::digest(&plaintext);
+    plaintext.extend_from_slice(&mdc);
+
+    let cipher = Aes256::new(GenericArray::from_slice(session_key));
+    let mut register = [0u8; 16];
+    for chunk in plaintext.chunks_mut(16) {
+        let mut block = GenericArray::clone_from_slice(&register);
+        cipher.encrypt_block(&mut block);
+        for (byte, key) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= key;
+        }
+        register[..chunk.len()].copy_from_slice(chunk);
+    }
+
+    let mut body = vec![1];
+    body.extend(plaintext);
+    body
+}
+
+/// Wrap the session key payload with the key derived from an ECDH shared
+/// secret, as in RFC 6637 section 8
+fn wrap_session_key(
+    key: &KeyPacket,
+    oid: &[u8],
+    hash: u8,
+    cipher: u8,
+    shared: &[u8],
+    mut payload: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let mut param = vec![oid.len() as u8];
+    param.extend_from_slice(oid);
+    param.extend_from_slice(&[ALGO_ECDH, 3, 1, hash, cipher]);
+    param.extend_from_slice(b"Anonymous Sender    ");
+    param.extend_from_slice(&key.fingerprint);
+
+    let kek = ecdh_kdf(hash, cipher, shared, &param)?;
+
+    // PKCS#5 padding to a multiple of 8 octets
+    let padding = 8 - payload.len() % 8;
+    payload.extend(std::iter::repeat_n(padding as u8, padding));
+    key_wrap(&kek, &payload)
+}
+
+fn ecdh_kdf(hash: u8, cipher: u8, shared: &[u8], param: &[u8]) -> Result<Vec<u8>> {
+    let input = [&[0, 0, 0, 1][..], shared, param].concat();
+    let digest = match hash {
+        HASH_SHA256 => Sha256::digest(&input).to_vec(),
+        HASH_SHA384 => Sha384::digest(&input).to_vec(),
+        HASH_SHA512 => Sha512::digest(&input).to_vec(),
+        _ => return Err(encryption_failed("unsupported KDF hash")),
+    };
+    let length = match cipher {
+        CIPHER_AES128 => 16,
+        CIPHER_AES192 => 24,
+        CIPHER_AES256 => 32,
+        _ => return Err(encryption_failed("unsupported key wrap cipher")),
+    };
+    if digest.len() < length {
+        return Err(encryption_failed("KDF output too short"));
+    }
+    Ok(digest[..length].to_vec())
+}
+
+/// AES key wrap (RFC 3394)
+fn key_wrap(kek: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    match kek.len() {
+        16 => Ok(wrap_with(&Aes128::new(GenericArray::from_slice(kek)), data)),
+        24 => Ok(wrap_with(&Aes192::new(GenericArray::from_slice(kek)), data)),
+        32 => Ok(wrap_with(&Aes256::new(GenericArray::from_slice(kek)), data)),
+        _ => Err(encryption_failed("invalid key wrap key")),
+    }
+}
+
+fn wrap_with<C: BlockEncrypt>(cipher: &C, data: &[u8]) -> Vec<u8> {
+    let mut a = [0xA6u8; 8];
+    let mut r: Vec<[u8; 8]> = data.chunks(8).map(|c| c.try_into().unwrap()).collect();
+    let n = r.len();
+
+    for j in 0..6 {
+        for (i, block) in r.iter_mut().enumerate() {
+            let mut b = GenericArray::default();
+            b[..8].copy_from_slice(&a);
+            b[8..].copy_from_slice(block);
+            cipher.encrypt_block(&mut b);
+
+            let t = (n * j + i + 1) as u64;
+            for ((out, byte), t) in a.iter_mut().zip(&b[..8]).zip(t.to_be_bytes()) {
+                *out = byte ^ t;
+            }
+            block.copy_from_slice(&b[8..]);
+        }
+    }
+
+    let mut out = a.to_vec();
+    for block in r {
+        out.extend_from_slice(&block);
+    }
+    out
+}
+
+fn dearmor(text: &str, kind: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", kind);
+    let end = format!("-----END {}-----", kind);
+
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != begin)
+        .skip(1);
+
+    // Armor headers end at the first blank line
+    let mut body: Vec<&str> = Vec::new();
+    let mut in_headers = true;
+    for line in lines.by_ref() {
+        if in_headers {
+            if line.is_empty() {
+                in_headers = false;
+            } else if !line.contains(": ") {
+                // No headers present
+                in_headers = false;
+                body.push(line);
+            }
+            continue;
+        }
+        if line == end {
+            break;
+        }
+        body.push(line);
+    }
+
+    if body.is_empty() {
+        return Err(invalid(&format!("expected an armored {}", kind.to_lowercase())));
+    }
+
+    let checksum = match body.last() {
+        Some(line) if line.starts_with('=') && line.len() == 5 => body.pop(),
+        _ => None,
+    };
+
+    let data = STANDARD
+        .decode(body.concat())
+        .map_err(|_| invalid("armor is not valid base64"))?;
+
+    if let Some(checksum) = checksum {
+        let expected = STANDARD
+            .decode(&checksum[1..])
+            .map_err(|_| invalid("armor checksum is not valid base64"))?;
+        if expected != crc24(&data).to_be_bytes()[1..] {
+            return Err(invalid("armor checksum mismatch"));
+        }
+    }
+
+    Ok(data)
+}
+
+fn armor(kind: &str, data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+
+    let mut out = format!("-----BEGIN {}-----\r\n\r\n", kind);
+    for line in encoded.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    out.push('=');
+    out.push_str(&STANDARD.encode(&crc24(data).to_be_bytes()[1..]));
+    out.push_str(&format!("\r\n-----END {}-----\r\n", kind));
+    out
+}
+
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xB704CE;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864CFB;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::RsaPrivateKey;
+
+    // Keys and messages generated with GnuPG 2.2. Each key has a signing
+    // primary key and an encryption subkey; the secret exports hold only
+    // the unprotected subkey. The messages encrypt "Hello from GnuPG\n".
+    const RSA_PUBLIC: &str = include_str!("../tests/fixtures/pgp/rsa.pub.asc");
+    const RSA_SECRET: &str = include_str!("../tests/fixtures/pgp/rsa.sec.asc");
+    const RSA_MESSAGE: &str = include_str!("../tests/fixtures/pgp/rsa.msg.asc");
+    const P256_PUBLIC: &str = include_str!("../tests/fixtures/pgp/p256.pub.asc");
+    const P256_SECRET: &str = include_str!("../tests/fixtures/pgp/p256.sec.asc");
+    const P256_MESSAGE: &str = include_str!("../tests/fixtures/pgp/p256.msg.asc");
+    const CV25519_PUBLIC: &str = include_str!("../tests/fixtures/pgp/cv25519.pub.asc");
+    const CV25519_SECRET: &str = include_str!("../tests/fixtures/pgp/cv25519.sec.asc");
+    const CV25519_MESSAGE: &str = include_str!("../tests/fixtures/pgp/cv25519.msg.asc");
+
+    /// Session keys of the messages, from `gpg --show-session-key`
+    const RSA_SESSION_KEY: &str = "91F76E3910549FBD8EF3995D0E2AC7B35340E4D8FCD41D3D4B02C9BD85F3A6D3";
+    const P256_SESSION_KEY: &str = "5CB54C94E84F0BF5FEFE53C4EE95C5DB03E7F9945C0032DE78DA209A05D2ED5D";
+    const CV25519_SESSION_KEY: &str = "B5293620B9D7F32418C2F1CFDD430313F918428A0136D86AED6824015A63DBDA";
+
+    const ARMORED: &str = "-----BEGIN PGP MESSAGE-----\n\nhQEMA1234\n=abcd\n-----END PGP MESSAGE-----";
+
+    fn unhex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// Secret MPIs of the subkey in a GnuPG secret key export
+    fn secret_mpis(armored: &str) -> Vec<Vec<u8>> {
+        let data = dearmor(armored, "PGP PRIVATE KEY BLOCK").unwrap();
+        let (_, body) = read_packets(&data).unwrap().into_iter().find(|(tag, _)| *tag == 7).unwrap();
+
+        // Skip the public key fields
+        let mut rest = &body[6..];
+        match body[5] {
+            ALGO_RSA => {
+                read_mpi(&mut rest).unwrap();
+                read_mpi(&mut rest).unwrap();
+            }
+            ALGO_ECDH => {
+                rest = &rest[1 + rest[0] as usize..];
+                read_mpi(&mut rest).unwrap();
+                rest = &rest[4..];
+            }
+            algorithm => panic!("unexpected algorithm {}", algorithm),
+        }
+
+        assert_eq!(rest[0], 0, "secret key material must be unprotected");
+        rest = &rest[1..];
+        let mut mpis = Vec::new();
+        while rest.len() > 2 {
+            mpis.push(read_mpi(&mut rest).unwrap());
+        }
+        mpis
+    }
+
+    fn session_payload(session_key: &[u8]) -> Vec<u8> {
+        let checksum = session_key.iter().map(|b| *b as u16).fold(0u16, u16::wrapping_add);
+        [&[CIPHER_AES256][..], session_key, &checksum.to_be_bytes()].concat()
+    }
+
+    /// Assert that a public-key encrypted session key packet carries
+    /// `session_key`, opening it with the secret subkey
+    fn assert_session_key(key: &PublicKey, secret: &str, body: &[u8], session_key: &[u8]) {
+        let subkey = &key.encryption_key;
+        assert_eq!(body[0], 3);
+        assert_eq!(&body[1..9], subkey.key_id());
+        assert_eq!(body[9], subkey.algorithm);
+
+        let mut rest = &body[10..];
+        let secret = secret_mpis(secret);
+        let expected = session_payload(session_key);
+
+        let (oid, hash, cipher, shared) = match &subkey.material {
+            KeyMaterial::Rsa { n, e } => {
+                let private = RsaPrivateKey::from_components(
+                    BigUint::from_bytes_be(n),
+                    BigUint::from_bytes_be(e),
+                    BigUint::from_bytes_be(&secret[0]),
+                    vec![BigUint::from_bytes_be(&secret[1]), BigUint::from_bytes_be(&secret[2])],
+                )
+                .unwrap();
+                let encrypted = read_mpi(&mut rest).unwrap();
+                assert_eq!(private.decrypt(Pkcs1v15Encrypt, &encrypted).unwrap(), expected);
+                return;
+            }
+            KeyMaterial::EcdhP256 { hash, cipher, .. } => {
+                let ephemeral = p256::PublicKey::from_sec1_bytes(&read_mpi(&mut rest).unwrap()).unwrap();
+                let secret = p256::SecretKey::from_slice(&secret[0]).unwrap();
+                let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), ephemeral.as_affine());
+                (OID_NIST_P256, *hash, *cipher, shared.raw_secret_bytes().to_vec())
+            }
+            KeyMaterial::EcdhX25519 { hash, cipher, .. } => {
+                let ephemeral = read_mpi(&mut rest).unwrap();
+                assert_eq!(ephemeral[0], CURVE25519_PREFIX);
+                let ephemeral: [u8; 32] = ephemeral[1..].try_into().unwrap();
+
+                // GnuPG stores the scalar big-endian, reversed from its native form
+                let mut scalar = [0u8; 32];
+                scalar[32 - secret[0].len()..].copy_from_slice(&secret[0]);
+                scalar.reverse();
+                let shared = StaticSecret::from(scalar).diffie_hellman(&x25519_dalek::PublicKey::from(ephemeral));
+                (OID_CURVE25519, *hash, *cipher, shared.as_bytes().to_vec())
+            }
+            KeyMaterial::Unsupported => panic!("unsupported key"),
+        };
+
+        // Key wrap is deterministic, so wrapping the expected payload with
+        // the derived key must reproduce the packet
+        let wrapped = &rest[1..1 + rest[0] as usize];
+        assert_eq!(wrap_session_key(subkey, oid, hash, cipher, &shared, expected).unwrap(), wrapped);
+    }
+
+    #[test]
+    fn parses_gnupg_keys() {
+        let cases = [
+            (RSA_PUBLIC, "7F43EBD4F33C1E1942990DFB9F459173308E0260", "DE81B4680ADD87667E329B226052FE5EA1B41F7B", "RSA Test <rsa@example.com>"),
+            (P256_PUBLIC, "D0DE9C3EADD364C393CCE038D7F3E66469DE4311", "FC3BCE700E40A5E25FBD996268027F2A5C134573", "P256 Test <p256@example.com>"),
+            (CV25519_PUBLIC, "29A35034A170C38FAA50B92BD101276D243DEF8A", "27F0F7E74D94B8E5F21385F91373EA07DE299F90", "Curve Test <cv25519@example.com>"),
+        ];
+
+        for (armored, fingerprint, subkey, user_id) in cases {
+            let key = PublicKey::parse(armored).unwrap();
+            assert_eq!(key.fingerprint, fingerprint);
+            assert_eq!(hex(&key.encryption_key.fingerprint), subkey);
+            assert_eq!(key.user_ids, vec![user_id.to_string()]);
+            assert_eq!(key.expires_at, None);
+        }
+
+        let material = |armored| PublicKey::parse(armored).unwrap().encryption_key.material;
+        assert!(matches!(material(RSA_PUBLIC), KeyMaterial::Rsa { .. }));
+        assert!(matches!(material(P256_PUBLIC), KeyMaterial::EcdhP256 { hash: HASH_SHA256, cipher: CIPHER_AES128, .. }));
+        assert!(matches!(material(CV25519_PUBLIC), KeyMaterial::EcdhX25519 { hash: HASH_SHA256, cipher: CIPHER_AES128, .. }));
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        assert!(PublicKey::parse("not a key").is_err());
+        assert!(PublicKey::parse(&RSA_PUBLIC.replacen("mQEN", "mQEO", 1)).is_err());
+
+        let data = dearmor(RSA_PUBLIC, PUBLIC_KEY_BLOCK).unwrap();
+        assert!(read_packets(&data[..data.len() - 1]).is_err());
+        assert!(read_packets(&[0x3F, 0x00]).is_err());
+    }
+
+    #[test]
+    fn reads_packets_of_gnupg_messages() {
+        for message in [RSA_MESSAGE, P256_MESSAGE, CV25519_MESSAGE] {
+            let data = dearmor(message, MESSAGE_BLOCK).unwrap();
+            let tags: Vec<u8> = read_packets(&data).unwrap().iter().map(|(tag, _)| *tag).collect();
+            assert_eq!(tags, vec![TAG_PKESK, TAG_SEIPD]);
+        }
+
+        // Old and new format headers of every length encoding
+        for body_len in [0, 191, 192, 8383, 8384] {
+            let body = vec![0xAB; body_len];
+            let mut data = packet(TAG_LITERAL_DATA, &body);
+            data.extend([0x88, 2, 1, 2]);
+            data.extend([0x89, 0, 3, 1, 2, 3]);
+            let packets = read_packets(&data).unwrap();
+            assert_eq!(packets, vec![(TAG_LITERAL_DATA, &body[..]), (TAG_SIGNATURE, &[1, 2][..]), (TAG_SIGNATURE, &[1, 2, 3][..])]);
+        }
+    }
+
+    #[test]
+    fn key_wrap_matches_rfc3394() {
+        let kek: Vec<u8> = (0..32).collect();
+
+        // Section 4.1: 128-bit data with a 128-bit key
+        let wrapped = key_wrap(&kek[..16], &unhex("00112233445566778899AABBCCDDEEFF")).unwrap();
+        assert_eq!(wrapped, unhex("1FA68B0A8112B447AEF34BD8FB5A7B829D3E862371D2CFE5"));
+
+        // Section 4.6: 256-bit data with a 256-bit key
+        let data = unhex("00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F");
+        assert_eq!(
+            key_wrap(&kek, &data).unwrap(),
+            unhex("28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21")
+        );
+
+        assert!(key_wrap(&kek[..20], &data).is_err());
+    }
+
+    #[test]
+    fn opens_gnupg_session_keys() {
+        let cases = [
+            (RSA_PUBLIC, RSA_SECRET, RSA_MESSAGE, RSA_SESSION_KEY),
+            (P256_PUBLIC, P256_SECRET, P256_MESSAGE, P256_SESSION_KEY),
+            (CV25519_PUBLIC, CV25519_SECRET, CV25519_MESSAGE, CV25519_SESSION_KEY),
+        ];
+
+        for (public, secret, message, session_key) in cases {
+            let key = PublicKey::parse(public).unwrap();
+            let data = dearmor(message, MESSAGE_BLOCK).unwrap();
+            let (_, body) = read_packets(&data).unwrap()[0];
+            assert_session_key(&key, secret, body, &unhex(session_key));
+        }
+    }
+
+    #[test]
+    fn session_key_packets_open_with_the_secret_key() {
+        let session_key: [u8; 32] = OsRng.gen();
+
+        for (public, secret) in [(RSA_PUBLIC, RSA_SECRET), (P256_PUBLIC, P256_SECRET), (CV25519_PUBLIC, CV25519_SECRET)] {
+            let key = PublicKey::parse(public).unwrap();
+            let body = key.session_key_packet(&session_key).unwrap();
+            assert_session_key(&key, secret, &body, &session_key);
+        }
+    }
+
+    #[test]
+    fn encrypts_to_an_armored_message() {
+        let key = PublicKey::parse(CV25519_PUBLIC).unwrap();
+        let armored = key.encrypt(b"hello").unwrap();
+
+        let data = dearmor(&armored, MESSAGE_BLOCK).unwrap();
+        let packets = read_packets(&data).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].0, TAG_PKESK);
+        assert_eq!(packets[1].0, TAG_SEIPD);
+        assert!(is_encrypted(Some(&armored), None));
+    }
+
+    #[test]
+    fn passes_through_only_a_single_armored_message() {
+        assert!(is_encrypted(Some(ARMORED), None));
+        assert!(is_encrypted(Some(&format!("\n  {}\n\n", ARMORED)), None));
+
+        assert!(!is_encrypted(None, None));
+        assert!(!is_encrypted(Some(ARMORED), Some("<p>hello</p>")));
+        assert!(!is_encrypted(Some(&format!("Secret reply\n\n> {}", ARMORED)), None));
+        assert!(!is_encrypted(Some(&format!("{}\nP.S. the password is hunter2", ARMORED)), None));
+        assert!(!is_encrypted(Some(&format!("{}\n{}", ARMORED, ARMORED)), None));
+        assert!(!is_encrypted(Some("How to read -----BEGIN PGP MESSAGE----- blocks"), None));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::error::AppError;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, Request},
+    response::IntoResponse,
+    Extension,
+};
+use governor::middleware::NoOpMiddleware;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_governor::{
+    governor::GovernorConfigBuilder, key_extractor::KeyExtractor, GovernorError, GovernorLayer,
+};
+
+/// Resolve the client address, honouring `X-Forwarded-For` only when the
+/// server is configured to sit behind a trusted reverse proxy.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.split(',').find_map(|s| s.trim().parse::<IpAddr>().ok()));
+
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    peer
+}
+
+/// Rate limiting key: the client IP as resolved by [`client_ip`]
+#[derive(Clone)]
+pub struct ClientIpKeyExtractor {
+    trust_proxy: bool,
+}
+
+impl KeyExtractor for ClientIpKeyExtractor {
+    type Key = IpAddr;
+
+    fn extract<T>(&self, req: &Request<T>) -> std::result::Result<Self::Key, GovernorError> {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        client_ip(req.headers(), peer, self.trust_proxy).ok_or(GovernorError::UnableToExtractKey)
+    }
+}
+
+/// Build a per-IP rate limiting layer that allows `burst` requests and
+/// replenishes one every `period`.
+pub fn layer(
+    period: Duration,
+    burst: u32,
+    trust_proxy: bool,
+) -> GovernorLayer<ClientIpKeyExtractor, NoOpMiddleware> {
+    let config = Arc::new(
+        GovernorConfigBuilder::default()
+            .period(period)
+            .burst_size(burst.max(1))
+            .key_extractor(ClientIpKeyExtractor { trust_proxy })
+            .error_handler(|_| AppError::RateLimit.into_response())
+            .finish()
+            .expect("rate limit period must be non-zero"),
+    );
+
+    // Forget idle clients periodically so the limiter does not grow unbounded
+    let limiter = config.limiter().clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            limiter.retain_recent();
+        }
+    });
+
+    GovernorLayer { config }
+}
+
+/// Extractor for the client IP address of the current request
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(config) = Extension::<Config>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AppError::Internal("Config not found".to_string()))?;
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(ClientIp(client_ip(&parts.headers, peer, config.trust_proxy_headers)))
+    }
+}
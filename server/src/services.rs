@@ -1,8 +1,40 @@
+pub mod account_service;
 pub mod alias_service;
+pub mod device_service;
+pub mod digest_service;
+pub mod directory_service;
+pub mod domain_service;
 pub mod email_service;
+pub mod image_proxy_service;
+pub mod import_service;
+pub mod log_service;
+pub mod push_service;
+pub mod quarantine_service;
+pub mod reserved_name_service;
+pub mod security_service;
+pub mod spam_service;
+pub mod stats_service;
+pub mod system_domain_service;
 pub mod target_service;
+pub mod webhook_service;
 
+pub use account_service::AccountService;
 pub use alias_service::AliasService;
+pub use device_service::DeviceService;
+pub use digest_service::DigestService;
+pub use directory_service::DirectoryService;
+pub use domain_service::DomainService;
 pub use email_service::EmailService;
+pub use image_proxy_service::ImageProxyService;
+pub use import_service::ImportService;
+pub use log_service::LogService;
+pub use push_service::PushService;
+pub use quarantine_service::QuarantineService;
+pub use reserved_name_service::ReservedNameService;
+pub use security_service::SecurityService;
+pub use spam_service::SpamService;
+pub use stats_service::StatsService;
+pub use system_domain_service::SystemDomainService;
 pub use target_service::TargetService;
+pub use webhook_service::WebhookService;
 
@@ -0,0 +1,627 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{
+    Alias, DeviceCredential, Directory, Domain, EmailChangeRequest, EmailLog, HeldMessage, PushSubscription,
+    SecurityEvent, TargetEmail, WebhookDelivery, WebhookEndpoint,
+};
+use crate::services::{AliasService, LogService, QuarantineService, SpamService};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct AccountService;
+
+/// Outcome of following one of the two email change confirmation links
+pub enum EmailChangeProgress {
+    /// The other address still has to confirm
+    Pending(EmailChangeRequest),
+    /// Both addresses confirmed and the account email was updated
+    Completed(EmailChangeRequest),
+}
+
+impl AccountService {
+    pub async fn email_in_use(pool: &PgPool, email: &str) -> Result<bool> {
+        let existing = sqlx::query("SELECT id FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(existing.is_some())
+    }
+
+    /// Create an account whose email address still has to be confirmed.
+    /// Returns the new user ID and the verification token.
+    pub async fn create_unverified(
+        pool: &PgPool,
+        email: &str,
+        password_hash: &str,
+    ) -> Result<(Uuid, String)> {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + chrono::Duration::hours(24);
+
+        let user_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (email, password_hash, email_verified, email_verification_token, email_verification_expires_at)
+            VALUES ($1, $2, false, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(email)
+        .bind(password_hash)
+        .bind(&token)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((user_id, token))
+    }
+
+    /// Issue a fresh verification token for an unverified account, if any
+    pub async fn renew_verification(pool: &PgPool, email: &str) -> Result<Option<String>> {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + chrono::Duration::hours(24);
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE users
+            SET email_verification_token = $1, email_verification_expires_at = $2
+            WHERE email = $3 AND email_verified = false
+            "#,
+        )
+        .bind(&token)
+        .bind(expires_at)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(token))
+    }
+
+    /// Mark an account email as verified. Returns the verified address.
+    pub async fn verify_email(pool: &PgPool, token: &str) -> Result<String> {
+        let email: String = sqlx::query_scalar(
+            r#"
+            UPDATE users
+            SET email_verified = true, email_verification_token = NULL, email_verification_expires_at = NULL
+            WHERE email_verification_token = $1 AND email_verification_expires_at > NOW()
+            RETURNING email
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired verification token".to_string()))?;
+
+        Ok(email)
+    }
+
+    /// Start an email change. Any earlier pending change for the user is replaced.
+    pub async fn request_email_change(
+        pool: &PgPool,
+        user_id: Uuid,
+        new_email: &str,
+    ) -> Result<EmailChangeRequest> {
+        let expires_at = Utc::now() + chrono::Duration::hours(24);
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM email_change_requests WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let request = sqlx::query_as::<_, EmailChangeRequest>(
+            r#"
+            INSERT INTO email_change_requests (user_id, new_email, old_token, new_token, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(new_email)
+        .bind(Uuid::new_v4().to_string())
+        .bind(Uuid::new_v4().to_string())
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(request)
+    }
+
+    /// Record a confirmation from either address and apply the change once both confirmed
+    pub async fn confirm_email_change(pool: &PgPool, token: &str) -> Result<EmailChangeProgress> {
+        let mut tx = pool.begin().await?;
+
+        let request = sqlx::query_as::<_, EmailChangeRequest>(
+            r#"
+            UPDATE email_change_requests
+            SET old_confirmed_at = CASE WHEN old_token = $1 THEN COALESCE(old_confirmed_at, NOW()) ELSE old_confirmed_at END,
+                new_confirmed_at = CASE WHEN new_token = $1 THEN COALESCE(new_confirmed_at, NOW()) ELSE new_confirmed_at END
+            WHERE (old_token = $1 OR new_token = $1) AND expires_at > NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired confirmation token".to_string()))?;
+
+        if request.old_confirmed_at.is_none() || request.new_confirmed_at.is_none() {
+            tx.commit().await?;
+            return Ok(EmailChangeProgress::Pending(request));
+        }
+
+        let taken = sqlx::query("SELECT id FROM users WHERE email = $1 AND id != $2")
+            .bind(&request.new_email)
+            .bind(request.user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        if taken.is_some() {
+            return Err(AppError::Validation("Email address is already in use".to_string()));
+        }
+
+        sqlx::query("UPDATE users SET email = $1, email_verified = true WHERE id = $2")
+            .bind(&request.new_email)
+            .bind(request.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM email_change_requests WHERE id = $1")
+            .bind(request.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(EmailChangeProgress::Completed(request))
+    }
+
+    /// Soft-delete an account. It is purged after the grace period unless
+    /// the owner signs in again before then.
+    pub async fn schedule_deletion(
+        pool: &PgPool,
+        user_id: Uuid,
+        grace_days: i64,
+    ) -> Result<DateTime<Utc>> {
+        let purge_after = Utc::now() + chrono::Duration::days(grace_days);
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("UPDATE users SET deleted_at = NOW(), purge_after = $1 WHERE id = $2")
+            .bind(purge_after)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Stop forwarding immediately and revoke paired devices
+        sqlx::query(
+            "UPDATE aliases SET status = 'paused', paused_by_deletion = true WHERE user_id = $1 AND status = 'active'",
+        )
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE device_credentials SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(purge_after)
+    }
+
+    /// Whether the user exists and is not scheduled for deletion. Tokens of
+    /// other users are refused until they sign in again.
+    pub async fn is_active(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+        let active: Option<bool> = sqlx::query_scalar("SELECT deleted_at IS NULL FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(active.unwrap_or(false))
+    }
+
+    /// Restore an account in its grace period, reactivating the aliases the
+    /// deletion paused. Revoked devices have to pair again.
+    pub async fn cancel_deletion(pool: &PgPool, user_id: Uuid) -> Result<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("UPDATE users SET deleted_at = NULL, purge_after = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Aliases deleted during the grace period stay deleted
+        sqlx::query(
+            r#"
+            UPDATE aliases
+            SET status = CASE WHEN status = 'paused' THEN 'active' ELSE status END, paused_by_deletion = false
+            WHERE user_id = $1 AND paused_by_deletion
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Permanently remove accounts whose grace period has ended. Deleting the
+    /// user cascades to aliases, target emails and email logs; alias
+    /// addresses are kept in `reserved_addresses`. Returns the number purged.
+    pub async fn purge_due_accounts(pool: &PgPool) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+
+        let user_ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM users
+            WHERE purge_after IS NOT NULL AND purge_after <= NOW()
+            ORDER BY purge_after
+            LIMIT 100
+            FOR UPDATE SKIP LOCKED
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if user_ids.is_empty() {
+            return Ok(0);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO reserved_addresses (address, reason)
+            SELECT address, 'account_deleted' FROM aliases WHERE user_id = ANY($1)
+            ON CONFLICT (address) DO NOTHING
+            "#,
+        )
+        .bind(&user_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(&user_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Collect everything stored about a user, grouped by section.
+    /// Quarantined messages are decrypted, so `config` must hold the key.
+    pub async fn export(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+    ) -> Result<Vec<(&'static str, serde_json::Value)>> {
+        let (email, email_verified, created_at): (String, bool, DateTime<Utc>) =
+            sqlx::query_as("SELECT email, email_verified, created_at FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        let aliases = sqlx::query_as::<_, Alias>(
+            "SELECT * FROM aliases WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let targets = sqlx::query_as::<_, TargetEmail>(
+            "SELECT * FROM target_emails WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let logs = sqlx::query_as::<_, EmailLog>(
+            r#"
+            SELECT el.*
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE a.user_id = $1
+            ORDER BY el.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let devices = sqlx::query_as::<_, DeviceCredential>(
+            "SELECT * FROM device_credentials WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let security_events = sqlx::query_as::<_, SecurityEvent>(
+            "SELECT * FROM security_events WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let domains = sqlx::query_as::<_, Domain>("SELECT * FROM domains WHERE user_id = $1 ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+        let directories = sqlx::query_as::<_, Directory>(
+            "SELECT * FROM directories WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let blocked_tags: Vec<(String, String, bool, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT a.address, t.tag, t.auto_blocked, t.created_at
+            FROM alias_blocked_tags t
+            JOIN aliases a ON t.alias_id = a.id
+            WHERE a.user_id = $1
+            ORDER BY t.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let imports: Vec<(String, String, Option<Uuid>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT source, original_address, alias_id, created_at FROM alias_imports WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let held = sqlx::query_as::<_, HeldMessage>(
+            r#"
+            SELECT el.id, el.alias_id, a.address AS alias_address, a.tracker_protection, a.user_id,
+                   el.from_email, el.subject, el.body_plain, el.body_html, el.release_token, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE a.user_id = $1 AND el.status = 'held' AND el.release_token IS NOT NULL
+            ORDER BY el.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let quarantined = QuarantineService::list(pool, user_id, i64::MAX).await?;
+
+        let daily_stats: Vec<(String, NaiveDate, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT a.address, s.day, s.status::text, s.messages
+            FROM alias_daily_stats s
+            JOIN aliases a ON s.alias_id = a.id
+            WHERE s.user_id = $1
+            ORDER BY s.day, a.address
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let daily_senders: Vec<(String, NaiveDate, String, Option<String>, i64)> = sqlx::query_as(
+            r#"
+            SELECT a.address, s.day, s.sender_hash, s.from_email, s.messages
+            FROM alias_daily_senders s
+            JOIN aliases a ON s.alias_id = a.id
+            WHERE s.user_id = $1
+            ORDER BY s.day, a.address
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let webhooks = sqlx::query_as::<_, WebhookEndpoint>(
+            "SELECT * FROM webhook_endpoints WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let webhook_deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT d.*
+            FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON d.endpoint_id = e.id
+            WHERE e.user_id = $1
+            ORDER BY d.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let push_subscriptions = sqlx::query_as::<_, PushSubscription>(
+            "SELECT * FROM push_subscriptions WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let alias_strategy = AliasService::generation_strategy(pool, user_id).await?;
+        let spam = SpamService::settings(pool, user_id).await?;
+        let log_settings = LogService::settings(pool, user_id).await?;
+
+        let targets: Vec<serde_json::Value> = targets
+            .into_iter()
+            .map(|t| {
+                serde_json::json!({
+                    "email": t.email,
+                    "verified": t.verified,
+                    "pgp_fingerprint": t.pgp_fingerprint,
+                    "pgp_public_key": t.pgp_public_key,
+                    "created_at": t.created_at
+                })
+            })
+            .collect();
+
+        let blocked_tags: Vec<serde_json::Value> = blocked_tags
+            .into_iter()
+            .map(|(alias, tag, auto_blocked, created_at)| {
+                serde_json::json!({
+                    "alias": alias,
+                    "tag": tag,
+                    "auto_blocked": auto_blocked,
+                    "created_at": created_at
+                })
+            })
+            .collect();
+
+        let imports: Vec<serde_json::Value> = imports
+            .into_iter()
+            .map(|(source, original_address, alias_id, created_at)| {
+                serde_json::json!({
+                    "source": source,
+                    "original_address": original_address,
+                    "alias_id": alias_id,
+                    "created_at": created_at
+                })
+            })
+            .collect();
+
+        let held: Vec<serde_json::Value> = held
+            .into_iter()
+            .map(|m| {
+                serde_json::json!({
+                    "email_log_id": m.id,
+                    "alias": m.alias_address,
+                    "from_email": m.from_email,
+                    "subject": m.subject,
+                    "body_plain": m.body_plain,
+                    "body_html": m.body_html,
+                    "created_at": m.created_at
+                })
+            })
+            .collect();
+
+        let quarantined = quarantined
+            .iter()
+            .map(|m| {
+                let content = QuarantineService::open(config, m)?;
+                Ok(serde_json::json!({
+                    "id": m.id,
+                    "email_log_id": m.email_log_id,
+                    "alias": m.alias_address,
+                    "from_email": m.from_email,
+                    "subject": m.subject,
+                    "reason": m.reason,
+                    "headers": content.headers,
+                    "body_plain": content.body_plain,
+                    "body_html": content.body_html,
+                    "expires_at": m.expires_at,
+                    "created_at": m.created_at
+                }))
+            })
+            .collect::<Result<Vec<serde_json::Value>>>()?;
+
+        let daily_stats: Vec<serde_json::Value> = daily_stats
+            .into_iter()
+            .map(|(alias, day, status, messages)| {
+                serde_json::json!({
+                    "alias": alias,
+                    "day": day,
+                    "status": status,
+                    "messages": messages
+                })
+            })
+            .collect();
+
+        let daily_senders: Vec<serde_json::Value> = daily_senders
+            .into_iter()
+            .map(|(alias, day, sender_hash, from_email, messages)| {
+                serde_json::json!({
+                    "alias": alias,
+                    "day": day,
+                    "sender_hash": sender_hash,
+                    "from_email": from_email,
+                    "messages": messages
+                })
+            })
+            .collect();
+
+        // Subscription keys belong to the browser, so only the endpoint is listed
+        let push_subscriptions: Vec<serde_json::Value> = push_subscriptions
+            .into_iter()
+            .map(|s| {
+                serde_json::json!({
+                    "endpoint": s.endpoint,
+                    "platform": s.platform,
+                    "device_name": s.device_name,
+                    "events": s.events,
+                    "last_success_at": s.last_success_at,
+                    "created_at": s.created_at
+                })
+            })
+            .collect();
+
+        let security_events: Vec<serde_json::Value> = security_events
+            .into_iter()
+            .map(|e| {
+                serde_json::json!({
+                    "event_type": e.event_type,
+                    "ip_address": e.ip_address,
+                    "metadata": e.metadata,
+                    "created_at": e.created_at
+                })
+            })
+            .collect();
+
+        Ok(vec![
+            (
+                "profile",
+                serde_json::json!({
+                    "id": user_id,
+                    "email": email,
+                    "email_verified": email_verified,
+                    "created_at": created_at,
+                    "exported_at": Utc::now()
+                }),
+            ),
+            (
+                "settings",
+                serde_json::json!({
+                    "alias_strategy": alias_strategy,
+                    "spam": spam,
+                    "logs": log_settings
+                }),
+            ),
+            ("aliases", to_value(aliases)?),
+            ("blocked_tags", serde_json::Value::Array(blocked_tags)),
+            ("alias_imports", serde_json::Value::Array(imports)),
+            ("targets", serde_json::Value::Array(targets)),
+            ("domains", to_value(domains)?),
+            ("directories", to_value(directories)?),
+            ("email_logs", to_value(logs)?),
+            ("held_messages", serde_json::Value::Array(held)),
+            ("quarantined_messages", serde_json::Value::Array(quarantined)),
+            ("daily_stats", serde_json::Value::Array(daily_stats)),
+            ("daily_senders", serde_json::Value::Array(daily_senders)),
+            ("webhooks", to_value(webhooks)?),
+            ("webhook_deliveries", to_value(webhook_deliveries)?),
+            ("push_subscriptions", serde_json::Value::Array(push_subscriptions)),
+            ("devices", to_value(devices)?),
+            ("security_events", serde_json::Value::Array(security_events)),
+        ])
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<serde_json::Value> {
+    serde_json::to_value(value).map_err(|e| AppError::Internal(e.to_string()))
+}
@@ -1,57 +1,86 @@
 use crate::error::{AppError, Result};
-use crate::models::{Alias, AliasStatus, AliasType, EmailLog};
-use chrono::Utc;
+use crate::generator::AliasGenerator;
+use crate::models::{
+    Alias, AliasExport, AliasStatus, AliasStrategy, AliasType, BulkAction, BulkAliasFilter, DeliveryMode, EmailLog, LogEvent, LogFilter, SubaddressTag, TrackerProtection,
+};
+use crate::services::WebhookService;
+use crate::validation::normalize_local_part;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::PgPool;
+use tracing::error;
 use uuid::Uuid;
 
+/// Generated addresses tried before giving up
+const GENERATE_ATTEMPTS: usize = 10;
+
+/// Most aliases a single bulk request may touch
+const BULK_LIMIT: usize = 1000;
+
+pub const MAX_LABEL_LENGTH: usize = 100;
+
+/// Most log entries returned per request
+const MAX_LOG_LIMIT: i64 = 100;
+
+/// Outcome of a bulk action for one alias
+#[derive(Debug, Serialize)]
+pub struct BulkItemResult {
+    pub id: Uuid,
+    pub address: Option<String>,
+    /// `updated`, `unchanged`, `skipped` or `not_found`
+    pub result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<serde_json::Value>,
+}
+
 pub struct AliasService;
 
 impl AliasService {
+    /// Create an alias. `generator` picks the address of random and
+    /// temporary aliases and is ignored for custom ones.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         pool: &PgPool,
         user_id: Uuid,
         alias_type: AliasType,
         custom: Option<String>,
         ttl_minutes: Option<u64>,
-        hush_domain: &str,
+        generator: Option<&AliasGenerator>,
+        domain: &str,
+        domain_id: Option<Uuid>,
     ) -> Result<Alias> {
         let address = match alias_type {
-            AliasType::Random => {
-                let uuid_str = Uuid::new_v4().to_string();
-                let random_part = format!("hush-{}", &uuid_str[..8]);
-                format!("{}@{}", random_part, hush_domain)
-            }
             AliasType::Custom => {
                 let custom_part = custom.ok_or_else(|| {
                     AppError::Validation("Custom alias requires 'custom' field".to_string())
                 })?;
-                format!("{}@{}", custom_part, hush_domain)
+
+                // Addresses are matched case-insensitively
+                let address = format!("{}@{}", normalize_local_part(&custom_part, "custom")?, domain).to_lowercase();
+
+                Self::check_available(pool, user_id, &address).await?;
+
+                address
             }
-            AliasType::Temporary => {
-                let uuid_str = Uuid::new_v4().to_string();
-                let random_part = format!("temp-{}", &uuid_str[..8]);
-                format!("{}@{}", random_part, hush_domain)
+            AliasType::Random | AliasType::Temporary => {
+                let generator = generator.ok_or_else(|| {
+                    AppError::Internal("No generator for a random alias".to_string())
+                })?;
+                let prefix = if matches!(alias_type, AliasType::Temporary) { "temp" } else { "hush" };
+                Self::generate_address(pool, generator, prefix, domain, domain_id).await?
             }
         };
 
-        // Check if address already exists
-        let existing = sqlx::query_as::<_, Alias>(
-            "SELECT * FROM aliases WHERE address = $1 AND status != 'deleted'",
-        )
-        .bind(&address)
-        .fetch_optional(pool)
-        .await?;
-
-        if existing.is_some() {
-            return Err(AppError::Validation("Alias already exists".to_string()));
-        }
-
         let expires_at = ttl_minutes.map(|ttl| Utc::now() + chrono::Duration::minutes(ttl as i64));
 
         let alias = sqlx::query_as::<_, Alias>(
             r#"
-            INSERT INTO aliases (user_id, address, status, alias_type, expires_at)
-            VALUES ($1, $2, 'active', $3, $4)
+            INSERT INTO aliases (user_id, address, status, alias_type, expires_at, domain_id)
+            VALUES ($1, $2, 'active', $3, $4, $5)
             RETURNING *
             "#,
         )
@@ -59,12 +88,162 @@ impl AliasService {
         .bind(&address)
         .bind(&alias_type)
         .bind(expires_at)
+        .bind(domain_id)
         .fetch_one(pool)
-        .await?;
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                AppError::Conflict("Alias already exists".to_string())
+            }
+            e => e.into(),
+        })?;
+
+        // The alias exists either way; a lost event must not fail the request
+        if let Err(e) = WebhookService::emit(
+            pool,
+            user_id,
+            "alias.created",
+            serde_json::json!({
+                "alias_id": alias.id,
+                "address": alias.address,
+                "expires_at": alias.expires_at
+            }),
+        )
+        .await
+        {
+            error!("Failed to queue alias.created webhook: {}", e);
+        }
 
         Ok(alias)
     }
 
+    /// Refuse to create more than `limit` aliases for a user within an hour
+    pub async fn check_creation_rate(pool: &PgPool, user_id: Uuid, limit: i64) -> Result<()> {
+        if Self::created_last_hour(pool, user_id).await? >= limit {
+            return Err(AppError::RateLimit);
+        }
+
+        Ok(())
+    }
+
+    /// Aliases the user created in the last hour, deleted ones included
+    pub async fn created_last_hour(pool: &PgPool, user_id: Uuid) -> Result<i64> {
+        let recent: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM aliases WHERE user_id = $1 AND created_at > NOW() - INTERVAL '1 hour'",
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(recent)
+    }
+
+    /// Generate addresses until one is free. Dotted addresses on the system
+    /// domains must also stay out of other users' directories.
+    async fn generate_address(
+        pool: &PgPool,
+        generator: &AliasGenerator,
+        prefix: &str,
+        domain: &str,
+        domain_id: Option<Uuid>,
+    ) -> Result<String> {
+        for _ in 0..GENERATE_ATTEMPTS {
+            let local = generator.generate(prefix);
+            let address = format!("{}@{}", local, domain).to_lowercase();
+
+            if Self::address_taken(pool, &address).await? {
+                continue;
+            }
+
+            if let (Some((name, _)), None) = (local.split_once('.'), domain_id) {
+                let directory = sqlx::query("SELECT id FROM directories WHERE name = $1")
+                    .bind(name)
+                    .fetch_optional(pool)
+                    .await?;
+
+                if directory.is_some() {
+                    continue;
+                }
+            }
+
+            return Ok(address);
+        }
+
+        Err(AppError::Internal("Could not generate an unused alias address".to_string()))
+    }
+
+    /// Explain why a custom address cannot be created, pointing the owner of
+    /// a deleted alias to restoring it
+    pub async fn check_available(pool: &PgPool, user_id: Uuid, address: &str) -> Result<()> {
+        let existing = sqlx::query_as::<_, Alias>("SELECT * FROM aliases WHERE address = $1")
+            .bind(address)
+            .fetch_optional(pool)
+            .await?;
+
+        match existing {
+            Some(alias) if alias.user_id == user_id && matches!(alias.status, AliasStatus::Deleted) => {
+                Err(AppError::Conflict(format!(
+                    "You deleted this alias; restore it with POST /api/v1/aliases/{}/restore",
+                    alias.id
+                )))
+            }
+            Some(_) => Err(AppError::Conflict("Alias already exists".to_string())),
+            None if Self::address_taken(pool, address).await? => {
+                Err(AppError::Conflict("Alias already exists".to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// In use by an alias, deleted aliases included, or reserved after its
+    /// account was purged
+    async fn address_taken(pool: &PgPool, address: &str) -> Result<bool> {
+        let existing = sqlx::query("SELECT id FROM aliases WHERE address = $1")
+            .bind(address)
+            .fetch_optional(pool)
+            .await?;
+
+        if existing.is_some() {
+            return Ok(true);
+        }
+
+        // Addresses from purged accounts are never handed out again
+        let reserved = sqlx::query("SELECT address FROM reserved_addresses WHERE address = $1")
+            .bind(address)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(reserved.is_some())
+    }
+
+    /// The user's default strategy for random and temporary aliases
+    pub async fn generation_strategy(pool: &PgPool, user_id: Uuid) -> Result<AliasStrategy> {
+        let strategy = sqlx::query_scalar::<_, AliasStrategy>("SELECT alias_strategy FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(strategy)
+    }
+
+    pub async fn set_generation_strategy(
+        pool: &PgPool,
+        user_id: Uuid,
+        strategy: AliasStrategy,
+    ) -> Result<AliasStrategy> {
+        let strategy = sqlx::query_scalar::<_, AliasStrategy>(
+            "UPDATE users SET alias_strategy = $2 WHERE id = $1 RETURNING alias_strategy",
+        )
+        .bind(user_id)
+        .bind(strategy)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(strategy)
+    }
+
     pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<Alias>> {
         let aliases = sqlx::query_as::<_, Alias>(
             "SELECT * FROM aliases WHERE user_id = $1 AND status != 'deleted' ORDER BY created_at DESC",
@@ -89,7 +268,13 @@ impl AliasService {
         Ok(alias)
     }
 
-    pub async fn toggle(pool: &PgPool, alias_id: Uuid, user_id: Uuid, enabled: bool) -> Result<Alias> {
+    pub async fn toggle(
+        pool: &PgPool,
+        alias_id: Uuid,
+        user_id: Uuid,
+        enabled: bool,
+        quarantine_when_paused: Option<bool>,
+    ) -> Result<Alias> {
         let status = if enabled {
             AliasStatus::Active
         } else {
@@ -99,14 +284,15 @@ impl AliasService {
         let alias = sqlx::query_as::<_, Alias>(
             r#"
             UPDATE aliases
-            SET status = $1, updated_at = NOW()
-            WHERE id = $2 AND user_id = $3
+            SET status = $1, quarantine_when_paused = COALESCE($4, quarantine_when_paused), updated_at = NOW()
+            WHERE id = $2 AND user_id = $3 AND status != 'deleted'
             RETURNING *
             "#,
         )
         .bind(&status)
         .bind(alias_id)
         .bind(user_id)
+        .bind(quarantine_when_paused)
         .fetch_optional(pool)
         .await?
         .ok_or_else(|| AppError::NotFound("Alias not found".to_string()))?;
@@ -114,6 +300,418 @@ impl AliasService {
         Ok(alias)
     }
 
+    /// Switch between forwarding each message and collecting a digest. The
+    /// first digest period starts now rather than at alias creation.
+    pub async fn set_delivery_mode(
+        pool: &PgPool,
+        alias_id: Uuid,
+        user_id: Uuid,
+        mode: DeliveryMode,
+    ) -> Result<Alias> {
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            UPDATE aliases
+            SET last_digest_at = CASE WHEN delivery_mode = $1 THEN last_digest_at ELSE NOW() END,
+                delivery_mode = $1,
+                updated_at = NOW()
+            WHERE id = $2 AND user_id = $3 AND status != 'deleted'
+            RETURNING *
+            "#,
+        )
+        .bind(mode)
+        .bind(alias_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Alias not found".to_string()))?;
+
+        Ok(alias)
+    }
+
+    pub async fn set_tracker_protection(
+        pool: &PgPool,
+        alias_id: Uuid,
+        user_id: Uuid,
+        protection: TrackerProtection,
+    ) -> Result<Alias> {
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            UPDATE aliases
+            SET tracker_protection = $1, updated_at = NOW()
+            WHERE id = $2 AND user_id = $3 AND status != 'deleted'
+            RETURNING *
+            "#,
+        )
+        .bind(protection)
+        .bind(alias_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Alias not found".to_string()))?;
+
+        Ok(alias)
+    }
+
+    pub async fn set_block_spam_tags(
+        pool: &PgPool,
+        alias_id: Uuid,
+        user_id: Uuid,
+        block_spam_tags: bool,
+    ) -> Result<Alias> {
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            UPDATE aliases
+            SET block_spam_tags = $1, updated_at = NOW()
+            WHERE id = $2 AND user_id = $3 AND status != 'deleted'
+            RETURNING *
+            "#,
+        )
+        .bind(block_spam_tags)
+        .bind(alias_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Alias not found".to_string()))?;
+
+        Ok(alias)
+    }
+
+    /// Subaddress tags that received mail on the alias, with blocked tags
+    /// that have not
+    pub async fn list_tags(pool: &PgPool, alias_id: Uuid, user_id: Uuid) -> Result<Vec<SubaddressTag>> {
+        let _alias = Self::get_by_id(pool, alias_id, user_id).await?;
+
+        let tags = sqlx::query_as::<_, SubaddressTag>(
+            r#"
+            SELECT COALESCE(l.tag, b.tag) AS tag,
+                   COALESCE(l.received, 0) AS received,
+                   COALESCE(l.spam, 0) AS spam,
+                   l.last_seen,
+                   b.tag IS NOT NULL AS blocked,
+                   COALESCE(b.auto_blocked, false) AS auto_blocked
+            FROM (
+                SELECT metadata->>'tag' AS tag,
+                       COUNT(*) AS received,
+                       COUNT(*) FILTER (WHERE metadata->'spam'->>'action' IN ('tag', 'quarantine')) AS spam,
+                       MAX(created_at) AS last_seen
+                FROM email_logs
+                WHERE alias_id = $1 AND metadata->>'tag' IS NOT NULL
+                GROUP BY 1
+            ) l
+            FULL OUTER JOIN (
+                SELECT tag, auto_blocked FROM alias_blocked_tags WHERE alias_id = $1
+            ) b ON b.tag = l.tag
+            ORDER BY l.last_seen DESC NULLS LAST, 1
+            "#,
+        )
+        .bind(alias_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tags)
+    }
+
+    /// Reject further mail to `alias+tag@domain`
+    pub async fn block_tag(pool: &PgPool, alias_id: Uuid, user_id: Uuid, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag)?;
+        let _alias = Self::get_by_id(pool, alias_id, user_id).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO alias_blocked_tags (alias_id, tag)
+            VALUES ($1, $2)
+            ON CONFLICT (alias_id, tag) DO UPDATE SET auto_blocked = false
+            "#,
+        )
+        .bind(alias_id)
+        .bind(&tag)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Block a tag after it received spam. Returns whether it was newly blocked.
+    pub async fn auto_block_tag(pool: &PgPool, alias_id: Uuid, tag: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO alias_blocked_tags (alias_id, tag, auto_blocked)
+            VALUES ($1, $2, true)
+            ON CONFLICT (alias_id, tag) DO NOTHING
+            "#,
+        )
+        .bind(alias_id)
+        .bind(tag)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn unblock_tag(pool: &PgPool, alias_id: Uuid, user_id: Uuid, tag: &str) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM alias_blocked_tags
+            WHERE alias_id = (SELECT id FROM aliases WHERE id = $1 AND user_id = $2)
+            AND tag = $3
+            "#,
+        )
+        .bind(alias_id)
+        .bind(user_id)
+        .bind(tag.trim().to_lowercase())
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Tag is not blocked".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_tag_blocked(pool: &PgPool, alias_id: Uuid, tag: &str) -> Result<bool> {
+        let blocked = sqlx::query("SELECT id FROM alias_blocked_tags WHERE alias_id = $1 AND tag = $2")
+            .bind(alias_id)
+            .bind(tag)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(blocked.is_some())
+    }
+
+    /// Deleted aliases, which only their owner can restore
+    pub async fn list_deleted(pool: &PgPool, user_id: Uuid) -> Result<Vec<Alias>> {
+        let aliases = sqlx::query_as::<_, Alias>(
+            "SELECT * FROM aliases WHERE user_id = $1 AND status = 'deleted' ORDER BY updated_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(aliases)
+    }
+
+    /// All aliases of a user that are not deleted, with delivery counts and
+    /// the address each was imported from
+    pub async fn export(pool: &PgPool, user_id: Uuid) -> Result<Vec<AliasExport>> {
+        let aliases = sqlx::query_as::<_, AliasExport>(
+            r#"
+            SELECT a.address, a.status::text AS status, a.label, a.alias_type::text AS alias_type,
+                   a.delivery_mode::text AS delivery_mode, a.expires_at, a.created_at,
+                   COALESCE(l.forwarded, 0) AS forwarded, COALESCE(l.blocked, 0) AS blocked,
+                   l.last_received_at, i.original_address AS imported_from
+            FROM aliases a
+            LEFT JOIN (
+                SELECT alias_id,
+                       COUNT(*) FILTER (WHERE status = 'forwarded') AS forwarded,
+                       COUNT(*) FILTER (WHERE status IN ('rejected', 'quarantined')) AS blocked,
+                       MAX(created_at) AS last_received_at
+                FROM email_logs
+                GROUP BY alias_id
+            ) l ON l.alias_id = a.id
+            LEFT JOIN alias_imports i ON i.alias_id = a.id
+            WHERE a.user_id = $1 AND a.status != 'deleted'
+            ORDER BY a.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(aliases)
+    }
+
+    /// Reactivate a deleted alias. Its domain must still be available to the
+    /// user and a temporary alias must not have expired.
+    pub async fn restore(pool: &PgPool, alias_id: Uuid, user_id: Uuid) -> Result<Alias> {
+        let alias = Self::get_by_id(pool, alias_id, user_id).await?;
+
+        if !matches!(alias.status, AliasStatus::Deleted) {
+            return Err(AppError::Conflict("Alias is not deleted".to_string()));
+        }
+
+        if alias.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(AppError::Validation("Alias has expired".to_string()));
+        }
+
+        // Aliases on a removed custom domain lose their domain_id; the rest
+        // are on a system domain or, for directories, a subdomain of one
+        if alias.domain_id.is_none() {
+            let domain = alias.address.rsplit_once('@').map(|(_, d)| d).unwrap_or_default();
+            let system = sqlx::query("SELECT id FROM system_domains WHERE domain = $1 OR $1 LIKE '%.' || domain")
+                .bind(domain)
+                .fetch_optional(pool)
+                .await?;
+
+            if system.is_none() {
+                return Err(AppError::Validation(
+                    "The alias's domain is no longer registered".to_string(),
+                ));
+            }
+        }
+
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            UPDATE aliases
+            SET status = 'active', updated_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND status = 'deleted'
+            RETURNING *
+            "#,
+        )
+        .bind(alias_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Alias is not deleted".to_string()))?;
+
+        if let Err(e) = WebhookService::emit(
+            pool,
+            user_id,
+            "alias.restored",
+            serde_json::json!({
+                "alias_id": alias.id,
+                "address": alias.address
+            }),
+        )
+        .await
+        {
+            error!("Failed to queue alias.restored webhook: {}", e);
+        }
+
+        Ok(alias)
+    }
+
+    /// Apply one action to many aliases in a single transaction. Every
+    /// matched alias gets a result; with `dry_run` nothing is written.
+    pub async fn bulk(
+        pool: &PgPool,
+        user_id: Uuid,
+        ids: Option<&[Uuid]>,
+        filter: Option<&BulkAliasFilter>,
+        action: &BulkAction,
+        dry_run: bool,
+    ) -> Result<Vec<BulkItemResult>> {
+        let action = match action {
+            BulkAction::ChangeTarget => {
+                return Err(AppError::Validation(
+                    "Aliases forward to the account's target; change it with PUT /api/v1/targets".to_string(),
+                ))
+            }
+            BulkAction::SetLabel { label } => BulkAction::SetLabel {
+                label: normalize_label(label.as_deref())?,
+            },
+            BulkAction::SetExpiry { expires_at: Some(expires_at) } if *expires_at <= Utc::now() => {
+                return Err(AppError::Validation("Expiry must be in the future".to_string()))
+            }
+            action => action.clone(),
+        };
+
+        let (ids, filter) = match (ids, filter) {
+            (Some(ids), None) if !ids.is_empty() => (Some(ids), None),
+            (None, Some(filter)) => (None, Some(filter)),
+            _ => {
+                return Err(AppError::Validation(
+                    "Give either a non-empty list of IDs or a filter".to_string(),
+                ))
+            }
+        };
+        if ids.is_some_and(|ids| ids.len() > BULK_LIMIT) {
+            return Err(AppError::Validation(format!("At most {} aliases per request", BULK_LIMIT)));
+        }
+
+        let default_filter = BulkAliasFilter::default();
+        let filter = filter.unwrap_or(&default_filter);
+
+        let mut tx = pool.begin().await?;
+
+        let aliases = sqlx::query_as::<_, Alias>(
+            r#"
+            SELECT * FROM aliases
+            WHERE user_id = $1
+            AND ($2::uuid[] IS NULL OR id = ANY($2))
+            AND (($2::uuid[] IS NOT NULL AND $3::alias_status IS NULL)
+                 OR ($3::alias_status IS NULL AND status != 'deleted')
+                 OR status = $3)
+            AND ($4::text IS NULL OR label = $4)
+            AND ($5::text IS NULL OR address LIKE '%@' || $5)
+            AND ($6::text IS NULL OR strpos(address, $6) > 0)
+            ORDER BY created_at
+            LIMIT $7
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .bind(ids)
+        .bind(filter.status.as_ref())
+        .bind(filter.label.as_deref().map(str::trim))
+        .bind(filter.domain.as_deref().map(|d| d.trim().to_lowercase()))
+        .bind(filter.query.as_deref().map(|q| q.trim().to_lowercase()))
+        .bind(BULK_LIMIT as i64 + 1)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if aliases.len() > BULK_LIMIT {
+            return Err(AppError::Validation(format!(
+                "Filter matches more than {} aliases",
+                BULK_LIMIT
+            )));
+        }
+
+        let mut results = Vec::with_capacity(aliases.len());
+        let mut changed = Vec::new();
+        for alias in &aliases {
+            let result = bulk_change(alias, &action);
+            if result.result == "updated" {
+                changed.push(alias.id);
+            }
+            results.push(result);
+        }
+
+        // Requested IDs that are not the user's
+        if let Some(ids) = ids {
+            for id in ids {
+                if !aliases.iter().any(|a| a.id == *id) {
+                    results.push(BulkItemResult {
+                        id: *id,
+                        address: None,
+                        result: "not_found",
+                        reason: None,
+                        from: None,
+                        to: None,
+                    });
+                }
+            }
+        }
+
+        if dry_run || changed.is_empty() {
+            tx.rollback().await?;
+            return Ok(results);
+        }
+
+        let sql = match &action {
+            BulkAction::Pause => "UPDATE aliases SET status = 'paused', updated_at = NOW() WHERE id = ANY($1)",
+            BulkAction::Activate => "UPDATE aliases SET status = 'active', updated_at = NOW() WHERE id = ANY($1)",
+            BulkAction::Delete => "UPDATE aliases SET status = 'deleted', updated_at = NOW() WHERE id = ANY($1)",
+            BulkAction::SetLabel { .. } => "UPDATE aliases SET label = $2, updated_at = NOW() WHERE id = ANY($1)",
+            BulkAction::SetExpiry { .. } => {
+                "UPDATE aliases SET expires_at = $2, updated_at = NOW() WHERE id = ANY($1)"
+            }
+            BulkAction::ChangeTarget => unreachable!("rejected above"),
+        };
+
+        let query = sqlx::query(sql).bind(&changed);
+        let query = match &action {
+            BulkAction::SetLabel { label } => query.bind(label.clone()),
+            BulkAction::SetExpiry { expires_at } => query.bind(*expires_at),
+            _ => query,
+        };
+        query.execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(results)
+    }
+
     pub async fn delete(pool: &PgPool, alias_id: Uuid, user_id: Uuid) -> Result<()> {
         let result = sqlx::query(
             r#"
@@ -138,6 +736,7 @@ impl AliasService {
         pool: &PgPool,
         alias_id: Uuid,
         user_id: Uuid,
+        tag: Option<&str>,
         limit: i64,
     ) -> Result<Vec<EmailLog>> {
         // Verify alias belongs to user
@@ -149,19 +748,183 @@ impl AliasService {
             FROM email_logs el
             JOIN aliases a ON el.alias_id = a.id
             WHERE el.alias_id = $1 AND a.user_id = $2
+            AND ($4::text IS NULL OR el.metadata->>'tag' = $4)
             ORDER BY el.created_at DESC
             LIMIT $3
             "#,
         )
         .bind(alias_id)
         .bind(user_id)
-        .bind(limit)
+        .bind(limit.clamp(1, MAX_LOG_LIMIT))
+        .bind(tag)
         .fetch_all(pool)
         .await?;
 
         Ok(logs)
     }
 
+    /// Search the logs of all the user's aliases, newest first. Pages are
+    /// keyed by sequence number: pass the returned cursor as `before_seq` to
+    /// continue.
+    pub async fn search_logs(
+        pool: &PgPool,
+        user_id: Uuid,
+        filter: &LogFilter,
+        before_seq: Option<i64>,
+        limit: i64,
+    ) -> Result<(Vec<LogEvent>, Option<i64>)> {
+        let limit = limit.clamp(1, MAX_LOG_LIMIT);
+
+        // Match the sender literally
+        let sender = filter.sender.as_deref().map(|s| {
+            s.to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        });
+
+        let mut events = sqlx::query_as::<_, LogEvent>(
+            r#"
+            SELECT el.seq, el.id, el.alias_id, a.address AS alias_address,
+                   el.from_email, el.subject, el.status, el.metadata, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE a.user_id = $1
+            AND ($2::uuid[] IS NULL OR el.alias_id = ANY($2))
+            AND ($3::email_status IS NULL OR el.status = $3)
+            AND ($4::text IS NULL OR lower(el.from_email) LIKE '%' || $4 || '%' OR el.sender_hash = $11)
+            AND ($5::text IS NULL OR el.metadata->>'tag' = $5)
+            AND ($6::timestamptz IS NULL OR el.created_at >= $6)
+            AND ($7::timestamptz IS NULL OR el.created_at < $7)
+            AND ($8::text IS NULL
+                 OR to_tsvector('simple', COALESCE(el.subject, '')) @@ websearch_to_tsquery('simple', $8))
+            AND ($9::bigint IS NULL OR el.seq < $9)
+            ORDER BY el.seq DESC
+            LIMIT $10
+            "#,
+        )
+        .bind(user_id)
+        .bind(filter.alias_ids.as_deref())
+        .bind(filter.status.as_ref())
+        .bind(sender)
+        .bind(filter.tag.as_deref())
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.query.as_deref())
+        .bind(before_seq)
+        .bind(limit + 1)
+        .bind(filter.sender_hash.as_deref())
+        .fetch_all(pool)
+        .await?;
+
+        // The extra row only tells whether another page follows
+        let next = if events.len() as i64 > limit {
+            events.truncate(limit as usize);
+            events.last().map(|e| e.seq)
+        } else {
+            None
+        };
+
+        Ok((events, next))
+    }
+
+    /// Log entries for the user's aliases written after `after_seq`, oldest
+    /// first, optionally restricted to a set of aliases. Sequence numbers are
+    /// taken at insert time, so an entry committed late can land below
+    /// `after_seq`; entries created after `rescan_since` are returned too
+    /// unless their sequence number is in `exclude`.
+    pub async fn get_logs_since(
+        pool: &PgPool,
+        user_id: Uuid,
+        alias_ids: Option<&[Uuid]>,
+        after_seq: i64,
+        rescan_since: DateTime<Utc>,
+        exclude: &[i64],
+        limit: i64,
+    ) -> Result<Vec<LogEvent>> {
+        let events = sqlx::query_as::<_, LogEvent>(
+            r#"
+            SELECT el.seq, el.id, el.alias_id, a.address AS alias_address,
+                   el.from_email, el.subject, el.status, el.metadata, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE a.user_id = $1
+            AND ($2::uuid[] IS NULL OR el.alias_id = ANY($2))
+            AND (el.seq > $3 OR (el.created_at > $4 AND el.seq != ALL($5)))
+            ORDER BY el.seq
+            LIMIT $6
+            "#,
+        )
+        .bind(user_id)
+        .bind(alias_ids)
+        .bind(after_seq)
+        .bind(rescan_since)
+        .bind(exclude)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Sequence numbers and creation times of the user's entries created
+    /// after `since` up to `up_to_seq`
+    pub async fn recent_log_seqs(
+        pool: &PgPool,
+        user_id: Uuid,
+        alias_ids: Option<&[Uuid]>,
+        since: DateTime<Utc>,
+        up_to_seq: i64,
+    ) -> Result<Vec<(i64, DateTime<Utc>)>> {
+        let seqs = sqlx::query_as(
+            r#"
+            SELECT el.seq, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE a.user_id = $1
+            AND ($2::uuid[] IS NULL OR el.alias_id = ANY($2))
+            AND el.created_at > $3
+            AND el.seq <= $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(alias_ids)
+        .bind(since)
+        .bind(up_to_seq)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(seqs)
+    }
+
+    /// Position of the most recent log entry across all users
+    pub async fn latest_log_seq(pool: &PgPool) -> Result<i64> {
+        let seq: Option<i64> = sqlx::query_scalar("SELECT MAX(seq) FROM email_logs")
+            .fetch_one(pool)
+            .await?;
+
+        Ok(seq.unwrap_or(0))
+    }
+
+    /// Find a paused alias whose owner asked for its mail to be quarantined
+    pub async fn find_quarantining_by_address(pool: &PgPool, address: &str) -> Result<Option<Alias>> {
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            SELECT * FROM aliases
+            WHERE address = $1
+            AND status = 'paused'
+            AND quarantine_when_paused = true
+            AND (expires_at IS NULL OR expires_at > NOW())
+            LIMIT 1
+            "#,
+        )
+        .bind(address)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(alias)
+    }
+
     /// Find alias by email address (for email forwarding)
     pub async fn find_by_address(pool: &PgPool, address: &str) -> Result<Option<Alias>> {
         let alias = sqlx::query_as::<_, Alias>(
@@ -181,3 +944,84 @@ impl AliasService {
     }
 }
 
+/// Split `base+tag@domain` into the base address and the tag
+pub fn split_subaddress(address: &str, separator: &str) -> Option<(String, String)> {
+    let (local, domain) = address.rsplit_once('@')?;
+    let (base, tag) = local.split_once(separator)?;
+
+    if base.is_empty() || tag.is_empty() {
+        return None;
+    }
+
+    Some((format!("{}@{}", base, domain), tag.to_string()))
+}
+
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+
+    if tag.is_empty() || tag.len() > 64 || tag.contains('@') || tag.chars().any(char::is_whitespace) {
+        return Err(AppError::Validation("Invalid tag".to_string()));
+    }
+
+    Ok(tag)
+}
+
+/// What `action` would do to one alias
+fn bulk_change(alias: &Alias, action: &BulkAction) -> BulkItemResult {
+    let status = |s: &AliasStatus| serde_json::json!(format!("{:?}", s).to_lowercase());
+    let deleted = matches!(alias.status, AliasStatus::Deleted);
+    let expired = alias.expires_at.is_some_and(|at| at <= Utc::now());
+
+    let (result, reason, from, to) = match action {
+        BulkAction::Delete if deleted => ("unchanged", None, None, None),
+        _ if deleted => ("skipped", Some("deleted"), None, None),
+        BulkAction::Pause if matches!(alias.status, AliasStatus::Paused) => ("unchanged", None, None, None),
+        BulkAction::Activate if matches!(alias.status, AliasStatus::Active) => ("unchanged", None, None, None),
+        BulkAction::Activate if expired => ("skipped", Some("expired"), None, None),
+        BulkAction::Pause => ("updated", None, Some(status(&alias.status)), Some(status(&AliasStatus::Paused))),
+        BulkAction::Activate => ("updated", None, Some(status(&alias.status)), Some(status(&AliasStatus::Active))),
+        BulkAction::Delete => ("updated", None, Some(status(&alias.status)), Some(status(&AliasStatus::Deleted))),
+        BulkAction::SetLabel { label } if *label == alias.label => ("unchanged", None, None, None),
+        BulkAction::SetLabel { label } => ("updated", None, Some(serde_json::json!(alias.label)), Some(serde_json::json!(label))),
+        BulkAction::SetExpiry { expires_at } if same_instant(*expires_at, alias.expires_at) => {
+            ("unchanged", None, None, None)
+        }
+        BulkAction::SetExpiry { expires_at } => (
+            "updated",
+            None,
+            Some(serde_json::json!(alias.expires_at)),
+            Some(serde_json::json!(expires_at)),
+        ),
+        BulkAction::ChangeTarget => ("skipped", Some("unsupported"), None, None),
+    };
+
+    BulkItemResult {
+        id: alias.id,
+        address: Some(alias.address.clone()),
+        result,
+        reason,
+        from,
+        to,
+    }
+}
+
+/// Equal to the second; Postgres keeps microseconds
+fn same_instant(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.timestamp() == b.timestamp(),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+fn normalize_label(label: Option<&str>) -> Result<Option<String>> {
+    let label = label.map(str::trim).filter(|l| !l.is_empty());
+
+    if label.is_some_and(|l| l.chars().count() > MAX_LABEL_LENGTH) {
+        return Err(AppError::Validation(format!(
+            "Labels can be at most {} characters",
+            MAX_LABEL_LENGTH
+        )));
+    }
+
+    Ok(label.map(str::to_string))
+}
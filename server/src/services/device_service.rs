@@ -0,0 +1,237 @@
+use crate::error::{AppError, Result};
+use crate::models::{DeviceAuthStatus, DeviceAuthorization, DeviceCredential};
+use chrono::Utc;
+use rand::Rng;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Scope granted to credentials issued through the device flow
+pub const DEVICE_SCOPE: &str = "aliases";
+
+/// User codes avoid vowels and look-alike characters so they are easy to type
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+pub struct DeviceService;
+
+impl DeviceService {
+    pub async fn start(
+        pool: &PgPool,
+        client_name: &str,
+        expires_in: u64,
+        poll_interval: u64,
+    ) -> Result<DeviceAuthorization> {
+        let device_code = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let expires_at = Utc::now() + chrono::Duration::seconds(expires_in as i64);
+
+        // User codes are short, so retry on the rare collision with a live request
+        for _ in 0..5 {
+            let user_code = generate_user_code();
+
+            let inserted = sqlx::query_as::<_, DeviceAuthorization>(
+                r#"
+                INSERT INTO device_authorizations (device_code, user_code, client_name, scope, poll_interval, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (user_code) DO NOTHING
+                RETURNING *
+                "#,
+            )
+            .bind(&device_code)
+            .bind(&user_code)
+            .bind(client_name)
+            .bind(DEVICE_SCOPE)
+            .bind(poll_interval as i32)
+            .bind(expires_at)
+            .fetch_optional(pool)
+            .await?;
+
+            if let Some(authorization) = inserted {
+                return Ok(authorization);
+            }
+        }
+
+        Err(AppError::Internal("Failed to allocate a user code".to_string()))
+    }
+
+    /// Approve or deny a pending request on behalf of the logged-in user
+    pub async fn decide(
+        pool: &PgPool,
+        user_code: &str,
+        user_id: Uuid,
+        approve: bool,
+    ) -> Result<DeviceAuthorization> {
+        let status = if approve {
+            DeviceAuthStatus::Approved
+        } else {
+            DeviceAuthStatus::Denied
+        };
+
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(
+            r#"
+            UPDATE device_authorizations
+            SET status = $1, user_id = $2
+            WHERE user_code = $3 AND status = 'pending' AND expires_at > NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(&status)
+        .bind(user_id)
+        .bind(normalize_user_code(user_code))
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invalid or expired user code".to_string()))?;
+
+        Ok(authorization)
+    }
+
+    /// Handle a device polling for its token. Errors use the RFC 8628 error codes.
+    pub async fn poll(pool: &PgPool, device_code: &str) -> Result<DeviceCredential> {
+        let authorization = sqlx::query_as::<_, DeviceAuthorization>(
+            "SELECT * FROM device_authorizations WHERE device_code = $1",
+        )
+        .bind(device_code)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::DeviceFlow("invalid_grant".to_string()))?;
+
+        let now = Utc::now();
+        if authorization.expires_at <= now {
+            return Err(AppError::DeviceFlow("expired_token".to_string()));
+        }
+
+        if let Some(last) = authorization.last_polled_at {
+            if now < last + chrono::Duration::seconds(authorization.poll_interval as i64) {
+                sqlx::query(
+                    "UPDATE device_authorizations SET poll_interval = poll_interval + 5, last_polled_at = NOW() WHERE id = $1",
+                )
+                .bind(authorization.id)
+                .execute(pool)
+                .await?;
+                return Err(AppError::DeviceFlow("slow_down".to_string()));
+            }
+        }
+
+        sqlx::query("UPDATE device_authorizations SET last_polled_at = NOW() WHERE id = $1")
+            .bind(authorization.id)
+            .execute(pool)
+            .await?;
+
+        match authorization.status {
+            DeviceAuthStatus::Pending => Err(AppError::DeviceFlow("authorization_pending".to_string())),
+            DeviceAuthStatus::Denied => Err(AppError::DeviceFlow("access_denied".to_string())),
+            DeviceAuthStatus::Consumed => Err(AppError::DeviceFlow("invalid_grant".to_string())),
+            DeviceAuthStatus::Approved => {
+                let user_id = authorization
+                    .user_id
+                    .ok_or_else(|| AppError::Internal("Approved request has no user".to_string()))?;
+
+                let mut tx = pool.begin().await?;
+
+                // Claim the request first so concurrent polls cannot mint two credentials
+                let claimed = sqlx::query(
+                    "UPDATE device_authorizations SET status = 'consumed' WHERE id = $1 AND status = 'approved'",
+                )
+                .bind(authorization.id)
+                .execute(&mut *tx)
+                .await?;
+
+                if claimed.rows_affected() == 0 {
+                    return Err(AppError::DeviceFlow("invalid_grant".to_string()));
+                }
+
+                let credential = sqlx::query_as::<_, DeviceCredential>(
+                    r#"
+                    INSERT INTO device_credentials (user_id, client_name, scope)
+                    VALUES ($1, $2, $3)
+                    RETURNING *
+                    "#,
+                )
+                .bind(user_id)
+                .bind(&authorization.client_name)
+                .bind(&authorization.scope)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                sqlx::query("UPDATE device_authorizations SET credential_id = $1 WHERE id = $2")
+                    .bind(credential.id)
+                    .bind(authorization.id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(credential)
+            }
+        }
+    }
+
+    pub async fn list_credentials(pool: &PgPool, user_id: Uuid) -> Result<Vec<DeviceCredential>> {
+        let credentials = sqlx::query_as::<_, DeviceCredential>(
+            "SELECT * FROM device_credentials WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(credentials)
+    }
+
+    pub async fn revoke(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE device_credentials
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(device_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Device not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Record use of a device credential. Returns false if it is revoked or unknown.
+    pub async fn touch_credential(pool: &PgPool, device_id: Uuid, user_id: Uuid) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE device_credentials
+            SET last_used_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(device_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn generate_user_code() -> String {
+    let mut rng = rand::thread_rng();
+    let code: String = (0..8)
+        .map(|_| USER_CODE_ALPHABET[rng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+/// Accept user codes typed in lowercase, without the dash or with stray spaces
+fn normalize_user_code(input: &str) -> String {
+    let code: String = input
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if code.len() == 8 {
+        format!("{}-{}", &code[..4], &code[4..])
+    } else {
+        code
+    }
+}
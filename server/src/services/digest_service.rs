@@ -0,0 +1,236 @@
+use crate::config::Config;
+use crate::crypto::sender_hash;
+use crate::error::{AppError, Result};
+use crate::models::{Alias, HeldMessage};
+use crate::services::{EmailService, TargetService};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Upper bound on messages collected per digest run
+const MAX_DIGEST_MESSAGES: i64 = 1000;
+
+pub struct DigestService;
+
+impl DigestService {
+    /// Store a message for the alias's next digest instead of forwarding it
+    #[allow(clippy::too_many_arguments)]
+    pub async fn hold(
+        pool: &PgPool,
+        config: &Config,
+        alias: &Alias,
+        from_email: &str,
+        subject: &str,
+        body_plain: Option<&str>,
+        body_html: Option<&str>,
+        message_id: Option<&str>,
+        tag: Option<&str>,
+    ) -> Result<()> {
+        let release_token = Uuid::new_v4().to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_logs
+                (alias_id, from_email, subject, status, metadata, body_plain, body_html, release_token, sender_hash)
+            VALUES ($1, $2, $3, 'held', $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(alias.id)
+        .bind(from_email)
+        .bind(subject)
+        .bind(serde_json::json!({
+            "delivery_mode": alias.delivery_mode.as_str(),
+            "message_id": message_id,
+            "tag": tag
+        }))
+        .bind(body_plain)
+        .bind(body_html)
+        .bind(&release_token)
+        .bind(sender_hash(config, from_email))
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Send one digest per user covering every alias whose digest period has
+    /// elapsed. Returns the number of digests sent.
+    pub async fn send_due_digests(pool: &PgPool, config: &Config) -> Result<usize> {
+        let due = sqlx::query_as::<_, HeldMessage>(
+            r#"
+            SELECT el.id, el.alias_id, a.address AS alias_address, a.tracker_protection, a.user_id,
+                   el.from_email, el.subject, el.body_plain, el.body_html, el.release_token, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE el.status = 'held'
+            AND el.digest_sent_at IS NULL
+            AND el.released_at IS NULL
+            AND el.release_token IS NOT NULL
+            AND COALESCE(a.last_digest_at, a.created_at) <= NOW() - CASE a.delivery_mode
+                WHEN 'weekly_digest' THEN INTERVAL '7 days'
+                WHEN 'daily_digest' THEN INTERVAL '1 day'
+                ELSE INTERVAL '0 seconds'
+            END
+            ORDER BY a.user_id, el.created_at
+            LIMIT $1
+            "#,
+        )
+        .bind(MAX_DIGEST_MESSAGES)
+        .fetch_all(pool)
+        .await?;
+
+        let mut by_user: BTreeMap<Uuid, Vec<HeldMessage>> = BTreeMap::new();
+        for message in due {
+            by_user.entry(message.user_id).or_default().push(message);
+        }
+
+        let mut sent = 0;
+        for (user_id, messages) in by_user {
+            // Keep the messages for a later run until there is somewhere to send them
+            let target = match TargetService::get_current(pool, user_id).await? {
+                Some(t) if t.verified => t,
+                _ => continue,
+            };
+
+            if let Err(e) = EmailService::send_digest(config, &target.email, &messages).await {
+                error!("Failed to send digest to user {}: {}", user_id, e);
+                continue;
+            }
+
+            let message_ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+            let mut alias_ids: Vec<Uuid> = messages.iter().map(|m| m.alias_id).collect();
+            alias_ids.sort();
+            alias_ids.dedup();
+
+            sqlx::query("UPDATE email_logs SET digest_sent_at = NOW() WHERE id = ANY($1)")
+                .bind(&message_ids)
+                .execute(pool)
+                .await?;
+
+            sqlx::query("UPDATE aliases SET last_digest_at = NOW() WHERE id = ANY($1)")
+                .bind(&alias_ids)
+                .execute(pool)
+                .await?;
+
+            sent += 1;
+        }
+
+        Ok(sent)
+    }
+
+    /// The held message a release link points to, without releasing it
+    pub async fn held_by_token(pool: &PgPool, token: &str) -> Result<HeldMessage> {
+        sqlx::query_as::<_, HeldMessage>(
+            r#"
+            SELECT el.id, el.alias_id, a.address AS alias_address, a.tracker_protection, a.user_id,
+                   el.from_email, el.subject, el.body_plain, el.body_html, el.release_token, el.created_at
+            FROM email_logs el
+            JOIN aliases a ON el.alias_id = a.id
+            WHERE el.release_token = $1 AND el.status = 'held'
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found or already delivered".to_string()))
+    }
+
+    /// Forward a held message to the owner's current target address. The
+    /// token is cleared before forwarding so concurrent requests cannot
+    /// deliver the message twice, and put back if delivery fails.
+    pub async fn release(pool: &PgPool, config: &Config, token: &str) -> Result<(HeldMessage, String)> {
+        let message = sqlx::query_as::<_, HeldMessage>(
+            r#"
+            WITH claimed AS (
+                UPDATE email_logs SET release_token = NULL
+                WHERE release_token = $1 AND status = 'held'
+                RETURNING *
+            )
+            SELECT el.id, el.alias_id, a.address AS alias_address, a.tracker_protection, a.user_id,
+                   el.from_email, el.subject, el.body_plain, el.body_html, $1 AS release_token, el.created_at
+            FROM claimed el
+            JOIN aliases a ON el.alias_id = a.id
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found or already delivered".to_string()))?;
+
+        match Self::deliver(pool, config, &message).await {
+            Ok(target_email) => Ok((message, target_email)),
+            Err(e) => {
+                sqlx::query(
+                    "UPDATE email_logs SET release_token = $2 WHERE id = $1 AND status = 'held' AND release_token IS NULL",
+                )
+                .bind(message.id)
+                .bind(token)
+                .execute(pool)
+                .await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn deliver(pool: &PgPool, config: &Config, message: &HeldMessage) -> Result<String> {
+        let target = match TargetService::get_current(pool, message.user_id).await? {
+            Some(t) if t.verified => t,
+            _ => {
+                return Err(AppError::Validation(
+                    "No verified target email to deliver to".to_string(),
+                ))
+            }
+        };
+
+        let (body_html, trackers) =
+            EmailService::sanitize_html(config, message.tracker_protection, message.body_html.as_deref());
+
+        EmailService::forward_email(
+            config,
+            &message.from_email,
+            &target,
+            &message.subject,
+            message.body_plain.as_deref(),
+            body_html.as_deref(),
+            Some(&message.from_email),
+            None,
+        )
+        .await?;
+
+        // The stored copy is no longer needed once delivered
+        sqlx::query(
+            r#"
+            UPDATE email_logs
+            SET status = 'forwarded', released_at = NOW(), release_token = NULL, body_plain = NULL, body_html = NULL,
+                metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('trackers', $2::jsonb)
+            WHERE id = $1
+            "#,
+        )
+        .bind(message.id)
+        .bind(serde_json::to_value(trackers).unwrap_or_default())
+        .execute(pool)
+        .await?;
+
+        info!("Released held message {} to {}", message.id, target.email);
+        Ok(target.email)
+    }
+
+    /// Drop the stored content of held messages past the retention period
+    pub async fn purge_expired(pool: &PgPool, config: &Config) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE email_logs
+            SET body_plain = NULL, body_html = NULL, release_token = NULL
+            WHERE status = 'held'
+            AND release_token IS NOT NULL
+            AND created_at < NOW() - make_interval(days => $1::int)
+            "#,
+        )
+        .bind(config.held_message_retention_days as i32)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,240 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{Alias, AliasType, Directory};
+use crate::services::{AliasService, ReservedNameService, SystemDomainService};
+use crate::validation::check_alias_policy;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub struct DirectoryService;
+
+impl DirectoryService {
+    pub async fn create(pool: &PgPool, config: &Config, user_id: Uuid, name: &str) -> Result<Directory> {
+        let name = normalize_name(name)?;
+        let policy = ReservedNameService::policy(pool, config).await?;
+        check_alias_policy(&name, "name", &policy, true)?;
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM directories WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+
+        if count >= config.directory_limit {
+            return Err(AppError::Validation(format!(
+                "Accounts can have at most {} directories",
+                config.directory_limit
+            )));
+        }
+
+        // Existing aliases of other users must not end up inside the namespace
+        let conflict = sqlx::query(
+            r#"
+            SELECT id FROM aliases
+            WHERE user_id != $1 AND domain_id IS NULL
+            AND (split_part(address, '@', 1) LIKE $2 || '.%' OR split_part(address, '@', 2) LIKE $2 || '.%')
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(&name)
+        .fetch_optional(pool)
+        .await?;
+
+        if conflict.is_some() {
+            return Err(AppError::Validation("Directory name is taken".to_string()));
+        }
+
+        let directory = sqlx::query_as::<_, Directory>(
+            "INSERT INTO directories (user_id, name) VALUES ($1, $2) RETURNING *",
+        )
+        .bind(user_id)
+        .bind(&name)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                AppError::Validation("Directory name is taken".to_string())
+            }
+            e => e.into(),
+        })?;
+
+        Ok(directory)
+    }
+
+    pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<Directory>> {
+        let directories = sqlx::query_as::<_, Directory>(
+            "SELECT * FROM directories WHERE user_id = $1 ORDER BY name",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(directories)
+    }
+
+    /// Stop or resume creating aliases from incoming mail. Aliases already
+    /// created are not affected.
+    pub async fn toggle(pool: &PgPool, id: Uuid, user_id: Uuid, enabled: bool) -> Result<Directory> {
+        let directory = sqlx::query_as::<_, Directory>(
+            r#"
+            UPDATE directories
+            SET enabled = $1, updated_at = NOW()
+            WHERE id = $2 AND user_id = $3
+            RETURNING *
+            "#,
+        )
+        .bind(enabled)
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Directory not found".to_string()))?;
+
+        Ok(directory)
+    }
+
+    /// Release the name. Aliases created in the directory are kept.
+    pub async fn delete(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<()> {
+        let result = sqlx::query("DELETE FROM directories WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Directory not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Reject a custom system-domain alias inside another user's directory
+    pub async fn check_custom(pool: &PgPool, user_id: Uuid, custom: &str) -> Result<()> {
+        let custom = custom.to_lowercase();
+        let Some((name, _)) = custom.split_once('.') else {
+            return Ok(());
+        };
+
+        let owner: Option<Uuid> = sqlx::query_scalar("SELECT user_id FROM directories WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+        if owner.is_some_and(|owner| owner != user_id) {
+            return Err(AppError::Validation("Alias is inside another user's directory".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Create an alias for mail to `name.anything@domain` or
+    /// `anything@name.domain` on an enabled system domain
+    pub async fn directory_alias(pool: &PgPool, config: &Config, address: &str) -> Result<Option<Alias>> {
+        let Some((local, domain)) = address.rsplit_once('@') else {
+            return Ok(None);
+        };
+
+        let (name, local_part) = match SystemDomainService::find(pool, domain).await? {
+            Some(system) if system.enabled => match local.split_once('.') {
+                Some((name, rest)) => (name, rest),
+                None => return Ok(None),
+            },
+            Some(_) => return Ok(None),
+            None => match domain.split_once('.') {
+                Some((name, parent))
+                    if SystemDomainService::find(pool, parent).await?.is_some_and(|s| s.enabled) =>
+                {
+                    (name, local)
+                }
+                _ => return Ok(None),
+            },
+        };
+
+        if !valid_local_part(local_part) {
+            return Ok(None);
+        }
+
+        let directory = sqlx::query_as::<_, Directory>(
+            "SELECT * FROM directories WHERE name = $1 AND enabled = true",
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(directory) = directory else {
+            return Ok(None);
+        };
+
+        let created_today: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM aliases WHERE directory_id = $1 AND created_at > NOW() - INTERVAL '1 day'",
+        )
+        .bind(directory.id)
+        .fetch_one(pool)
+        .await?;
+
+        if created_today >= config.directory_daily_alias_limit {
+            warn!("Directory {} reached its daily alias limit, ignoring {}", directory.name, address);
+            return Ok(None);
+        }
+
+        let alias = match AliasService::create(
+            pool,
+            directory.user_id,
+            AliasType::Custom,
+            Some(local.to_string()),
+            None,
+            None,
+            domain,
+            None,
+        )
+        .await
+        {
+            Ok(alias) => alias,
+            // The address exists, possibly deleted, or is otherwise unavailable
+            Err(e @ (AppError::Validation(_) | AppError::InvalidField { .. } | AppError::Conflict(_))) => {
+                warn!("Directory {} did not create {}: {}", directory.name, address, e);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let alias = sqlx::query_as::<_, Alias>(
+            "UPDATE aliases SET directory_id = $1 WHERE id = $2 RETURNING *",
+        )
+        .bind(directory.id)
+        .bind(alias.id)
+        .fetch_one(pool)
+        .await?;
+
+        info!("Created directory alias {}", alias.address);
+        Ok(Some(alias))
+    }
+}
+
+fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+
+    if name.len() < 3
+        || name.len() > 32
+        || name.starts_with('-')
+        || name.ends_with('-')
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(AppError::Validation(
+            "Directory names are 3 to 32 letters, digits or hyphens".to_string(),
+        ));
+    }
+
+    Ok(name)
+}
+
+fn valid_local_part(local: &str) -> bool {
+    !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
@@ -0,0 +1,500 @@
+use crate::config::Config;
+use crate::dns::{normalize_host, DnsResolver, RecordType};
+use crate::error::{AppError, Result};
+use crate::models::{Alias, AliasType, Domain};
+use crate::services::{AliasService, SystemDomainService};
+use rand::Rng;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Prefix of the TXT record proving ownership of a domain
+const VERIFICATION_PREFIX: &str = "hush-verification=";
+
+/// DKIM selector delegated to the system domain
+const DKIM_SELECTOR: &str = "hush";
+
+/// A DNS record the user has to publish for their domain
+#[derive(Debug, Serialize)]
+pub struct DnsRecord {
+    #[serde(rename = "type")]
+    pub record_type: &'static str,
+    pub name: String,
+    pub value: String,
+    pub purpose: &'static str,
+}
+
+/// Which of the required records were found by the last check
+#[derive(Debug, Default, Serialize)]
+pub struct DomainCheck {
+    pub ownership: bool,
+    pub mx: bool,
+    pub spf: bool,
+    pub dkim: bool,
+}
+
+pub struct DomainService;
+
+impl DomainService {
+    pub async fn create(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+        domain: &str,
+        catch_all: bool,
+    ) -> Result<Domain> {
+        let domain = normalize_domain(config, domain)?;
+
+        if SystemDomainService::find(pool, &domain).await?.is_some() {
+            return Err(AppError::Validation("System domains cannot be registered".to_string()));
+        }
+
+        let taken = sqlx::query("SELECT id FROM domains WHERE domain = $1 AND (user_id = $2 OR verified_at IS NOT NULL)")
+            .bind(&domain)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+        if taken.is_some() {
+            return Err(AppError::Validation("Domain is already registered".to_string()));
+        }
+
+        let token: [u8; 16] = rand::thread_rng().gen();
+        let token: String = token.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let domain = sqlx::query_as::<_, Domain>(
+            r#"
+            INSERT INTO domains (user_id, domain, verification_token, catch_all)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(&domain)
+        .bind(&token)
+        .bind(catch_all)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(domain)
+    }
+
+    pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<Domain>> {
+        let domains = sqlx::query_as::<_, Domain>(
+            "SELECT * FROM domains WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(domains)
+    }
+
+    pub async fn get_by_id(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<Domain> {
+        let domain = sqlx::query_as::<_, Domain>(
+            "SELECT * FROM domains WHERE id = $1 AND user_id = $2",
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Domain not found".to_string()))?;
+
+        Ok(domain)
+    }
+
+    /// Records to publish: the ownership challenge plus what is needed to
+    /// receive mail for the domain
+    pub fn records(config: &Config, domain: &Domain) -> Vec<DnsRecord> {
+        vec![
+            DnsRecord {
+                record_type: "TXT",
+                name: domain.domain.clone(),
+                value: format!("{}{}", VERIFICATION_PREFIX, domain.verification_token),
+                purpose: "ownership",
+            },
+            DnsRecord {
+                record_type: "MX",
+                name: domain.domain.clone(),
+                value: format!("10 {}", mx_host(config)),
+                purpose: "mx",
+            },
+            DnsRecord {
+                record_type: "TXT",
+                name: domain.domain.clone(),
+                value: format!("v=spf1 include:{} ~all", spf_include(config)),
+                purpose: "spf",
+            },
+            DnsRecord {
+                record_type: "CNAME",
+                name: dkim_name(&domain.domain),
+                value: dkim_name(&config.hush_domain),
+                purpose: "dkim",
+            },
+        ]
+    }
+
+    /// Look up the domain's records. Finding the ownership challenge marks
+    /// the domain verified; the other records are reported but not required.
+    pub async fn verify(
+        pool: &PgPool,
+        config: &Config,
+        resolver: &dyn DnsResolver,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(Domain, DomainCheck)> {
+        let domain = Self::get_by_id(pool, id, user_id).await?;
+        let check = check_records(config, resolver, &domain.domain, &domain.verification_token).await?;
+
+        let domain = if check.ownership {
+            sqlx::query_as::<_, Domain>(
+                r#"
+                UPDATE domains
+                SET verified_at = COALESCE(verified_at, NOW()), last_checked_at = NOW(), updated_at = NOW()
+                WHERE id = $1
+                RETURNING *
+                "#,
+            )
+            .bind(domain.id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                    AppError::Validation("Domain is already verified by another account".to_string())
+                }
+                e => e.into(),
+            })?
+        } else {
+            sqlx::query_as::<_, Domain>(
+                "UPDATE domains SET last_checked_at = NOW() WHERE id = $1 RETURNING *",
+            )
+            .bind(domain.id)
+            .fetch_one(pool)
+            .await?
+        };
+
+        Ok((domain, check))
+    }
+
+    pub async fn set_catch_all(pool: &PgPool, id: Uuid, user_id: Uuid, catch_all: bool) -> Result<Domain> {
+        let domain = sqlx::query_as::<_, Domain>(
+            r#"
+            UPDATE domains
+            SET catch_all = $1, updated_at = NOW()
+            WHERE id = $2 AND user_id = $3
+            RETURNING *
+            "#,
+        )
+        .bind(catch_all)
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Domain not found".to_string()))?;
+
+        Ok(domain)
+    }
+
+    /// Remove the domain and delete every alias on it
+    pub async fn delete(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE aliases SET status = 'deleted', updated_at = NOW()
+            WHERE domain_id = (SELECT id FROM domains WHERE id = $1 AND user_id = $2)
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query("DELETE FROM domains WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Domain not found".to_string()));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Domain and domain ID for a new alias: the default system domain, an
+    /// enabled system domain, or one of the user's verified domains
+    pub async fn for_new_alias(
+        pool: &PgPool,
+        user_id: Uuid,
+        requested: Option<&str>,
+    ) -> Result<(String, Option<Uuid>)> {
+        let requested = match requested.map(normalize_host) {
+            Some(d) if !d.is_empty() => d,
+            _ => return Ok((SystemDomainService::default_domain(pool).await?.domain, None)),
+        };
+
+        if let Some(system) = SystemDomainService::find(pool, &requested).await? {
+            if !system.enabled {
+                return Err(AppError::Validation(
+                    "Domain is not accepting new aliases".to_string(),
+                ));
+            }
+            return Ok((system.domain, None));
+        }
+
+        let domain = sqlx::query_as::<_, Domain>(
+            "SELECT * FROM domains WHERE domain = $1 AND user_id = $2 AND verified_at IS NOT NULL",
+        )
+        .bind(&requested)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Validation("Domain is not verified for this account".to_string()))?;
+
+        Ok((domain.domain, Some(domain.id)))
+    }
+
+    /// Create an alias for mail to an unknown address on a verified
+    /// catch-all domain, up to the daily limit per domain
+    pub async fn catch_all_alias(pool: &PgPool, config: &Config, address: &str) -> Result<Option<Alias>> {
+        let Some((local, domain)) = address.rsplit_once('@') else {
+            return Ok(None);
+        };
+
+        let domain = sqlx::query_as::<_, Domain>(
+            "SELECT * FROM domains WHERE domain = $1 AND verified_at IS NOT NULL AND catch_all = true",
+        )
+        .bind(domain)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(domain) = domain else {
+            return Ok(None);
+        };
+
+        let created_today: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM aliases WHERE domain_id = $1 AND created_at > NOW() - INTERVAL '1 day'",
+        )
+        .bind(domain.id)
+        .fetch_one(pool)
+        .await?;
+
+        if created_today >= config.catch_all_daily_alias_limit {
+            warn!("Domain {} reached its daily catch-all limit, ignoring {}", domain.domain, address);
+            return Ok(None);
+        }
+
+        match AliasService::create(
+            pool,
+            domain.user_id,
+            AliasType::Custom,
+            Some(local.to_string()),
+            None,
+            None,
+            &domain.domain,
+            Some(domain.id),
+        )
+        .await
+        {
+            Ok(alias) => {
+                info!("Created catch-all alias {}", alias.address);
+                Ok(Some(alias))
+            }
+            // The address exists, possibly deleted, or is otherwise unavailable
+            Err(e @ (AppError::Validation(_) | AppError::InvalidField { .. } | AppError::Conflict(_))) => {
+                warn!("Catch-all did not create {}: {}", address, e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Lower-case and check a domain name, rejecting the system domain
+fn normalize_domain(config: &Config, domain: &str) -> Result<String> {
+    let domain = normalize_host(domain);
+    let invalid = || AppError::Validation("Invalid domain name".to_string());
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    if domain.len() > 253 || labels.len() < 2 {
+        return Err(invalid());
+    }
+    for label in &labels {
+        if label.is_empty()
+            || label.len() > 63
+            || label.starts_with('-')
+            || label.ends_with('-')
+            || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(invalid());
+        }
+    }
+    if labels.last().is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit())) {
+        return Err(invalid());
+    }
+
+    let system = &config.hush_domain;
+    if domain == *system || domain.ends_with(&format!(".{}", system)) {
+        return Err(AppError::Validation("The system domain cannot be registered".to_string()));
+    }
+
+    Ok(domain)
+}
+
+/// Compare the records published for `domain` with the required ones
+async fn check_records(
+    config: &Config,
+    resolver: &dyn DnsResolver,
+    domain: &str,
+    verification_token: &str,
+) -> Result<DomainCheck> {
+    let txt = resolver.lookup(domain, RecordType::Txt).await?;
+    let mx = resolver.lookup(domain, RecordType::Mx).await?;
+    let dkim = resolver.lookup(&dkim_name(domain), RecordType::Cname).await?;
+
+    let challenge = format!("{}{}", VERIFICATION_PREFIX, verification_token);
+    let spf_mechanism = format!("include:{}", spf_include(config));
+    Ok(DomainCheck {
+        ownership: txt.iter().any(|record| record.trim() == challenge),
+        mx: mx.iter().any(|record| {
+            record.split_whitespace().nth(1).map(normalize_host) == Some(mx_host(config))
+        }),
+        spf: txt.iter().any(|record| {
+            record.starts_with("v=spf1")
+                && record.split_whitespace().any(|term| term.eq_ignore_ascii_case(&spf_mechanism))
+        }),
+        dkim: dkim.iter().any(|record| *record == dkim_name(&config.hush_domain)),
+    })
+}
+
+fn mx_host(config: &Config) -> String {
+    config
+        .domain_mx_host
+        .as_deref()
+        .map(normalize_host)
+        .unwrap_or_else(|| format!("mx.{}", config.hush_domain))
+}
+
+fn spf_include(config: &Config) -> String {
+    config
+        .domain_spf_include
+        .as_deref()
+        .map(normalize_host)
+        .unwrap_or_else(|| config.hush_domain.clone())
+}
+
+fn dkim_name(domain: &str) -> String {
+    format!("{}._domainkey.{}", DKIM_SELECTOR, domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::StaticResolver;
+
+    const TOKEN: &str = "abc123";
+
+    fn config() -> Config {
+        let mut config = Config::from_env().unwrap();
+        config.hush_domain = "hush.test".to_string();
+        config.domain_mx_host = None;
+        config.domain_spf_include = None;
+        config
+    }
+
+    /// Every record the user is asked to publish for example.com
+    fn published() -> StaticResolver {
+        StaticResolver::default()
+            .with("example.com", RecordType::Txt, "hush-verification=abc123")
+            .with("example.com", RecordType::Txt, "v=spf1 include:hush.test ~all")
+            .with("example.com", RecordType::Mx, "10 mx.hush.test")
+            .with("hush._domainkey.example.com", RecordType::Cname, "hush._domainkey.hush.test")
+    }
+
+    async fn check(config: &Config, resolver: &StaticResolver) -> DomainCheck {
+        check_records(config, resolver, "example.com", TOKEN).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn passes_when_all_records_are_published() {
+        let check = check(&config(), &published()).await;
+        assert!(check.ownership && check.mx && check.spf && check.dkim);
+    }
+
+    #[tokio::test]
+    async fn fails_every_check_without_records() {
+        let check = check(&config(), &StaticResolver::default()).await;
+        assert!(!check.ownership && !check.mx && !check.spf && !check.dkim);
+    }
+
+    #[tokio::test]
+    async fn ownership_needs_this_domain_token() {
+        let other_token = StaticResolver::default().with("example.com", RecordType::Txt, "hush-verification=abc1234");
+        assert!(!check(&config(), &other_token).await.ownership);
+
+        let padded = StaticResolver::default().with("example.com", RecordType::Txt, " hush-verification=abc123 ");
+        assert!(check(&config(), &padded).await.ownership);
+
+        // The challenge on another name does not count
+        let subdomain = StaticResolver::default().with("www.example.com", RecordType::Txt, "hush-verification=abc123");
+        assert!(!check(&config(), &subdomain).await.ownership);
+    }
+
+    #[tokio::test]
+    async fn mx_must_point_at_the_mail_host() {
+        let backup = StaticResolver::default()
+            .with("example.com", RecordType::Mx, "10 mail.example.com")
+            .with("example.com", RecordType::Mx, "20 MX.Hush.Test.");
+        assert!(check(&config(), &backup).await.mx);
+
+        let elsewhere = StaticResolver::default().with("example.com", RecordType::Mx, "10 mx.hush.test.evil.com");
+        assert!(!check(&config(), &elsewhere).await.mx);
+
+        let mut config = config();
+        config.domain_mx_host = Some("Inbound.Hush.Test.".to_string());
+        assert!(!check(&config, &published()).await.mx);
+        let custom = StaticResolver::default().with("example.com", RecordType::Mx, "5 inbound.hush.test");
+        assert!(check(&config, &custom).await.mx);
+    }
+
+    #[tokio::test]
+    async fn spf_must_include_the_system_domain() {
+        let spf = |record: &str| StaticResolver::default().with("example.com", RecordType::Txt, record);
+
+        assert!(check(&config(), &spf("v=spf1 mx INCLUDE:hush.test -all")).await.spf);
+        assert!(!check(&config(), &spf("v=spf1 include:hush.test.evil.com ~all")).await.spf);
+        assert!(!check(&config(), &spf("v=spf1 include:other.test ~all")).await.spf);
+        assert!(!check(&config(), &spf("include:hush.test")).await.spf);
+
+        let mut config = config();
+        config.domain_spf_include = Some("spf.hush.test".to_string());
+        assert!(!check(&config, &published()).await.spf);
+        assert!(check(&config, &spf("v=spf1 include:spf.hush.test ~all")).await.spf);
+    }
+
+    #[tokio::test]
+    async fn dkim_must_delegate_to_the_system_domain() {
+        let cname = |target: &str| {
+            StaticResolver::default().with("hush._domainkey.example.com", RecordType::Cname, target)
+        };
+
+        assert!(check(&config(), &cname("hush._domainkey.hush.test")).await.dkim);
+        assert!(!check(&config(), &cname("default._domainkey.hush.test")).await.dkim);
+
+        // The CNAME must be on the hush selector
+        let other_selector = StaticResolver::default()
+            .with("default._domainkey.example.com", RecordType::Cname, "hush._domainkey.hush.test");
+        assert!(!check(&config(), &other_selector).await.dkim);
+    }
+
+    #[tokio::test]
+    async fn lookup_failures_are_errors() {
+        let resolver = published().failing("example.com", RecordType::Mx);
+        assert!(check_records(&config(), &resolver, "example.com", TOKEN).await.is_err());
+    }
+}
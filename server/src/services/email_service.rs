@@ -1,12 +1,48 @@
+use crate::api::pages::escape_html;
 use crate::config::Config;
 use crate::error::{AppError, Result};
+use crate::html::{strip_trackers, SanitizeOptions, TrackerReport};
+use crate::models::{HeldMessage, TargetEmail, TrackerProtection};
+use crate::pgp;
+use crate::services::ImageProxyService;
+use chrono::{DateTime, Utc};
 use lettre::{
-    message::{header::ContentType, MessageBuilder, MultiPart, SinglePart},
+    message::{
+        header::{ContentDisposition, ContentType, Header, HeaderName, HeaderValue},
+        MessageBuilder, MultiPart, SinglePart,
+    },
     transport::smtp::authentication::Credentials,
-    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
 };
+use std::net::IpAddr;
 use tracing::{error, info};
 
+const DIGEST_TEXT: &str = include_str!("../../templates/digest.txt");
+const DIGEST_HTML: &str = include_str!("../../templates/digest.html");
+const DIGEST_ITEM_TEXT: &str = include_str!("../../templates/digest_item.txt");
+const DIGEST_ITEM_HTML: &str = include_str!("../../templates/digest_item.html");
+
+/// Longest message preview shown in a digest, in characters
+const SNIPPET_LENGTH: usize = 160;
+
+/// `X-Hush-Spam` header added to forwarded mail that was tagged as spam
+#[derive(Debug, Clone)]
+struct SpamHeader(String);
+
+impl Header for SpamHeader {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("X-Hush-Spam")
+    }
+
+    fn parse(s: &str) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(SpamHeader(s.to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.0.clone())
+    }
+}
+
 pub struct EmailService;
 
 impl EmailService {
@@ -15,32 +51,77 @@ impl EmailService {
         to: &str,
         token: &str,
     ) -> Result<()> {
-        info!("Sending verification email to: {}", to);
-        info!("SMTP_FROM value: '{}'", config.smtp_from);
         let verification_url = format!("{}/api/v1/targets/verify?token={}", config.api_base_url, token);
 
-        let from_addr = config.smtp_from.trim();
-        if from_addr.is_empty() {
-            return Err(AppError::Internal("SMTP_FROM is empty".to_string()));
-        }
+        Self::send_verification_link(
+            config,
+            to,
+            "Verify your email address for Hush",
+            "Please verify your email address by clicking the following link:",
+            &verification_url,
+        )
+        .await
+    }
 
-        let email = MessageBuilder::new()
-            .from(from_addr.parse().map_err(|e| {
-                error!("Failed to parse SMTP_FROM '{}': {}", from_addr, e);
-                AppError::Internal(format!("Invalid from address '{}': {}", from_addr, e))
-            })?)
-            .to(to.parse().map_err(|e| {
-                AppError::Internal(format!("Invalid to address: {}", e))
-            })?)
-            .subject("Verify your email address for Hush")
+    /// Confirm ownership of the address used to register an account
+    pub async fn send_account_verification_email(
+        config: &Config,
+        to: &str,
+        token: &str,
+    ) -> Result<()> {
+        let verification_url = format!("{}/api/v1/auth/verify?token={}", config.api_base_url, token);
+
+        Self::send_verification_link(
+            config,
+            to,
+            "Confirm your Hush account",
+            "Please confirm the email address for your new Hush account by clicking the following link:",
+            &verification_url,
+        )
+        .await
+    }
+
+    /// Ask the current or the new address to confirm an account email change
+    pub async fn send_email_change_confirmation(
+        config: &Config,
+        to: &str,
+        token: &str,
+        new_email: &str,
+    ) -> Result<()> {
+        let confirm_url = format!("{}/api/v1/account/email/confirm?token={}", config.api_base_url, token);
+
+        Self::send_verification_link(
+            config,
+            to,
+            "Confirm your Hush account email change",
+            &format!(
+                "A request was made to change your Hush account email to {}. Both the old and the new address must confirm. Click the following link to confirm:",
+                new_email
+            ),
+            &confirm_url,
+        )
+        .await
+    }
+
+    async fn send_verification_link(
+        config: &Config,
+        to: &str,
+        subject: &str,
+        intro: &str,
+        url: &str,
+    ) -> Result<()> {
+        info!("Sending verification email to: {}", to);
+        info!("SMTP_FROM value: '{}'", config.smtp_from);
+
+        let email = Self::system_message(config, to, subject)?
             .multipart(
                 MultiPart::alternative()
                     .singlepart(
                         SinglePart::builder()
                             .header(ContentType::TEXT_PLAIN)
                             .body(format!(
-                                "Please verify your email address by clicking the following link:\n\n{}\n\nThis link will expire in 24 hours.",
-                                verification_url
+                                "{}\n\n{}\n\nThis link will expire in 24 hours.",
+                                intro, url
                             )),
                     )
                     .singlepart(
@@ -50,58 +131,44 @@ impl EmailService {
                                 r#"
                                 <html>
                                 <body>
-                                    <h2>Verify your email address</h2>
-                                    <p>Please click the following link to verify your email address:</p>
+                                    <h2>{}</h2>
+                                    <p>{}</p>
                                     <p><a href="{}">Verify Email</a></p>
                                     <p>This link will expire in 24 hours.</p>
                                 </body>
                                 </html>
                                 "#,
-                                verification_url
+                                escape_html(subject),
+                                escape_html(intro),
+                                escape_html(url)
                             )),
                     ),
             )
             .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
 
-        let creds = Credentials::new(
-            config.smtp_username.clone(),
-            config.smtp_password.clone(),
-        );
-
         // For Brevo and most SMTP servers, we need STARTTLS
         info!("Connecting to SMTP server: {}:{}", config.smtp_host, config.smtp_port);
-        
-        let mailer = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
-            .map_err(|e| {
-                error!("Failed to create SMTP transport: {}", e);
-                AppError::Internal(format!("Failed to create SMTP transport: {}", e))
-            })?
-            .port(config.smtp_port)
-            .credentials(creds)
-            .build();
-
-        mailer
-            .send(email)
-            .await
-            .map_err(|e| {
-                error!("Failed to send email: {}", e);
-                AppError::Internal(format!("Failed to send email: {}", e))
-            })?;
+        Self::deliver(config, email).await?;
 
         info!("Verification email sent successfully to: {}", to);
         Ok(())
     }
 
-    /// Forward email to target address
+    /// Forward email to target address, encrypted when the target has an
+    /// OpenPGP key. `spam` is the value of the `X-Hush-Spam` header for
+    /// messages tagged as spam.
+    #[allow(clippy::too_many_arguments)]
     pub async fn forward_email(
         config: &Config,
         from: &str,
-        to: &str,
+        target: &TargetEmail,
         subject: &str,
         text_body: Option<&str>,
         html_body: Option<&str>,
         reply_to: Option<&str>,
+        spam: Option<&str>,
     ) -> Result<()> {
+        let to = target.email.as_str();
         info!("Forwarding email from {} to {}", from, to);
 
         let from_addr = config.smtp_from.trim();
@@ -126,6 +193,10 @@ impl EmailService {
             })?);
         }
 
+        if let Some(spam) = spam {
+            builder = builder.header(SpamHeader(spam.to_string()));
+        }
+
         // Build multipart message
         let multipart = if let Some(html) = html_body {
             // Both text and HTML
@@ -168,6 +239,13 @@ impl EmailService {
                 )
         };
 
+        // Mail that is already encrypted is passed through as is
+        let multipart = if pgp::is_encrypted(text_body, html_body) {
+            multipart
+        } else {
+            Self::encrypt_for(target, multipart)?
+        };
+
         let email = builder
             .multipart(multipart)
             .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
@@ -199,5 +277,312 @@ impl EmailService {
         info!("Email forwarded successfully from {} to {}", from, to);
         Ok(())
     }
+
+    /// Apply the alias's tracker protection to an HTML body before it is
+    /// forwarded. The report is `None` when protection is off.
+    pub fn sanitize_html(
+        config: &Config,
+        protection: TrackerProtection,
+        html_body: Option<&str>,
+    ) -> (Option<String>, Option<TrackerReport>) {
+        let Some(html) = html_body else {
+            return (None, None);
+        };
+
+        let proxy = |src: &str| ImageProxyService::url_for(config, src);
+        let options = match protection {
+            TrackerProtection::Off => return (Some(html.to_string()), None),
+            TrackerProtection::Standard => SanitizeOptions { proxy_image: None },
+            TrackerProtection::ProxyImages => SanitizeOptions { proxy_image: Some(&proxy) },
+        };
+
+        let (cleaned, report) = strip_trackers(html, &options);
+        (Some(cleaned), Some(report))
+    }
+
+    /// Tell the account owner that repeated failed logins locked their account
+    pub async fn send_lockout_notice(
+        config: &Config,
+        to: &str,
+        locked_until: DateTime<Utc>,
+        ip: Option<IpAddr>,
+    ) -> Result<()> {
+        info!("Sending lockout notice to: {}", to);
+
+        let source = ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "an unknown address".to_string());
+        let until = locked_until.format("%Y-%m-%d %H:%M UTC");
+
+        let email = Self::system_message(config, to, "Your Hush account has been temporarily locked")?
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_PLAIN)
+                            .body(format!(
+                                "We noticed several failed sign-in attempts on your Hush account from {}.\n\nFor your protection, sign-in is locked until {}.\n\nIf this wasn't you, consider changing your password once the lock expires.",
+                                source, until
+                            )),
+                    )
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_HTML)
+                            .body(format!(
+                                r#"
+                                <html>
+                                <body>
+                                    <h2>Your account has been temporarily locked</h2>
+                                    <p>We noticed several failed sign-in attempts on your Hush account from {}.</p>
+                                    <p>For your protection, sign-in is locked until {}.</p>
+                                    <p>If this wasn't you, consider changing your password once the lock expires.</p>
+                                </body>
+                                </html>
+                                "#,
+                                source, until
+                            )),
+                    ),
+            )
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        Self::deliver(config, email).await?;
+
+        info!("Lockout notice sent to: {}", to);
+        Ok(())
+    }
+
+    /// Summary of messages held for digest-mode aliases, each with a link to
+    /// release the full message
+    pub async fn send_digest(config: &Config, to: &str, messages: &[HeldMessage]) -> Result<()> {
+        info!("Sending digest of {} messages to: {}", messages.len(), to);
+
+        let title = "Your Hush digest";
+        let intro = format!(
+            "{} message{} arrived at your digest aliases.",
+            messages.len(),
+            if messages.len() == 1 { "" } else { "s" }
+        );
+        let retention_days = config.held_message_retention_days.to_string();
+
+        let mut text_items = String::new();
+        let mut html_items = String::new();
+        for message in messages {
+            let release_url = format!(
+                "{}/api/v1/messages/release?token={}",
+                config.api_base_url,
+                message.release_token.as_deref().unwrap_or_default()
+            );
+            let received = message.created_at.format("%Y-%m-%d %H:%M UTC").to_string();
+            let snippet = snippet(message.body_plain.as_deref(), message.body_html.as_deref());
+
+            let values = [
+                ("alias", message.alias_address.as_str()),
+                ("received", received.as_str()),
+                ("subject", message.subject.as_str()),
+                ("from", message.from_email.as_str()),
+                ("snippet", snippet.as_str()),
+                ("release_url", release_url.as_str()),
+            ];
+            text_items.push_str(&render(DIGEST_ITEM_TEXT, &values, &[], false));
+            html_items.push_str(&render(DIGEST_ITEM_HTML, &values, &[], true));
+        }
+
+        let values = [("title", title), ("intro", intro.as_str()), ("retention_days", retention_days.as_str())];
+        let text_body = render(DIGEST_TEXT, &values, &[("items", &text_items)], false);
+        let html_body = render(DIGEST_HTML, &values, &[("items", &html_items)], true);
+
+        let email = Self::system_message(config, to, title)?
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_PLAIN)
+                            .body(text_body),
+                    )
+                    .singlepart(
+                        SinglePart::builder()
+                            .header(ContentType::TEXT_HTML)
+                            .body(html_body),
+                    ),
+            )
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        Self::deliver(config, email).await?;
+
+        info!("Digest sent to: {}", to);
+        Ok(())
+    }
+
+    /// Send an encrypted test message to confirm the target's OpenPGP key works
+    pub async fn send_pgp_test(config: &Config, target: &TargetEmail) -> Result<()> {
+        info!("Sending OpenPGP test message to: {}", target.email);
+
+        let body = MultiPart::alternative().singlepart(
+            SinglePart::builder()
+                .header(ContentType::TEXT_PLAIN)
+                .body(format!(
+                    "This message was encrypted by Hush to the OpenPGP key {}.\n\nIf you can read it, mail forwarded to {} will be encrypted the same way.",
+                    target.pgp_fingerprint.as_deref().unwrap_or_default(),
+                    target.email
+                )),
+        );
+
+        let email = Self::system_message(config, &target.email, "Hush OpenPGP test message")?
+            .multipart(Self::encrypt_for(target, body)?)
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        Self::deliver(config, email).await?;
+
+        info!("OpenPGP test message sent to: {}", target.email);
+        Ok(())
+    }
+
+    /// Wrap the message body as PGP/MIME (RFC 3156) when the target has an
+    /// OpenPGP key. The whole MIME body is encrypted, attachments included.
+    fn encrypt_for(target: &TargetEmail, body: MultiPart) -> Result<MultiPart> {
+        let Some(armored) = target.pgp_public_key.as_deref() else {
+            return Ok(body);
+        };
+
+        let key = pgp::PublicKey::parse(armored)
+            .map_err(|e| AppError::Internal(format!("Stored OpenPGP key is unusable: {}", e)))?;
+        let encrypted = key.encrypt(&body.formatted())?;
+        let content_type = |value: &str| {
+            ContentType::parse(value)
+                .map_err(|e| AppError::Internal(format!("Invalid content type: {}", e)))
+        };
+
+        Ok(MultiPart::encrypted("application/pgp-encrypted".to_string())
+            .singlepart(
+                SinglePart::builder()
+                    .header(content_type("application/pgp-encrypted")?)
+                    .body("Version: 1\r\n".to_string()),
+            )
+            .singlepart(
+                SinglePart::builder()
+                    .header(content_type("application/octet-stream; name=\"encrypted.asc\"")?)
+                    .header(ContentDisposition::inline_with_name("encrypted.asc"))
+                    .body(encrypted),
+            ))
+    }
+
+    /// Start a message from the system sender address
+    fn system_message(config: &Config, to: &str, subject: &str) -> Result<MessageBuilder> {
+        let from_addr = config.smtp_from.trim();
+        if from_addr.is_empty() {
+            return Err(AppError::Internal("SMTP_FROM is empty".to_string()));
+        }
+
+        Ok(MessageBuilder::new()
+            .from(from_addr.parse().map_err(|e| {
+                error!("Failed to parse SMTP_FROM '{}': {}", from_addr, e);
+                AppError::Internal(format!("Invalid from address '{}': {}", from_addr, e))
+            })?)
+            .to(to.parse().map_err(|e| {
+                AppError::Internal(format!("Invalid to address: {}", e))
+            })?)
+            .subject(subject))
+    }
+
+    /// Send a built message through the configured SMTP relay
+    async fn deliver(config: &Config, email: Message) -> Result<()> {
+        let creds = Credentials::new(
+            config.smtp_username.clone(),
+            config.smtp_password.clone(),
+        );
+
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+            .map_err(|e| {
+                error!("Failed to create SMTP transport: {}", e);
+                AppError::Internal(format!("Failed to create SMTP transport: {}", e))
+            })?
+            .port(config.smtp_port)
+            .credentials(creds)
+            .build();
+
+        mailer
+            .send(email)
+            .await
+            .map_err(|e| {
+                error!("Failed to send email: {}", e);
+                AppError::Internal(format!("Failed to send email: {}", e))
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Substitute `{{name}}` placeholders in a single pass, so text coming from
+/// a value is never expanded again. `values` are HTML-escaped for HTML
+/// templates; `raw` values are already rendered and inserted as they are.
+/// Unknown placeholders are left in place.
+fn render(template: &str, values: &[(&str, &str)], raw: &[(&str, &str)], html: bool) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &after[..end];
+
+        if let Some((_, value)) = values.iter().find(|(n, _)| *n == name) {
+            if html {
+                output.push_str(&escape_html(value));
+            } else {
+                output.push_str(value);
+            }
+        } else if let Some((_, value)) = raw.iter().find(|(n, _)| *n == name) {
+            output.push_str(value);
+        } else {
+            output.push_str(&rest[start..start + 2 + end + 2]);
+        }
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+    output
 }
 
+/// Short single-line preview of a message body
+fn snippet(text_body: Option<&str>, html_body: Option<&str>) -> String {
+    let source = match (text_body, html_body) {
+        (Some(text), _) if !text.trim().is_empty() => text.to_string(),
+        (_, Some(html)) => crate::html::to_text(html),
+        _ => String::new(),
+    };
+
+    let collapsed = source.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > SNIPPET_LENGTH {
+        let truncated: String = collapsed.chars().take(SNIPPET_LENGTH).collect();
+        format!("{}…", truncated.trim_end())
+    } else {
+        collapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_does_not_expand_placeholders_in_values() {
+        let output = render(
+            "<p>{{subject}}</p>{{items}}{{unknown}}",
+            &[("subject", "{{release_url}} & {{items}}"), ("release_url", "https://x")],
+            &[("items", "<li>a</li>")],
+            true,
+        );
+
+        assert_eq!(output, "<p>{{release_url}} &amp; {{items}}</p><li>a</li>{{unknown}}");
+    }
+
+    #[test]
+    fn render_keeps_unclosed_braces() {
+        assert_eq!(render("a {{b", &[("b", "x")], &[], false), "a {{b");
+    }
+}
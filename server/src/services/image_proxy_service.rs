@@ -0,0 +1,118 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use reqwest::{header, Url};
+use sha2::Sha256;
+
+/// Largest image the proxy will pass through
+const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+
+const MAX_REDIRECTS: usize = 3;
+
+pub struct ProxiedImage {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+pub struct ImageProxyService;
+
+impl ImageProxyService {
+    /// Signed proxy URL for a remote image in a forwarded message
+    pub fn url_for(config: &Config, src: &str) -> String {
+        let src = if src.starts_with("//") {
+            format!("https:{}", src)
+        } else {
+            src.to_string()
+        };
+        let mut url = Url::parse(&format!("{}/api/v1/image_proxy", config.api_base_url))
+            .expect("API_BASE_URL must be a valid URL");
+        url.query_pairs_mut()
+            .append_pair("url", &src)
+            .append_pair("sig", &sign(config, &src));
+        url.to_string()
+    }
+
+    /// Fetch an image on behalf of a mail client, so the sender never sees
+    /// the recipient's address or user agent
+    pub async fn fetch(config: &Config, src: &str, signature: &str) -> Result<ProxiedImage> {
+        let signature = URL_SAFE_NO_PAD.decode(signature).unwrap_or_default();
+        if mac(config, src).verify_slice(&signature).is_err() {
+            return Err(AppError::Forbidden("Invalid image signature".to_string()));
+        }
+
+        let mut url = Url::parse(src).map_err(|_| AppError::Validation("Invalid image URL".to_string()))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            if (url.scheme() != "https" && url.scheme() != "http") || crate::http::is_private_host(&url) {
+                return Err(AppError::Forbidden("Image URL is not allowed".to_string()));
+            }
+
+            let mut response = crate::http::client()
+                .get(url.clone())
+                .header(header::ACCEPT, "image/*")
+                .send()
+                .await
+                .map_err(|e| AppError::NotFound(format!("Image could not be loaded: {}", e)))?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|h| h.to_str().ok())
+                    .ok_or_else(|| AppError::NotFound("Image could not be loaded".to_string()))?;
+                url = url
+                    .join(location)
+                    .map_err(|_| AppError::NotFound("Image could not be loaded".to_string()))?;
+                continue;
+            }
+
+            if !response.status().is_success() {
+                return Err(AppError::NotFound(format!("Image server returned {}", response.status())));
+            }
+
+            // SVG can carry script, so it is never served from our origin
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !content_type.starts_with("image/") || content_type.starts_with("image/svg") {
+                return Err(AppError::Validation("Not a supported image".to_string()));
+            }
+
+            if response.content_length().is_some_and(|len| len as usize > MAX_IMAGE_SIZE) {
+                return Err(AppError::Validation("Image is too large".to_string()));
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| AppError::NotFound(format!("Image could not be loaded: {}", e)))?
+            {
+                if body.len() + chunk.len() > MAX_IMAGE_SIZE {
+                    return Err(AppError::Validation("Image is too large".to_string()));
+                }
+                body.extend_from_slice(&chunk);
+            }
+
+            return Ok(ProxiedImage { content_type, body });
+        }
+
+        Err(AppError::NotFound("Too many redirects".to_string()))
+    }
+}
+
+fn mac(config: &Config, src: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.jwt_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"image-proxy\0");
+    mac.update(src.as_bytes());
+    mac
+}
+
+fn sign(config: &Config, src: &str) -> String {
+    URL_SAFE_NO_PAD.encode(mac(config, src).finalize().into_bytes())
+}
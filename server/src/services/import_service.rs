@@ -0,0 +1,531 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::generator::AliasGenerator;
+use crate::models::{Alias, AliasType, ImportFormat, UnmanagedImport};
+use crate::services::alias_service::MAX_LABEL_LENGTH;
+use crate::services::{AliasService, DirectoryService, DomainService, ReservedNameService};
+use crate::validation::{check_alias_policy, normalize_local_part, AliasPolicy};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing::info;
+use uuid::Uuid;
+
+/// Most rows a single import may contain
+const IMPORT_LIMIT: usize = 1000;
+
+/// Outcome of importing one row
+#[derive(Debug, Serialize)]
+pub struct ImportItemResult {
+    /// Position in the upload, starting at 1
+    pub row: usize,
+    pub address: Option<String>,
+    /// `created`, `mapped`, `skipped` or `failed`
+    pub result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias_id: Option<Uuid>,
+    /// Generated address an unmanaged address was mapped to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_address: Option<String>,
+}
+
+/// One alias read from an upload, whatever its layout
+#[derive(Debug)]
+struct ImportRow {
+    address: Option<String>,
+    label: Option<String>,
+    enabled: bool,
+    deleted: bool,
+}
+
+pub struct ImportService;
+
+impl ImportService {
+    /// Recreate aliases exported from another service. Addresses on domains
+    /// the user controls become custom aliases; others are skipped or mapped
+    /// to a generated alias, as chosen by `unmanaged`.
+    pub async fn import(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+        format: ImportFormat,
+        body: &str,
+        unmanaged: UnmanagedImport,
+        dry_run: bool,
+    ) -> Result<Vec<ImportItemResult>> {
+        let rows = match format {
+            ImportFormat::Json => parse_json(body)?,
+            ImportFormat::SimpleLogin | ImportFormat::Addy | ImportFormat::Csv => parse_csv(body)?,
+        };
+
+        if rows.is_empty() {
+            return Err(AppError::Validation("The upload contains no aliases".to_string()));
+        }
+        if rows.len() > IMPORT_LIMIT {
+            return Err(AppError::Validation(format!("At most {} aliases per import", IMPORT_LIMIT)));
+        }
+
+        // Every alias an import creates counts towards the hourly limit; rows
+        // beyond it are skipped and can be imported again later
+        let mut remaining =
+            config.alias_create_limit_per_hour - AliasService::created_last_hour(pool, user_id).await?;
+        if remaining <= 0 && !dry_run {
+            return Err(AppError::RateLimit);
+        }
+
+        let policy = ReservedNameService::policy(pool, config).await?;
+        let strategy = AliasService::generation_strategy(pool, user_id).await?;
+        let generator = AliasGenerator::new(config, strategy);
+
+        let mut results = Vec::with_capacity(rows.len());
+        for (index, row) in rows.into_iter().enumerate() {
+            let mut result = ImportItemResult {
+                row: index + 1,
+                address: row.address.clone(),
+                result: "skipped",
+                reason: None,
+                alias_id: None,
+                new_address: None,
+            };
+
+            let Some((address, local, domain)) = row.address.as_deref().and_then(split_address) else {
+                result.result = "failed";
+                result.reason = Some("invalid_address".to_string());
+                results.push(result);
+                continue;
+            };
+            result.address = Some(address.clone());
+
+            if row.deleted {
+                result.reason = Some("deleted".to_string());
+                results.push(result);
+                continue;
+            }
+
+            let imported: Option<Option<Uuid>> = sqlx::query_scalar(
+                "SELECT alias_id FROM alias_imports WHERE user_id = $1 AND original_address = $2",
+            )
+            .bind(user_id)
+            .bind(&address)
+            .fetch_optional(pool)
+            .await?;
+
+            if let Some(alias_id) = imported {
+                result.reason = Some("already_imported".to_string());
+                result.alias_id = alias_id;
+                results.push(result);
+                continue;
+            }
+
+            let owner: Option<(Uuid, Uuid)> = sqlx::query_as("SELECT id, user_id FROM aliases WHERE address = $1")
+                .bind(&address)
+                .fetch_optional(pool)
+                .await?;
+
+            match owner {
+                Some((alias_id, owner)) if owner == user_id => {
+                    result.reason = Some("exists".to_string());
+                    result.alias_id = Some(alias_id);
+                    results.push(result);
+                    continue;
+                }
+                Some(_) => {
+                    result.result = "failed";
+                    result.reason = Some("taken".to_string());
+                    results.push(result);
+                    continue;
+                }
+                None => {}
+            }
+
+            let controlled = match DomainService::for_new_alias(pool, user_id, Some(domain)).await {
+                Ok(controlled) => Some(controlled),
+                Err(AppError::Validation(_)) => None,
+                Err(e) => return Err(e),
+            };
+
+            if controlled.is_none() && matches!(unmanaged, UnmanagedImport::Skip) {
+                result.reason = Some("domain_not_controlled".to_string());
+                results.push(result);
+                continue;
+            }
+
+            if remaining <= 0 {
+                result.reason = Some("rate_limited".to_string());
+                results.push(result);
+                continue;
+            }
+
+            let outcome = match controlled {
+                Some((domain, domain_id)) => {
+                    result.result = "created";
+                    Self::import_custom(pool, user_id, local, &domain, domain_id, &policy, dry_run).await
+                }
+                None => {
+                    result.result = "mapped";
+                    if dry_run {
+                        Ok(None)
+                    } else {
+                        let (domain, domain_id) = DomainService::for_new_alias(pool, user_id, None).await?;
+                        AliasService::create(
+                            pool,
+                            user_id,
+                            AliasType::Random,
+                            None,
+                            None,
+                            Some(&generator),
+                            &domain,
+                            domain_id,
+                        )
+                        .await
+                        .map(Some)
+                    }
+                }
+            };
+
+            if outcome.is_ok() {
+                remaining -= 1;
+            }
+
+            match outcome {
+                Ok(Some(alias)) => {
+                    // Mapped aliases are labelled with the address they replace
+                    let label = match (result.result, &row.label) {
+                        ("mapped", None) => Some(address.chars().take(MAX_LABEL_LENGTH).collect()),
+                        _ => row.label.clone(),
+                    };
+                    let alias = Self::record(pool, user_id, format, &address, &alias, label, row.enabled).await?;
+
+                    if result.result == "mapped" {
+                        result.new_address = Some(alias.address);
+                    }
+                    result.alias_id = Some(alias.id);
+                }
+                Ok(None) => {}
+                Err(e @ (AppError::Validation(_) | AppError::InvalidField { .. } | AppError::Conflict(_))) => {
+                    result.result = "failed";
+                    result.reason = Some(failure_reason(e));
+                }
+                Err(e) => return Err(e),
+            }
+
+            results.push(result);
+        }
+
+        if !dry_run {
+            let created = results.iter().filter(|r| r.result == "created" || r.result == "mapped").count();
+            info!("Imported {} of {} aliases for user {}", created, results.len(), user_id);
+        }
+
+        Ok(results)
+    }
+
+    /// Check a custom alias against the same rules as the create endpoint and
+    /// create it unless this is a dry run
+    async fn import_custom(
+        pool: &PgPool,
+        user_id: Uuid,
+        local: &str,
+        domain: &str,
+        domain_id: Option<Uuid>,
+        policy: &AliasPolicy,
+        dry_run: bool,
+    ) -> Result<Option<Alias>> {
+        let custom = normalize_local_part(local, "address")?;
+        check_alias_policy(&custom, "address", policy, domain_id.is_none())?;
+        if domain_id.is_none() {
+            DirectoryService::check_custom(pool, user_id, &custom).await?;
+        }
+        AliasService::check_available(pool, user_id, &format!("{}@{}", custom, domain)).await?;
+
+        if dry_run {
+            return Ok(None);
+        }
+
+        let alias = AliasService::create(
+            pool,
+            user_id,
+            AliasType::Custom,
+            Some(custom),
+            None,
+            None,
+            domain,
+            domain_id,
+        )
+        .await?;
+
+        Ok(Some(alias))
+    }
+
+    /// Apply the imported label and state and remember where the alias came from
+    async fn record(
+        pool: &PgPool,
+        user_id: Uuid,
+        format: ImportFormat,
+        original_address: &str,
+        alias: &Alias,
+        label: Option<String>,
+        enabled: bool,
+    ) -> Result<Alias> {
+        let alias = sqlx::query_as::<_, Alias>(
+            r#"
+            UPDATE aliases
+            SET label = $1, status = CASE WHEN $2 THEN status ELSE 'paused' END, updated_at = NOW()
+            WHERE id = $3
+            RETURNING *
+            "#,
+        )
+        .bind(label)
+        .bind(enabled)
+        .bind(alias.id)
+        .fetch_one(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO alias_imports (user_id, alias_id, source, original_address)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, original_address) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(alias.id)
+        .bind(format.as_str())
+        .bind(original_address)
+        .execute(pool)
+        .await?;
+
+        Ok(alias)
+    }
+}
+
+/// Read a CSV upload with a header row. SimpleLogin, addy.io and the alias
+/// export differ only in column names, so all three are read by name.
+fn parse_csv(body: &str) -> Result<Vec<ImportRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(body.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| AppError::Validation(format!("Could not read CSV header: {}", e)))?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| AppError::Validation(format!("Could not read CSV: {}", e)))?;
+        let fields: HashMap<&str, String> = headers
+            .iter()
+            .map(String::as_str)
+            .zip(record.iter().map(str::to_string))
+            .collect();
+        rows.push(import_row(&fields));
+
+        if rows.len() > IMPORT_LIMIT {
+            break;
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Read a JSON upload: an array of alias objects, or an object holding one
+/// under `aliases`
+fn parse_json(body: &str) -> Result<Vec<ImportRow>> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| AppError::Validation(format!("Could not read JSON: {}", e)))?;
+
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut object) => match object.remove("aliases") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err(AppError::Validation("Expected an 'aliases' array".to_string())),
+        },
+        _ => return Err(AppError::Validation("Expected an array of aliases".to_string())),
+    };
+
+    let rows = items
+        .iter()
+        .take(IMPORT_LIMIT + 1)
+        .map(|item| {
+            let fields: HashMap<&str, String> = item
+                .as_object()
+                .map(|object| {
+                    object
+                        .iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                serde_json::Value::String(s) => s.trim().to_string(),
+                                serde_json::Value::Null => String::new(),
+                                other => other.to_string(),
+                            };
+                            (key.as_str(), value)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            import_row(&fields)
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Pick the known columns out of a row. Keys are those of the SimpleLogin
+/// (`alias`, `note`, `enabled`) and addy.io (`email`, `description`, `active`,
+/// `deleted_at`) exports and of the alias export (`address`, `label`, `status`).
+fn import_row(fields: &HashMap<&str, String>) -> ImportRow {
+    let field = |names: &[&str]| {
+        names
+            .iter()
+            .filter_map(|name| fields.get(name))
+            .find(|value| !value.is_empty())
+            .cloned()
+    };
+
+    let status = field(&["status"]).map(|s| s.to_lowercase());
+    let enabled = field(&["enabled", "active"])
+        .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(true)
+        && status.as_deref() != Some("paused");
+    let deleted = field(&["deleted_at"]).is_some() || status.as_deref() == Some("deleted");
+
+    let label = field(&["label", "note", "description"])
+        .map(|label| label.chars().take(MAX_LABEL_LENGTH).collect::<String>().trim().to_string())
+        .filter(|label| !label.is_empty());
+
+    ImportRow {
+        address: field(&["address", "alias", "email"]),
+        label,
+        enabled,
+        deleted,
+    }
+}
+
+/// Lowercased address with its local part and domain
+fn split_address(address: &str) -> Option<(String, &str, &str)> {
+    let (local, domain) = address.trim().rsplit_once('@')?;
+    if local.is_empty() || domain.is_empty() || !domain.contains('.') || address.trim().len() > 254 {
+        return None;
+    }
+
+    Some((address.trim().to_lowercase(), local, domain))
+}
+
+fn failure_reason(e: AppError) -> String {
+    match e {
+        AppError::Validation(message) | AppError::Conflict(message) => message,
+        AppError::InvalidField { message, .. } => message,
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simplelogin_csv_columns_are_mapped() {
+        let rows = parse_csv(
+            "Alias,Note,Enabled,Mailboxes\n\
+             shop@example.com,Online shop,true,me@example.org\n\
+             old@example.com,,false,me@example.org\n",
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].address.as_deref(), Some("shop@example.com"));
+        assert_eq!(rows[0].label.as_deref(), Some("Online shop"));
+        assert!(rows[0].enabled && !rows[0].deleted);
+        assert_eq!(rows[1].label, None);
+        assert!(!rows[1].enabled);
+    }
+
+    #[test]
+    fn addy_csv_columns_are_mapped() {
+        let rows = parse_csv(
+            "id,email,description,active,deleted_at\n\
+             1,news@example.com,Newsletters,1,\n\
+             2,gone@example.com,,0,2024-01-01 10:00:00\n",
+        )
+        .unwrap();
+
+        assert_eq!(rows[0].address.as_deref(), Some("news@example.com"));
+        assert_eq!(rows[0].label.as_deref(), Some("Newsletters"));
+        assert!(rows[0].enabled && !rows[0].deleted);
+        assert!(!rows[1].enabled && rows[1].deleted);
+    }
+
+    #[test]
+    fn csv_byte_order_mark_is_ignored() {
+        let rows = parse_csv("\u{feff}alias,note\nshop@example.com,Shop\n").unwrap();
+        assert_eq!(rows[0].address.as_deref(), Some("shop@example.com"));
+    }
+
+    #[test]
+    fn json_arrays_and_alias_exports_are_read() {
+        let rows = parse_json(r#"[{"alias": "a@example.com", "enabled": false}]"#).unwrap();
+        assert_eq!(rows[0].address.as_deref(), Some("a@example.com"));
+        assert!(!rows[0].enabled);
+
+        let rows = parse_json(
+            r#"{"aliases": [
+                {"address": "b@example.com", "label": "B", "status": "paused"},
+                {"address": "c@example.com", "status": "deleted"},
+                {"email": "d@example.com", "active": true, "deleted_at": null}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(!rows[0].enabled && !rows[0].deleted);
+        assert!(rows[1].enabled && rows[1].deleted);
+        assert!(rows[2].enabled && !rows[2].deleted);
+
+        assert!(parse_json(r#"{"items": []}"#).is_err());
+        assert!(parse_json("\"alias\"").is_err());
+    }
+
+    #[test]
+    fn enabled_values_are_parsed() {
+        let values = [
+            ("true", true),
+            ("TRUE", true),
+            ("1", true),
+            ("yes", true),
+            ("false", false),
+            ("0", false),
+            ("no", false),
+            ("", true),
+        ];
+        for (value, enabled) in values {
+            let fields = HashMap::from([("enabled", value.to_string())]);
+            assert_eq!(import_row(&fields).enabled, enabled, "enabled = {:?}", value);
+        }
+    }
+
+    #[test]
+    fn labels_are_truncated() {
+        let fields = HashMap::from([("note", "x".repeat(MAX_LABEL_LENGTH + 10))]);
+        assert_eq!(import_row(&fields).label.unwrap().len(), MAX_LABEL_LENGTH);
+    }
+
+    #[test]
+    fn reading_stops_past_the_import_limit() {
+        let csv: String = std::iter::once("alias\n".to_string())
+            .chain((0..IMPORT_LIMIT + 50).map(|i| format!("a{}@example.com\n", i)))
+            .collect();
+        assert_eq!(parse_csv(&csv).unwrap().len(), IMPORT_LIMIT + 1);
+
+        let json = serde_json::to_string(
+            &(0..IMPORT_LIMIT + 50)
+                .map(|i| serde_json::json!({ "alias": format!("a{}@example.com", i) }))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(parse_json(&json).unwrap().len(), IMPORT_LIMIT + 1);
+    }
+}
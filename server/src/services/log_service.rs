@@ -0,0 +1,227 @@
+use crate::config::Config;
+use crate::crypto::sender_hash;
+use crate::error::{AppError, Result};
+use crate::models::LogSettings;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Retention periods a user can choose, in days
+const RETENTION_CHOICES: [i32; 4] = [7, 30, 90, 365];
+
+/// Rows deleted or updated per statement by the maintenance jobs
+const BATCH_SIZE: i64 = 5000;
+
+pub struct LogService;
+
+impl LogService {
+    pub async fn settings(pool: &PgPool, user_id: Uuid) -> Result<LogSettings> {
+        let settings = sqlx::query_as::<_, LogSettings>(
+            "SELECT log_retention_days, log_privacy FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(settings)
+    }
+
+    /// Turning privacy mode on also removes senders and subjects from the
+    /// entries already stored
+    pub async fn update_settings(
+        pool: &PgPool,
+        user_id: Uuid,
+        retention_days: Option<i32>,
+        privacy: bool,
+    ) -> Result<LogSettings> {
+        if retention_days.is_some_and(|days| !RETENTION_CHOICES.contains(&days)) {
+            return Err(AppError::Validation(
+                "Retention must be 7, 30, 90 or 365 days, or null to keep logs".to_string(),
+            ));
+        }
+
+        let settings = sqlx::query_as::<_, LogSettings>(
+            r#"
+            UPDATE users
+            SET log_retention_days = $2, log_privacy = $3
+            WHERE id = $1
+            RETURNING log_retention_days, log_privacy
+            "#,
+        )
+        .bind(user_id)
+        .bind(retention_days)
+        .bind(privacy)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if settings.log_privacy {
+            Self::scrub_private(pool, Some(user_id)).await?;
+        }
+
+        Ok(settings)
+    }
+
+    /// Whether new log entries for the user's aliases leave out the sender
+    /// and subject
+    pub async fn privacy_mode(pool: &PgPool, user_id: Uuid) -> Result<bool> {
+        Ok(Self::settings(pool, user_id).await?.log_privacy)
+    }
+
+    /// Delete entries older than their owner's retention period. Held
+    /// messages that can still be released and messages in quarantine are
+    /// kept until they are resolved. Finished webhook deliveries, whose
+    /// payloads may carry senders and subjects, follow the same period.
+    pub async fn prune_expired(pool: &PgPool) -> Result<u64> {
+        sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries d
+            USING webhook_endpoints e, users u
+            WHERE d.endpoint_id = e.id
+            AND e.user_id = u.id
+            AND u.log_retention_days IS NOT NULL
+            AND d.created_at < NOW() - make_interval(days => u.log_retention_days)
+            AND d.status != 'pending'
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        let mut total = 0;
+        loop {
+            let result = sqlx::query(
+                r#"
+                DELETE FROM email_logs
+                WHERE id IN (
+                    SELECT el.id
+                    FROM email_logs el
+                    JOIN aliases a ON el.alias_id = a.id
+                    JOIN users u ON a.user_id = u.id
+                    WHERE u.log_retention_days IS NOT NULL
+                    AND el.created_at < NOW() - make_interval(days => u.log_retention_days)
+                    AND el.status != 'quarantined'
+                    AND el.release_token IS NULL
+                    LIMIT $1
+                )
+                "#,
+            )
+            .bind(BATCH_SIZE)
+            .execute(pool)
+            .await?;
+
+            total += result.rows_affected();
+            if result.rows_affected() < BATCH_SIZE as u64 {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Blank the sender and subject of privacy-mode entries, of one user or
+    /// of all users, and drop their senders from the stats rollups. Held and
+    /// quarantined messages keep them until resolved, since digests and the
+    /// quarantine list show them; entries are only blanked once their sender
+    /// is hashed. Mail events lose theirs once the webhook delivery is
+    /// finished.
+    pub async fn scrub_private(pool: &PgPool, user_id: Option<Uuid>) -> Result<u64> {
+        sqlx::query(
+            r#"
+            UPDATE alias_daily_senders s
+            SET from_email = NULL
+            FROM users u
+            WHERE u.id = s.user_id
+            AND u.log_privacy
+            AND ($1::uuid IS NULL OR u.id = $1)
+            AND s.from_email IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries d
+            SET payload = d.payload #- '{data,from}' #- '{data,subject}'
+            FROM webhook_endpoints e, users u
+            WHERE d.endpoint_id = e.id
+            AND e.user_id = u.id
+            AND u.log_privacy
+            AND ($1::uuid IS NULL OR u.id = $1)
+            AND d.status != 'pending'
+            AND (d.payload->'data' ? 'from' OR d.payload->'data' ? 'subject')
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        let mut total = 0;
+        loop {
+            let result = sqlx::query(
+                r#"
+                UPDATE email_logs
+                SET from_email = '', subject = ''
+                WHERE id IN (
+                    SELECT el.id
+                    FROM email_logs el
+                    JOIN aliases a ON el.alias_id = a.id
+                    JOIN users u ON a.user_id = u.id
+                    WHERE u.log_privacy
+                    AND ($1::uuid IS NULL OR u.id = $1)
+                    AND (el.from_email != '' OR COALESCE(el.subject, '') != '')
+                    AND el.sender_hash IS NOT NULL
+                    AND el.status != 'quarantined'
+                    AND el.release_token IS NULL
+                    LIMIT $2
+                )
+                "#,
+            )
+            .bind(user_id)
+            .bind(BATCH_SIZE)
+            .execute(pool)
+            .await?;
+
+            total += result.rows_affected();
+            if result.rows_affected() < BATCH_SIZE as u64 {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Hash the senders of entries written before sender hashes were stored
+    pub async fn backfill_sender_hashes(pool: &PgPool, config: &Config) -> Result<usize> {
+        let mut total = 0;
+        loop {
+            let rows: Vec<(Uuid, String)> = sqlx::query_as(
+                "SELECT id, from_email FROM email_logs WHERE sender_hash IS NULL AND from_email != '' LIMIT $1",
+            )
+            .bind(BATCH_SIZE)
+            .fetch_all(pool)
+            .await?;
+
+            if rows.is_empty() {
+                return Ok(total);
+            }
+
+            let (ids, hashes): (Vec<Uuid>, Vec<String>) = rows
+                .iter()
+                .map(|(id, sender)| (*id, sender_hash(config, sender)))
+                .unzip();
+
+            sqlx::query(
+                r#"
+                UPDATE email_logs el
+                SET sender_hash = d.hash
+                FROM unnest($1::uuid[], $2::text[]) AS d(id, hash)
+                WHERE el.id = d.id
+                "#,
+            )
+            .bind(&ids)
+            .bind(&hashes)
+            .execute(pool)
+            .await?;
+
+            total += rows.len();
+        }
+    }
+}
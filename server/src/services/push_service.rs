@@ -0,0 +1,235 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::PushSubscription;
+use crate::webpush::{self, PushOutcome, VapidKey};
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Events a subscription can opt into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushEvent {
+    NewSender,
+    Bounce,
+    AliasExpiring,
+}
+
+impl PushEvent {
+    pub const ALL: [PushEvent; 3] = [PushEvent::NewSender, PushEvent::Bounce, PushEvent::AliasExpiring];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushEvent::NewSender => "new_sender",
+            PushEvent::Bounce => "bounce",
+            PushEvent::AliasExpiring => "alias_expiring",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == value)
+    }
+}
+
+/// New or updated subscription as sent by the browser
+pub struct SubscriptionInput {
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub platform: String,
+    pub device_name: Option<String>,
+    pub events: Vec<PushEvent>,
+}
+
+pub struct PushService;
+
+impl PushService {
+    /// Save a subscription. Re-subscribing the same endpoint updates it in
+    /// place; an endpoint registered by another user is refused.
+    pub async fn subscribe(
+        pool: &PgPool,
+        user_id: Uuid,
+        input: SubscriptionInput,
+    ) -> Result<PushSubscription> {
+        let events: Vec<String> = input.events.iter().map(|e| e.as_str().to_string()).collect();
+
+        let subscription = sqlx::query_as::<_, PushSubscription>(
+            r#"
+            INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth, platform, device_name, events)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (endpoint) DO UPDATE
+            SET p256dh = EXCLUDED.p256dh,
+                auth = EXCLUDED.auth,
+                platform = EXCLUDED.platform,
+                device_name = EXCLUDED.device_name,
+                events = EXCLUDED.events
+            WHERE push_subscriptions.user_id = EXCLUDED.user_id
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(&input.endpoint)
+        .bind(&input.p256dh)
+        .bind(&input.auth)
+        .bind(&input.platform)
+        .bind(&input.device_name)
+        .bind(&events)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Push endpoint is registered to another account".to_string()))?;
+
+        Ok(subscription)
+    }
+
+    pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<PushSubscription>> {
+        let subscriptions = sqlx::query_as::<_, PushSubscription>(
+            "SELECT * FROM push_subscriptions WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    pub async fn unsubscribe(pool: &PgPool, subscription_id: Uuid, user_id: Uuid) -> Result<()> {
+        let result = sqlx::query("DELETE FROM push_subscriptions WHERE id = $1 AND user_id = $2")
+            .bind(subscription_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Subscription not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Send a notification to every subscription of the user that opted into
+    /// the event. Does nothing when VAPID is not configured.
+    pub async fn notify(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+        event: PushEvent,
+        title: &str,
+        body: &str,
+        data: serde_json::Value,
+    ) -> Result<()> {
+        let Some(private_key) = &config.vapid_private_key else {
+            return Ok(());
+        };
+        let vapid = VapidKey::from_base64(private_key)?;
+
+        let subscriptions = sqlx::query_as::<_, PushSubscription>(
+            "SELECT * FROM push_subscriptions WHERE user_id = $1 AND $2 = ANY(events)",
+        )
+        .bind(user_id)
+        .bind(event.as_str())
+        .fetch_all(pool)
+        .await?;
+
+        let payload = serde_json::json!({
+            "event": event.as_str(),
+            "title": title,
+            "body": body,
+            "data": data
+        })
+        .to_string();
+
+        for subscription in subscriptions {
+            let encrypted = match webpush::encrypt(payload.as_bytes(), &subscription.p256dh, &subscription.auth) {
+                Ok(body) => body,
+                Err(e) => {
+                    warn!("Skipping push subscription {}: {}", subscription.id, e);
+                    continue;
+                }
+            };
+            let authorization = vapid.authorization(&subscription.endpoint, &config.vapid_subject)?;
+
+            match webpush::send(
+                crate::http::client(),
+                &subscription.endpoint,
+                &authorization,
+                encrypted,
+                config.push_ttl_secs,
+            )
+            .await
+            {
+                PushOutcome::Delivered => {
+                    sqlx::query("UPDATE push_subscriptions SET last_success_at = NOW() WHERE id = $1")
+                        .bind(subscription.id)
+                        .execute(pool)
+                        .await?;
+                }
+                PushOutcome::Gone => {
+                    info!("Removing expired push subscription: {}", subscription.id);
+                    sqlx::query("DELETE FROM push_subscriptions WHERE id = $1")
+                        .bind(subscription.id)
+                        .execute(pool)
+                        .await?;
+                }
+                PushOutcome::Failed(reason) => {
+                    warn!("Push delivery to subscription {} failed: {}", subscription.id, reason);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fire-and-forget variant of [`PushService::notify`] for request handlers
+    pub fn spawn_notify(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+        event: PushEvent,
+        title: String,
+        body: String,
+        data: serde_json::Value,
+    ) {
+        let pool = pool.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = Self::notify(&pool, &config, user_id, event, &title, &body, data).await {
+                error!("Failed to send push notification: {}", e);
+            }
+        });
+    }
+
+    /// Warn owners of aliases that expire within the configured window.
+    /// Each alias is only announced once. Returns the number of aliases.
+    pub async fn notify_expiring_aliases(pool: &PgPool, config: &Config) -> Result<usize> {
+        let window_end = chrono::Utc::now() + chrono::Duration::minutes(config.alias_expiry_warning_minutes);
+
+        let expiring: Vec<(Uuid, Uuid, String, chrono::DateTime<chrono::Utc>)> = sqlx::query_as(
+            r#"
+            UPDATE aliases
+            SET expiry_notified_at = NOW()
+            WHERE status = 'active'
+            AND expiry_notified_at IS NULL
+            AND expires_at > NOW() AND expires_at <= $1
+            RETURNING id, user_id, address, expires_at
+            "#,
+        )
+        .bind(window_end)
+        .fetch_all(pool)
+        .await?;
+
+        let count = expiring.len();
+        for (alias_id, user_id, address, expires_at) in expiring {
+            Self::notify(
+                pool,
+                config,
+                user_id,
+                PushEvent::AliasExpiring,
+                "Alias expiring soon",
+                &format!("{} stops receiving mail at {}", address, expires_at.format("%H:%M UTC")),
+                serde_json::json!({ "alias_id": alias_id, "address": address, "expires_at": expires_at }),
+            )
+            .await?;
+        }
+
+        Ok(count)
+    }
+}
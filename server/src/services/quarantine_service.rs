@@ -0,0 +1,291 @@
+use crate::config::Config;
+use crate::crypto::{sender_hash, MessageCipher};
+use crate::error::{AppError, Result};
+use crate::models::{Alias, QuarantinedMessage};
+use crate::services::{EmailService, TargetService};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::info;
+use uuid::Uuid;
+
+/// Why a message was quarantined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuarantineReason {
+    /// Sent to a paused alias configured to quarantine
+    PausedAlias,
+    /// Failed sender authentication (DMARC)
+    AuthFailure,
+    /// Scored above the user's spam quarantine threshold
+    Spam,
+}
+
+impl QuarantineReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuarantineReason::PausedAlias => "paused_alias",
+            QuarantineReason::AuthFailure => "auth_failure",
+            QuarantineReason::Spam => "spam",
+        }
+    }
+}
+
+/// Message content sealed in the quarantine store
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub body_plain: Option<String>,
+    pub body_html: Option<String>,
+    pub headers: Option<String>,
+    pub message_id: Option<String>,
+}
+
+const SELECT_QUARANTINED: &str = r#"
+    SELECT q.id, q.email_log_id, q.alias_id, a.address AS alias_address, a.tracker_protection,
+           el.from_email, el.subject, q.reason, q.nonce, q.content, q.expires_at, q.created_at
+    FROM quarantined_messages q
+    JOIN email_logs el ON q.email_log_id = el.id
+    JOIN aliases a ON q.alias_id = a.id
+"#;
+
+pub struct QuarantineService;
+
+impl QuarantineService {
+    /// Log the message as quarantined and store its content encrypted.
+    /// `metadata` is merged into the email log entry.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn quarantine(
+        pool: &PgPool,
+        config: &Config,
+        alias: &Alias,
+        from_email: &str,
+        subject: &str,
+        message: &StoredMessage,
+        reason: QuarantineReason,
+        metadata: serde_json::Value,
+    ) -> Result<Uuid> {
+        let plaintext = serde_json::to_vec(message)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize message: {}", e)))?;
+        let (nonce, content) = MessageCipher::from_config(config)?.seal(&plaintext)?;
+        let expires_at =
+            chrono::Utc::now() + chrono::Duration::days(config.quarantine_retention_days);
+
+        let mut tx = pool.begin().await?;
+
+        let (email_log_id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO email_logs (alias_id, from_email, subject, status, metadata, sender_hash)
+            VALUES ($1, $2, $3, 'quarantined', $4::jsonb || $5::jsonb, $6)
+            RETURNING id
+            "#,
+        )
+        .bind(alias.id)
+        .bind(from_email)
+        .bind(subject)
+        .bind(metadata)
+        .bind(serde_json::json!({
+            "reason": reason.as_str(),
+            "message_id": message.message_id
+        }))
+        .bind(sender_hash(config, from_email))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let (id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO quarantined_messages (email_log_id, alias_id, user_id, reason, nonce, content, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+        )
+        .bind(email_log_id)
+        .bind(alias.id)
+        .bind(alias.user_id)
+        .bind(reason.as_str())
+        .bind(nonce)
+        .bind(content)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        info!("Quarantined message to {} ({})", alias.address, reason.as_str());
+        Ok(id)
+    }
+
+    pub async fn list(pool: &PgPool, user_id: Uuid, limit: i64) -> Result<Vec<QuarantinedMessage>> {
+        let messages = sqlx::query_as::<_, QuarantinedMessage>(&format!(
+            "{} WHERE q.user_id = $1 AND q.expires_at > NOW() ORDER BY q.created_at DESC LIMIT $2",
+            SELECT_QUARANTINED
+        ))
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    pub async fn get_by_id(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<QuarantinedMessage> {
+        let message = sqlx::query_as::<_, QuarantinedMessage>(&format!(
+            "{} WHERE q.id = $1 AND q.user_id = $2 AND q.expires_at > NOW()",
+            SELECT_QUARANTINED
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Quarantined message not found".to_string()))?;
+
+        Ok(message)
+    }
+
+    /// Like [`Self::get_by_id`], locking the message until `tx` ends so it
+    /// is released or deleted only once
+    async fn lock(tx: &mut Transaction<'_, Postgres>, id: Uuid, user_id: Uuid) -> Result<QuarantinedMessage> {
+        let message = sqlx::query_as::<_, QuarantinedMessage>(&format!(
+            "{} WHERE q.id = $1 AND q.user_id = $2 AND q.expires_at > NOW() FOR UPDATE OF q",
+            SELECT_QUARANTINED
+        ))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Quarantined message not found".to_string()))?;
+
+        Ok(message)
+    }
+
+    /// Decrypt the stored content of a quarantined message
+    pub fn open(config: &Config, message: &QuarantinedMessage) -> Result<StoredMessage> {
+        let plaintext = MessageCipher::from_config(config)?.open(&message.nonce, &message.content)?;
+
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| AppError::Internal(format!("Failed to read stored message: {}", e)))
+    }
+
+    /// Forward a quarantined message to the user's target address and remove
+    /// it from quarantine. The message stays locked while it is forwarded, so
+    /// a repeated request waits and then finds it gone.
+    pub async fn release(pool: &PgPool, config: &Config, id: Uuid, user_id: Uuid) -> Result<String> {
+        let mut tx = pool.begin().await?;
+        let message = Self::lock(&mut tx, id, user_id).await?;
+        let stored = Self::open(config, &message)?;
+
+        let target = match TargetService::get_current(pool, user_id).await? {
+            Some(t) if t.verified => t,
+            _ => {
+                return Err(AppError::Validation(
+                    "No verified target email to deliver to".to_string(),
+                ))
+            }
+        };
+
+        let (body_html, trackers) =
+            EmailService::sanitize_html(config, message.tracker_protection, stored.body_html.as_deref());
+
+        EmailService::forward_email(
+            config,
+            &message.from_email,
+            &target,
+            &message.subject,
+            stored.body_plain.as_deref(),
+            body_html.as_deref(),
+            Some(&message.from_email),
+            None,
+        )
+        .await?;
+
+        Self::remove(
+            &mut tx,
+            &message,
+            "released",
+            "forwarded",
+            serde_json::json!({ "trackers": trackers }),
+        )
+        .await?;
+        tx.commit().await?;
+
+        info!("Released quarantined message {} to {}", message.id, target.email);
+        Ok(target.email)
+    }
+
+    pub async fn delete(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        let message = Self::lock(&mut tx, id, user_id).await?;
+        Self::remove(&mut tx, &message, "deleted", "rejected", serde_json::json!({})).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Discard quarantined messages past the retention window
+    pub async fn purge_expired(pool: &PgPool) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE email_logs
+            SET status = 'rejected',
+                metadata = COALESCE(metadata, '{}'::jsonb) || '{"quarantine_action": "expired"}'::jsonb
+            WHERE id IN (SELECT email_log_id FROM quarantined_messages WHERE expires_at <= NOW())
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query("DELETE FROM quarantined_messages WHERE expires_at <= NOW()")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Drop the stored copy and record the outcome on the email log
+    async fn remove(
+        tx: &mut Transaction<'_, Postgres>,
+        message: &QuarantinedMessage,
+        action: &str,
+        status: &str,
+        metadata: serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE email_logs
+            SET status = $2::email_status,
+                metadata = COALESCE(metadata, '{}'::jsonb) || $4::jsonb || jsonb_build_object('quarantine_action', $3::text)
+            WHERE id = $1
+            "#,
+        )
+        .bind(message.email_log_id)
+        .bind(status)
+        .bind(action)
+        .bind(metadata)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query("DELETE FROM quarantined_messages WHERE id = $1")
+            .bind(message.id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Whether the `Authentication-Results` headers record a DMARC failure.
+/// `headers` is the provider's JSON list of `[name, value]` pairs.
+pub fn dmarc_failed(headers: Option<&str>) -> bool {
+    let Some(headers) = headers else {
+        return false;
+    };
+    let Ok(pairs) = serde_json::from_str::<Vec<(String, String)>>(headers) else {
+        return false;
+    };
+
+    pairs.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("Authentication-Results")
+            && value.to_ascii_lowercase().contains("dmarc=fail")
+    })
+}
@@ -0,0 +1,70 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{ReservedName, ReservedNameKind};
+use crate::validation::{normalize_local_part, AliasPolicy};
+use sqlx::PgPool;
+
+pub struct ReservedNameService;
+
+impl ReservedNameService {
+    pub async fn list(pool: &PgPool) -> Result<Vec<ReservedName>> {
+        let names = sqlx::query_as::<_, ReservedName>("SELECT * FROM reserved_names ORDER BY kind, name")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(names)
+    }
+
+    pub async fn add(pool: &PgPool, name: &str, kind: ReservedNameKind) -> Result<ReservedName> {
+        let name = normalize_local_part(name, "name")?;
+
+        let created = sqlx::query_as::<_, ReservedName>(
+            "INSERT INTO reserved_names (name, kind) VALUES ($1, $2) RETURNING *",
+        )
+        .bind(&name)
+        .bind(kind)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                AppError::Validation("Name is already listed".to_string())
+            }
+            e => e.into(),
+        })?;
+
+        Ok(created)
+    }
+
+    /// Remove a name from the list. Aliases already using it are unaffected.
+    pub async fn remove(pool: &PgPool, name: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM reserved_names WHERE name = $1")
+            .bind(name.trim().to_lowercase())
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Name is not listed".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// The current policy for new custom aliases
+    pub async fn policy(pool: &PgPool, config: &Config) -> Result<AliasPolicy> {
+        let mut policy = AliasPolicy {
+            min_length: config.alias_min_length,
+            separator: config.subaddress_separator.clone(),
+            reserved: Vec::new(),
+            profanity: Vec::new(),
+        };
+
+        for entry in Self::list(pool).await? {
+            match entry.kind {
+                ReservedNameKind::Reserved => policy.reserved.push(entry.name),
+                ReservedNameKind::Profanity => policy.profanity.push(entry.name),
+            }
+        }
+
+        Ok(policy)
+    }
+}
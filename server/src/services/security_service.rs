@@ -0,0 +1,102 @@
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+pub struct SecurityService;
+
+impl SecurityService {
+    /// Append an entry to the security event log
+    pub async fn log_event(
+        pool: &PgPool,
+        user_id: Option<Uuid>,
+        event_type: &str,
+        email: Option<&str>,
+        ip: Option<IpAddr>,
+        metadata: Option<serde_json::Value>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO security_events (user_id, event_type, email, ip_address, metadata)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(user_id)
+        .bind(event_type)
+        .bind(email)
+        .bind(ip.map(|ip| ip.to_string()))
+        .bind(metadata)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Number of failed logins from an IP address within the window
+    pub async fn recent_ip_failures(pool: &PgPool, ip: IpAddr, window_secs: u64) -> Result<i64> {
+        let since = Utc::now() - chrono::Duration::seconds(window_secs as i64);
+
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM security_events
+            WHERE ip_address = $1 AND event_type = 'login_failed' AND created_at > $2
+            "#,
+        )
+        .bind(ip.to_string())
+        .bind(since)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Count a failed login against the account. Returns the lockout expiry
+    /// when this failure pushed the account over `max_failures`. The counter
+    /// starts over once a previous lockout has expired.
+    pub async fn record_failure(
+        pool: &PgPool,
+        user_id: Uuid,
+        max_failures: i32,
+        lockout_secs: u64,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let locked_until = Utc::now() + chrono::Duration::seconds(lockout_secs as i64);
+
+        let (count, locked): (i32, Option<DateTime<Utc>>) = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET failed_login_count = CASE
+                    WHEN locked_until <= NOW() THEN 1
+                    ELSE failed_login_count + 1
+                END,
+                locked_until = CASE
+                    WHEN (CASE WHEN locked_until <= NOW() THEN 1 ELSE failed_login_count + 1 END) >= $2 THEN $3
+                    WHEN locked_until <= NOW() THEN NULL
+                    ELSE locked_until
+                END
+            WHERE id = $1
+            RETURNING failed_login_count, locked_until
+            "#,
+        )
+        .bind(user_id)
+        .bind(max_failures)
+        .bind(locked_until)
+        .fetch_one(pool)
+        .await?;
+
+        if count == max_failures {
+            Ok(locked)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn reset_failures(pool: &PgPool, user_id: Uuid) -> Result<()> {
+        sqlx::query("UPDATE users SET failed_login_count = 0, locked_until = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::crypto::sender_hash;
+use crate::error::{AppError, Result};
+use crate::models::{Alias, SpamSettings};
+use crate::spam::{HeuristicClassifier, InboundMessage, SpamClassifier, SpamdClassifier};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Highest threshold a user can configure
+const MAX_THRESHOLD: f64 = 100.0;
+
+/// What to do with a scored message
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpamAction {
+    Forward,
+    /// Forward with a subject prefix and an `X-Hush-Spam` header
+    Tag,
+    Quarantine,
+}
+
+impl SpamAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpamAction::Forward => "forward",
+            SpamAction::Tag => "tag",
+            SpamAction::Quarantine => "quarantine",
+        }
+    }
+}
+
+/// Combined result of all classifiers, recorded in the email log metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct SpamVerdict {
+    pub score: f64,
+    pub action: SpamAction,
+    /// Score and matched rules per classifier
+    pub classifiers: serde_json::Value,
+}
+
+impl SpamVerdict {
+    /// Value of the `X-Hush-Spam` header on tagged messages
+    pub fn header(&self) -> String {
+        format!("Yes, score={:.1}", self.score)
+    }
+}
+
+pub struct SpamService;
+
+impl SpamService {
+    /// Classifiers enabled by the configuration, in the order they run
+    pub fn classifiers(config: &Config) -> Vec<Box<dyn SpamClassifier>> {
+        let mut classifiers: Vec<Box<dyn SpamClassifier>> = vec![Box::new(HeuristicClassifier)];
+
+        if let Some(address) = &config.spamd_address {
+            classifiers.push(Box::new(SpamdClassifier {
+                address: address.clone(),
+                timeout: Duration::from_millis(config.spamd_timeout_ms),
+            }));
+        }
+
+        classifiers
+    }
+
+    /// Score a message to one of the user's aliases and decide what to do
+    /// with it. A classifier that fails is skipped so scoring never blocks
+    /// delivery.
+    pub async fn evaluate(
+        pool: &PgPool,
+        config: &Config,
+        alias: &Alias,
+        mut message: InboundMessage<'_>,
+    ) -> Result<SpamVerdict> {
+        let (forwarded, flagged): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*) FILTER (
+                    WHERE alias_id = $2 AND status = 'forwarded'
+                    AND COALESCE(metadata->'spam'->>'action', 'forward') = 'forward'
+                ),
+                COUNT(*) FILTER (WHERE metadata->'spam'->>'action' IN ('tag', 'quarantine'))
+            FROM email_logs
+            WHERE sender_hash = $1
+            AND created_at > NOW() - INTERVAL '90 days'
+            "#,
+        )
+        .bind(sender_hash(config, message.sender))
+        .bind(alias.id)
+        .fetch_one(pool)
+        .await?;
+
+        message.reputation.forwarded = forwarded;
+        message.reputation.flagged = flagged;
+
+        let settings = Self::settings(pool, alias.user_id).await?;
+        Ok(Self::classify(&Self::classifiers(config), &message, &settings).await)
+    }
+
+    /// Sum the scores of `classifiers` and compare the total with the
+    /// user's thresholds
+    async fn classify(
+        classifiers: &[Box<dyn SpamClassifier>],
+        message: &InboundMessage<'_>,
+        settings: &SpamSettings,
+    ) -> SpamVerdict {
+        let mut score = 0.0;
+        let mut results = serde_json::Map::new();
+        for classifier in classifiers {
+            match classifier.score(message).await {
+                Ok(result) => {
+                    score += result.score;
+                    results.insert(
+                        classifier.name().to_string(),
+                        serde_json::to_value(result).unwrap_or_default(),
+                    );
+                }
+                Err(e) => warn!("Spam classifier {} failed: {}", classifier.name(), e),
+            }
+        }
+
+        let action = match settings.spam_quarantine_threshold {
+            Some(threshold) if score >= threshold => SpamAction::Quarantine,
+            _ if score >= settings.spam_tag_threshold => SpamAction::Tag,
+            _ => SpamAction::Forward,
+        };
+
+        SpamVerdict {
+            score,
+            action,
+            classifiers: serde_json::Value::Object(results),
+        }
+    }
+
+    pub async fn settings(pool: &PgPool, user_id: Uuid) -> Result<SpamSettings> {
+        let settings = sqlx::query_as::<_, SpamSettings>(
+            "SELECT spam_tag_threshold, spam_quarantine_threshold FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(settings)
+    }
+
+    pub async fn update_settings(
+        pool: &PgPool,
+        user_id: Uuid,
+        tag_threshold: f64,
+        quarantine_threshold: Option<f64>,
+    ) -> Result<SpamSettings> {
+        let valid = |t: f64| t.is_finite() && (0.0..=MAX_THRESHOLD).contains(&t);
+        if !valid(tag_threshold) || !quarantine_threshold.is_none_or(valid) {
+            return Err(AppError::Validation(format!(
+                "Thresholds must be between 0 and {}",
+                MAX_THRESHOLD
+            )));
+        }
+        if quarantine_threshold.is_some_and(|q| q < tag_threshold) {
+            return Err(AppError::Validation(
+                "Quarantine threshold must not be below the tag threshold".to_string(),
+            ));
+        }
+
+        let settings = sqlx::query_as::<_, SpamSettings>(
+            r#"
+            UPDATE users
+            SET spam_tag_threshold = $2, spam_quarantine_threshold = $3
+            WHERE id = $1
+            RETURNING spam_tag_threshold, spam_quarantine_threshold
+            "#,
+        )
+        .bind(user_id)
+        .bind(tag_threshold)
+        .bind(quarantine_threshold)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spam::{SenderReputation, SpamScore};
+
+    /// Classifier with a fixed score, or failing without one
+    struct Fixed(&'static str, Option<f64>);
+
+    #[axum::async_trait]
+    impl SpamClassifier for Fixed {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        async fn score(&self, _message: &InboundMessage<'_>) -> Result<SpamScore> {
+            match self.1 {
+                Some(score) => Ok(SpamScore { score, rules: vec!["FIXED".to_string()] }),
+                None => Err(AppError::Internal("unavailable".to_string())),
+            }
+        }
+    }
+
+    fn message() -> InboundMessage<'static> {
+        InboundMessage {
+            sender: "a@example.com",
+            recipient: "b@hush.test",
+            subject: "Hello",
+            body_plain: Some("Hi"),
+            body_html: None,
+            message_id: Some("<1@example.com>"),
+            headers: &[],
+            reputation: SenderReputation::default(),
+        }
+    }
+
+    async fn verdict(scores: &[Option<f64>], tag: f64, quarantine: Option<f64>) -> SpamVerdict {
+        let names = ["first", "second", "third"];
+        let classifiers: Vec<Box<dyn SpamClassifier>> = scores
+            .iter()
+            .zip(names)
+            .map(|(score, name)| Box::new(Fixed(name, *score)) as Box<dyn SpamClassifier>)
+            .collect();
+        let settings = SpamSettings {
+            spam_tag_threshold: tag,
+            spam_quarantine_threshold: quarantine,
+        };
+        SpamService::classify(&classifiers, &message(), &settings).await
+    }
+
+    #[tokio::test]
+    async fn compares_the_summed_score_with_the_thresholds() {
+        assert_eq!(verdict(&[Some(2.0)], 5.0, Some(10.0)).await.action, SpamAction::Forward);
+        assert_eq!(verdict(&[Some(2.0), Some(3.0)], 5.0, Some(10.0)).await.action, SpamAction::Tag);
+        assert_eq!(verdict(&[Some(6.0), Some(4.0)], 5.0, Some(10.0)).await.action, SpamAction::Quarantine);
+        assert_eq!(verdict(&[Some(50.0)], 5.0, None).await.action, SpamAction::Tag);
+        assert_eq!(verdict(&[Some(-2.0), Some(6.0)], 5.0, None).await.action, SpamAction::Forward);
+
+        // A quarantine threshold below the tag threshold wins
+        assert_eq!(verdict(&[Some(3.0)], 5.0, Some(2.0)).await.action, SpamAction::Quarantine);
+    }
+
+    #[tokio::test]
+    async fn records_each_classifier_and_skips_failures() {
+        let verdict = verdict(&[Some(4.0), None, Some(1.5)], 5.0, None).await;
+        assert_eq!(verdict.score, 5.5);
+        assert_eq!(verdict.action, SpamAction::Tag);
+        assert_eq!(verdict.classifiers["first"]["score"], 4.0);
+        assert_eq!(verdict.classifiers["third"]["rules"][0], "FIXED");
+        assert!(verdict.classifiers.get("second").is_none());
+        assert_eq!(verdict.header(), "Yes, score=5.5");
+    }
+}
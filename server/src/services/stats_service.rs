@@ -0,0 +1,314 @@
+use crate::error::{AppError, Result};
+use crate::models::StatsBucket;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Log statuses reported in every bucket, even when zero
+const STATUSES: [&str; 6] = ["forwarded", "rejected", "quarantined", "held", "bounced", "pending"];
+
+/// Senders and aliases listed in the top lists
+const TOP_LIMIT: i64 = 10;
+
+/// Longest window a stats request may cover
+const MAX_STATS_DAYS: i64 = 366;
+
+/// Usage of one alias or a whole account over a window of days
+#[derive(Debug, Serialize)]
+pub struct UsageStats {
+    pub bucket: StatsBucket,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub buckets: Vec<BucketCounts>,
+    pub totals: BTreeMap<String, i64>,
+    pub forwarded: i64,
+    /// Rejected or quarantined
+    pub blocked: i64,
+    /// Share of blocked messages among forwarded and blocked ones
+    pub blocked_ratio: Option<f64>,
+    pub top_senders: Vec<SenderCount>,
+    /// Latest message ever received, regardless of the window
+    pub last_received_at: Option<DateTime<Utc>>,
+    /// Messages logged after this time are not counted yet
+    pub rolled_up_at: Option<DateTime<Utc>>,
+}
+
+/// Message counts by status for the bucket starting at `start`
+#[derive(Debug, Serialize)]
+pub struct BucketCounts {
+    pub start: NaiveDate,
+    #[serde(flatten)]
+    pub counts: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SenderCount {
+    pub sender_hash: String,
+    /// Unknown for accounts in privacy mode and for senders only logged in it
+    pub from_email: Option<String>,
+    pub messages: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AliasCount {
+    pub alias_id: Uuid,
+    pub address: String,
+    pub messages: i64,
+}
+
+pub struct StatsService;
+
+impl StatsService {
+    /// Rebuild the daily rollups of every alias and day with log entries
+    /// added or changed since the last run. Days that reach back past the
+    /// owner's log retention may already have lost entries, so they keep the
+    /// counts they had. Returns the number of alias days rebuilt.
+    pub async fn rollup(pool: &PgPool) -> Result<usize> {
+        let mut tx = pool.begin().await?;
+
+        // The row lock keeps concurrent runs from interleaving
+        sqlx::query("INSERT INTO stats_rollup_state (rolled_up_at) VALUES ('epoch') ON CONFLICT DO NOTHING")
+            .execute(&mut *tx)
+            .await?;
+        let since: DateTime<Utc> = sqlx::query_scalar("SELECT rolled_up_at FROM stats_rollup_state FOR UPDATE")
+            .fetch_one(&mut *tx)
+            .await?;
+        let started: DateTime<Utc> = sqlx::query_scalar("SELECT NOW()").fetch_one(&mut *tx).await?;
+
+        // Entries written by transactions still open at the last run carry
+        // an earlier timestamp, so look back a little further
+        let dirty: Vec<(Uuid, NaiveDate)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT l.alias_id, (l.created_at AT TIME ZONE 'UTC')::date
+            FROM email_logs l
+            JOIN aliases a ON a.id = l.alias_id
+            JOIN users u ON u.id = a.user_id
+            WHERE l.updated_at > $1 - INTERVAL '5 minutes'
+            AND (
+                u.log_retention_days IS NULL
+                OR date_trunc('day', l.created_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+                    >= NOW() - make_interval(days => u.log_retention_days)
+            )
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if !dirty.is_empty() {
+            let (alias_ids, days): (Vec<Uuid>, Vec<NaiveDate>) = dirty.iter().cloned().unzip();
+
+            sqlx::query(
+                r#"
+                DELETE FROM alias_daily_stats s
+                USING unnest($1::uuid[], $2::date[]) AS d(alias_id, day)
+                WHERE s.alias_id = d.alias_id AND s.day = d.day
+                "#,
+            )
+            .bind(&alias_ids)
+            .bind(&days)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                DELETE FROM alias_daily_senders s
+                USING unnest($1::uuid[], $2::date[]) AS d(alias_id, day)
+                WHERE s.alias_id = d.alias_id AND s.day = d.day
+                "#,
+            )
+            .bind(&alias_ids)
+            .bind(&days)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO alias_daily_stats (alias_id, user_id, day, status, messages, last_received_at)
+                SELECT l.alias_id, a.user_id, d.day, l.status, COUNT(*), MAX(l.created_at)
+                FROM unnest($1::uuid[], $2::date[]) AS d(alias_id, day)
+                JOIN email_logs l ON l.alias_id = d.alias_id
+                    AND l.created_at >= d.day::timestamp AT TIME ZONE 'UTC'
+                    AND l.created_at < (d.day + 1)::timestamp AT TIME ZONE 'UTC'
+                JOIN aliases a ON a.id = l.alias_id
+                GROUP BY l.alias_id, a.user_id, d.day, l.status
+                "#,
+            )
+            .bind(&alias_ids)
+            .bind(&days)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO alias_daily_senders (alias_id, user_id, day, sender_hash, from_email, messages)
+                SELECT l.alias_id, a.user_id, d.day, l.sender_hash,
+                    CASE WHEN u.log_privacy THEN NULL ELSE NULLIF(MAX(lower(l.from_email)), '') END,
+                    COUNT(*)
+                FROM unnest($1::uuid[], $2::date[]) AS d(alias_id, day)
+                JOIN email_logs l ON l.alias_id = d.alias_id
+                    AND l.created_at >= d.day::timestamp AT TIME ZONE 'UTC'
+                    AND l.created_at < (d.day + 1)::timestamp AT TIME ZONE 'UTC'
+                    AND l.sender_hash IS NOT NULL
+                JOIN aliases a ON a.id = l.alias_id
+                JOIN users u ON u.id = a.user_id
+                GROUP BY l.alias_id, a.user_id, u.log_privacy, d.day, l.sender_hash
+                "#,
+            )
+            .bind(&alias_ids)
+            .bind(&days)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query("UPDATE stats_rollup_state SET rolled_up_at = $1")
+            .bind(started)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(dirty.len())
+    }
+
+    /// Usage over the last `days` days, of one alias or, without `alias_id`,
+    /// of all the user's aliases. Days are UTC.
+    pub async fn usage(
+        pool: &PgPool,
+        user_id: Uuid,
+        alias_id: Option<Uuid>,
+        days: i64,
+        bucket: StatsBucket,
+    ) -> Result<UsageStats> {
+        if !(1..=MAX_STATS_DAYS).contains(&days) {
+            return Err(AppError::Validation(format!("Days must be between 1 and {}", MAX_STATS_DAYS)));
+        }
+
+        let to = Utc::now().date_naive();
+        let from = to - chrono::Duration::days(days - 1);
+
+        let starts: Vec<NaiveDate> = sqlx::query_scalar(
+            "SELECT generate_series(date_trunc($1, $2::timestamp), $3::timestamp, ('1 ' || $1)::interval)::date",
+        )
+        .bind(bucket.as_str())
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await?;
+
+        let rows: Vec<(NaiveDate, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT date_trunc($3, day::timestamp)::date, status::text, SUM(messages)::bigint
+            FROM alias_daily_stats
+            WHERE user_id = $1 AND ($2::uuid IS NULL OR alias_id = $2) AND day >= $4
+            GROUP BY 1, 2
+            "#,
+        )
+        .bind(user_id)
+        .bind(alias_id)
+        .bind(bucket.as_str())
+        .bind(from)
+        .fetch_all(pool)
+        .await?;
+
+        let empty: BTreeMap<String, i64> = STATUSES.iter().map(|s| (s.to_string(), 0)).collect();
+        let mut buckets: Vec<BucketCounts> = starts
+            .into_iter()
+            .map(|start| BucketCounts { start, counts: empty.clone() })
+            .collect();
+        let mut totals = empty;
+
+        for (start, status, messages) in rows {
+            if let Some(bucket) = buckets.iter_mut().find(|b| b.start == start) {
+                *bucket.counts.entry(status.clone()).or_insert(0) += messages;
+            }
+            *totals.entry(status).or_insert(0) += messages;
+        }
+
+        let forwarded = totals["forwarded"];
+        let blocked = totals["rejected"] + totals["quarantined"];
+        let blocked_ratio = (forwarded + blocked > 0).then(|| blocked as f64 / (forwarded + blocked) as f64);
+
+        let top_senders = sqlx::query_as::<_, SenderCount>(
+            r#"
+            SELECT sender_hash, MAX(from_email) AS from_email, SUM(messages)::bigint AS messages
+            FROM alias_daily_senders
+            WHERE user_id = $1 AND ($2::uuid IS NULL OR alias_id = $2) AND day >= $3
+            GROUP BY sender_hash
+            ORDER BY messages DESC, sender_hash
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(alias_id)
+        .bind(from)
+        .bind(TOP_LIMIT)
+        .fetch_all(pool)
+        .await?;
+
+        let last_received_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+            "SELECT MAX(last_received_at) FROM alias_daily_stats WHERE user_id = $1 AND ($2::uuid IS NULL OR alias_id = $2)",
+        )
+        .bind(user_id)
+        .bind(alias_id)
+        .fetch_one(pool)
+        .await?;
+
+        let rolled_up_at: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT rolled_up_at FROM stats_rollup_state")
+                .fetch_optional(pool)
+                .await?;
+
+        Ok(UsageStats {
+            bucket,
+            from,
+            to,
+            buckets,
+            totals,
+            forwarded,
+            blocked,
+            blocked_ratio,
+            top_senders,
+            last_received_at,
+            rolled_up_at,
+        })
+    }
+
+    /// The user's aliases that received the most mail in the last `days` days
+    pub async fn top_aliases(pool: &PgPool, user_id: Uuid, days: i64) -> Result<Vec<AliasCount>> {
+        let from = Utc::now().date_naive() - chrono::Duration::days(days - 1);
+
+        let aliases = sqlx::query_as::<_, AliasCount>(
+            r#"
+            SELECT s.alias_id, a.address, SUM(s.messages)::bigint AS messages
+            FROM alias_daily_stats s
+            JOIN aliases a ON a.id = s.alias_id
+            WHERE s.user_id = $1 AND s.day >= $2
+            GROUP BY s.alias_id, a.address
+            ORDER BY messages DESC, a.address
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(from)
+        .bind(TOP_LIMIT)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(aliases)
+    }
+
+    /// Number of the user's aliases in each status
+    pub async fn alias_counts(pool: &PgPool, user_id: Uuid) -> Result<BTreeMap<String, i64>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT status::text, COUNT(*) FROM aliases WHERE user_id = $1 GROUP BY status",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+}
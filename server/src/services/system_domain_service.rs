@@ -0,0 +1,226 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::SystemDomain;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::RsaPrivateKey;
+use sqlx::PgPool;
+use tracing::info;
+
+const DKIM_KEY_BITS: usize = 2048;
+
+pub struct SystemDomainService;
+
+impl SystemDomainService {
+    /// Seed the list with `HUSH_DOMAIN` as the default on first start
+    pub async fn ensure_default(pool: &PgPool, config: &Config) -> Result<()> {
+        let existing = sqlx::query("SELECT id FROM system_domains LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+
+        if existing.is_none() {
+            Self::create(pool, &config.hush_domain, true, true, None, None).await?;
+            info!("Registered {} as the default system domain", config.hush_domain);
+        }
+
+        Ok(())
+    }
+
+    pub async fn list(pool: &PgPool) -> Result<Vec<SystemDomain>> {
+        let domains = sqlx::query_as::<_, SystemDomain>(
+            "SELECT * FROM system_domains ORDER BY is_default DESC, domain",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(domains)
+    }
+
+    pub async fn list_enabled(pool: &PgPool) -> Result<Vec<SystemDomain>> {
+        let domains = sqlx::query_as::<_, SystemDomain>(
+            "SELECT * FROM system_domains WHERE enabled = true ORDER BY is_default DESC, domain",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(domains)
+    }
+
+    /// Domain used for new aliases when the user does not choose one
+    pub async fn default_domain(pool: &PgPool) -> Result<SystemDomain> {
+        let domain = sqlx::query_as::<_, SystemDomain>(
+            "SELECT * FROM system_domains WHERE is_default = true AND enabled = true",
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Internal("No default system domain is configured".to_string()))?;
+
+        Ok(domain)
+    }
+
+    pub async fn find(pool: &PgPool, domain: &str) -> Result<Option<SystemDomain>> {
+        let domain = sqlx::query_as::<_, SystemDomain>("SELECT * FROM system_domains WHERE domain = $1")
+            .bind(domain)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(domain)
+    }
+
+    pub async fn create(
+        pool: &PgPool,
+        domain: &str,
+        enabled: bool,
+        is_default: bool,
+        dkim_selector: Option<&str>,
+        dkim_private_key: Option<&str>,
+    ) -> Result<SystemDomain> {
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+        if domain.is_empty() || !domain.contains('.') {
+            return Err(AppError::Validation("Invalid domain name".to_string()));
+        }
+        if is_default && !enabled {
+            return Err(AppError::Validation("The default domain must be enabled".to_string()));
+        }
+
+        let selector = dkim_selector.map(str::trim).unwrap_or("hush");
+        if selector.is_empty() || !selector.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(AppError::Validation("Invalid DKIM selector".to_string()));
+        }
+
+        let private_key = match dkim_private_key {
+            Some(pem) => {
+                parse_private_key(pem)?;
+                pem.trim().to_string()
+            }
+            None => generate_private_key().await?,
+        };
+
+        if Self::find(pool, &domain).await?.is_some() {
+            return Err(AppError::Validation("System domain already exists".to_string()));
+        }
+
+        let taken = sqlx::query("SELECT id FROM domains WHERE domain = $1 AND verified_at IS NOT NULL")
+            .bind(&domain)
+            .fetch_optional(pool)
+            .await?;
+
+        if taken.is_some() {
+            return Err(AppError::Validation("Domain is verified by a user account".to_string()));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        if is_default {
+            sqlx::query("UPDATE system_domains SET is_default = false, updated_at = NOW() WHERE is_default")
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let created = sqlx::query_as::<_, SystemDomain>(
+            r#"
+            INSERT INTO system_domains (domain, enabled, is_default, dkim_selector, dkim_private_key)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(&domain)
+        .bind(enabled)
+        .bind(is_default)
+        .bind(selector)
+        .bind(&private_key)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    /// Enable or disable a domain, or make it the default. Disabling keeps
+    /// existing aliases working but prevents new ones.
+    pub async fn update(
+        pool: &PgPool,
+        domain: &str,
+        enabled: Option<bool>,
+        is_default: Option<bool>,
+    ) -> Result<SystemDomain> {
+        let current = Self::find(pool, domain)
+            .await?
+            .ok_or_else(|| AppError::NotFound("System domain not found".to_string()))?;
+
+        if is_default == Some(false) && current.is_default {
+            return Err(AppError::Validation(
+                "Make another domain the default instead".to_string(),
+            ));
+        }
+
+        let enabled = enabled.unwrap_or(current.enabled);
+        let is_default = is_default.unwrap_or(current.is_default);
+        if is_default && !enabled {
+            return Err(AppError::Validation("The default domain must be enabled".to_string()));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        if is_default && !current.is_default {
+            sqlx::query("UPDATE system_domains SET is_default = false, updated_at = NOW() WHERE is_default")
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let updated = sqlx::query_as::<_, SystemDomain>(
+            r#"
+            UPDATE system_domains
+            SET enabled = $1, is_default = $2, updated_at = NOW()
+            WHERE id = $3
+            RETURNING *
+            "#,
+        )
+        .bind(enabled)
+        .bind(is_default)
+        .bind(current.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(updated)
+    }
+
+    /// DNS TXT record publishing the domain's DKIM public key, as
+    /// `(name, value)`
+    pub fn dkim_record(domain: &SystemDomain) -> Result<(String, String)> {
+        let key = parse_private_key(&domain.dkim_private_key)?;
+        let public = key
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|e| AppError::Internal(format!("Failed to encode DKIM key: {}", e)))?;
+
+        Ok((
+            format!("{}._domainkey.{}", domain.dkim_selector, domain.domain),
+            format!("v=DKIM1; k=rsa; p={}", STANDARD.encode(public.as_bytes())),
+        ))
+    }
+}
+
+/// Accept PKCS#8 (`BEGIN PRIVATE KEY`) or PKCS#1 (`BEGIN RSA PRIVATE KEY`) PEM
+fn parse_private_key(pem: &str) -> Result<RsaPrivateKey> {
+    RsaPrivateKey::from_pkcs8_pem(pem.trim())
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem.trim()))
+        .map_err(|_| AppError::Validation("Invalid DKIM private key".to_string()))
+}
+
+async fn generate_private_key() -> Result<String> {
+    tokio::task::spawn_blocking(|| {
+        let key = RsaPrivateKey::new(&mut rand::rngs::OsRng, DKIM_KEY_BITS)
+            .map_err(|e| AppError::Internal(format!("Failed to generate DKIM key: {}", e)))?;
+        let pem = key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|e| AppError::Internal(format!("Failed to encode DKIM key: {}", e)))?;
+        Ok(pem.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("DKIM key generation failed: {}", e)))?
+}
@@ -1,5 +1,6 @@
 use crate::error::{AppError, Result};
 use crate::models::TargetEmail;
+use crate::pgp;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -33,7 +34,9 @@ impl TargetService {
             sqlx::query_as::<_, TargetEmail>(
                 r#"
                 UPDATE target_emails
-                SET email = $1, verified = false, verification_token = $2, verification_expires_at = $3, updated_at = NOW()
+                SET email = $1, verified = false, verification_token = $2, verification_expires_at = $3, updated_at = NOW(),
+                    pgp_public_key = CASE WHEN email = $1 THEN pgp_public_key END,
+                    pgp_fingerprint = CASE WHEN email = $1 THEN pgp_fingerprint END
                 WHERE id = $4
                 RETURNING *
                 "#,
@@ -80,5 +83,51 @@ impl TargetService {
 
         Ok(target)
     }
-}
 
+    /// Validate and store the OpenPGP key used to encrypt mail forwarded to
+    /// the user's target address
+    pub async fn set_pgp_key(
+        pool: &PgPool,
+        user_id: Uuid,
+        armored: &str,
+    ) -> Result<(TargetEmail, pgp::PublicKey)> {
+        let key = pgp::PublicKey::parse(armored)?;
+
+        let target = sqlx::query_as::<_, TargetEmail>(
+            r#"
+            UPDATE target_emails
+            SET pgp_public_key = $1, pgp_fingerprint = $2, updated_at = NOW()
+            WHERE id = (SELECT id FROM target_emails WHERE user_id = $3 ORDER BY created_at DESC LIMIT 1)
+            RETURNING *
+            "#,
+        )
+        .bind(armored.trim())
+        .bind(&key.fingerprint)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No target email set".to_string()))?;
+
+        Ok((target, key))
+    }
+
+    pub async fn remove_pgp_key(pool: &PgPool, user_id: Uuid) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE target_emails
+            SET pgp_public_key = NULL, pgp_fingerprint = NULL, updated_at = NOW()
+            WHERE id = (SELECT id FROM target_emails WHERE user_id = $1 ORDER BY created_at DESC LIMIT 1)
+            AND pgp_public_key IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("No OpenPGP key set".to_string()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{WebhookDelivery, WebhookEndpoint};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+use sqlx::{PgExecutor, PgPool};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Event types users can subscribe webhook endpoints to
+pub const WEBHOOK_EVENTS: [&str; 6] = [
+    "email.forwarded",
+    "email.rejected",
+    "email.bounced",
+    "alias.created",
+    "alias.expired",
+    "alias.restored",
+];
+
+/// Sent by the "send test event" endpoint regardless of the event filter
+pub const TEST_EVENT: &str = "webhook.test";
+
+/// Deliveries attempted per run of the delivery job
+const BATCH_SIZE: i64 = 20;
+
+/// How long a claimed delivery is left to its worker before another run may
+/// retry it. Must comfortably exceed a batch of request timeouts.
+const CLAIM_LEASE_SECS: u64 = 600;
+
+pub struct WebhookService;
+
+impl WebhookService {
+    pub async fn create(
+        pool: &PgPool,
+        config: &Config,
+        user_id: Uuid,
+        url: &str,
+        events: Vec<String>,
+        description: Option<String>,
+    ) -> Result<WebhookEndpoint> {
+        validate_url(url, config.webhook_allow_private_targets)?;
+
+        if events.is_empty() {
+            return Err(AppError::Validation("At least one event is required".to_string()));
+        }
+        if let Some(unknown) = events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+            return Err(AppError::Validation(format!("Unknown event: {}", unknown)));
+        }
+
+        let secret_bytes: [u8; 32] = rand::thread_rng().gen();
+        let secret = format!("whsec_{}", to_hex(&secret_bytes));
+
+        let endpoint = sqlx::query_as::<_, WebhookEndpoint>(
+            r#"
+            INSERT INTO webhook_endpoints (user_id, url, secret, events, description)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(url)
+        .bind(&secret)
+        .bind(&events)
+        .bind(description)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(endpoint)
+    }
+
+    pub async fn list(pool: &PgPool, user_id: Uuid) -> Result<Vec<WebhookEndpoint>> {
+        let endpoints = sqlx::query_as::<_, WebhookEndpoint>(
+            "SELECT * FROM webhook_endpoints WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(endpoints)
+    }
+
+    pub async fn get_by_id(pool: &PgPool, endpoint_id: Uuid, user_id: Uuid) -> Result<WebhookEndpoint> {
+        let endpoint = sqlx::query_as::<_, WebhookEndpoint>(
+            "SELECT * FROM webhook_endpoints WHERE id = $1 AND user_id = $2",
+        )
+        .bind(endpoint_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))?;
+
+        Ok(endpoint)
+    }
+
+    pub async fn delete(pool: &PgPool, endpoint_id: Uuid, user_id: Uuid) -> Result<()> {
+        let result = sqlx::query("DELETE FROM webhook_endpoints WHERE id = $1 AND user_id = $2")
+            .bind(endpoint_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Webhook not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn deliveries(
+        pool: &PgPool,
+        endpoint_id: Uuid,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        // Verify endpoint belongs to user
+        let _endpoint = Self::get_by_id(pool, endpoint_id, user_id).await?;
+
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE endpoint_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(endpoint_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Queue an event for every enabled endpoint of the user subscribed to it
+    pub async fn emit<'e>(
+        executor: impl PgExecutor<'e>,
+        user_id: Uuid,
+        event_type: &str,
+        data: serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (endpoint_id, event_type, payload)
+            SELECT id, $2, jsonb_build_object('id', uuid_generate_v4(), 'type', $2::text, 'created_at', NOW(), 'data', $3::jsonb)
+            FROM webhook_endpoints
+            WHERE user_id = $1 AND enabled = true AND $2 = ANY(events)
+            "#,
+        )
+        .bind(user_id)
+        .bind(event_type)
+        .bind(data)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Queue a test event for a single endpoint
+    pub async fn send_test(pool: &PgPool, endpoint_id: Uuid, user_id: Uuid) -> Result<WebhookDelivery> {
+        let endpoint = Self::get_by_id(pool, endpoint_id, user_id).await?;
+
+        let payload = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "type": TEST_EVENT,
+            "created_at": Utc::now(),
+            "data": { "message": "This is a test event from Hush" }
+        });
+
+        let delivery = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            INSERT INTO webhook_deliveries (endpoint_id, event_type, payload)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+        )
+        .bind(endpoint.id)
+        .bind(TEST_EVENT)
+        .bind(payload)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Attempt all due deliveries. Failures are retried with exponential
+    /// backoff until `webhook_max_attempts`. Returns the number attempted.
+    pub async fn deliver_due(pool: &PgPool, config: &Config) -> Result<usize> {
+        // Claim the batch by pushing its next attempt past the lease, so no
+        // lock or transaction is held while the requests are made. Claims of
+        // a worker that dies mid-batch are picked up once the lease ends.
+        let due: Vec<(Uuid, i32, String, serde_json::Value, String, String)> = sqlx::query_as(
+            r#"
+            WITH claimed AS (
+                UPDATE webhook_deliveries
+                SET next_attempt_at = NOW() + make_interval(secs => $1)
+                WHERE id IN (
+                    SELECT id FROM webhook_deliveries
+                    WHERE status = 'pending' AND next_attempt_at <= NOW()
+                    ORDER BY next_attempt_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, attempts, event_type, payload, endpoint_id
+            )
+            SELECT c.id, c.attempts, c.event_type, c.payload, e.url, e.secret
+            FROM claimed c
+            JOIN webhook_endpoints e ON c.endpoint_id = e.id
+            "#,
+        )
+        .bind(CLAIM_LEASE_SECS as f64)
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        let count = due.len();
+        for (delivery_id, attempts, event_type, payload, url, secret) in due {
+            let body = payload.to_string();
+            let timestamp = Utc::now().timestamp();
+            let signature = sign(&secret, timestamp, &body);
+
+            let client = if config.webhook_allow_private_targets {
+                crate::http::internal_client()
+            } else {
+                crate::http::client()
+            };
+            let response = client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("User-Agent", "Hush-Webhooks/1.0")
+                .header("X-Hush-Event", &event_type)
+                .header("X-Hush-Delivery", delivery_id.to_string())
+                .header("X-Hush-Signature", format!("t={},v1={}", timestamp, signature))
+                .body(body)
+                .send()
+                .await;
+
+            let (status_code, error) = match response {
+                Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i32), None),
+                Ok(resp) => (
+                    Some(resp.status().as_u16() as i32),
+                    Some(format!("endpoint returned {}", resp.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            let attempts = attempts + 1;
+            match error {
+                None => {
+                    sqlx::query(
+                        r#"
+                        UPDATE webhook_deliveries
+                        SET status = 'succeeded', attempts = $2, last_status_code = $3, last_error = NULL, delivered_at = NOW()
+                        WHERE id = $1
+                        "#,
+                    )
+                    .bind(delivery_id)
+                    .bind(attempts)
+                    .bind(status_code)
+                    .execute(pool)
+                    .await?;
+                }
+                Some(error) => {
+                    let exhausted = attempts >= config.webhook_max_attempts;
+                    if exhausted {
+                        warn!("Webhook delivery {} failed permanently: {}", delivery_id, error);
+                    } else {
+                        info!("Webhook delivery {} failed (attempt {}): {}", delivery_id, attempts, error);
+                    }
+
+                    sqlx::query(
+                        r#"
+                        UPDATE webhook_deliveries
+                        SET status = $2, attempts = $3, last_status_code = $4, last_error = $5, next_attempt_at = $6
+                        WHERE id = $1
+                        "#,
+                    )
+                    .bind(delivery_id)
+                    .bind(if exhausted { "failed" } else { "pending" })
+                    .bind(attempts)
+                    .bind(status_code)
+                    .bind(error)
+                    .bind(Utc::now() + retry_delay(attempts))
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Emit `alias.expired` once for aliases whose expiry has passed. The
+    /// aliases are marked in the same transaction that queues their events,
+    /// so a failure leaves them to the next run.
+    pub async fn emit_expired_aliases(pool: &PgPool) -> Result<usize> {
+        let mut tx = pool.begin().await?;
+        let expired: Vec<(Uuid, Uuid, String, chrono::DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            UPDATE aliases
+            SET expired_event_at = NOW()
+            WHERE expired_event_at IS NULL
+            AND status != 'deleted'
+            AND expires_at IS NOT NULL AND expires_at <= NOW()
+            RETURNING id, user_id, address, expires_at
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let count = expired.len();
+        for (alias_id, user_id, address, expires_at) in expired {
+            Self::emit(
+                &mut *tx,
+                user_id,
+                "alias.expired",
+                serde_json::json!({ "alias_id": alias_id, "address": address, "expires_at": expires_at }),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(count)
+    }
+}
+
+/// `v1` signature: hex HMAC-SHA256 over "{timestamp}.{body}"
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+/// 30s, 1m, 2m, 4m, ... capped at 6 hours
+fn retry_delay(attempts: i32) -> chrono::Duration {
+    let exponent = (attempts - 1).clamp(0, 10) as u32;
+    chrono::Duration::seconds((30i64 << exponent).min(6 * 3600))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Only allow HTTP(S) URLs and, unless configured otherwise, refuse targets
+/// that point at the server's own network
+fn validate_url(url: &str, allow_private: bool) -> Result<()> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| AppError::Validation("Invalid webhook URL".to_string()))?;
+
+    if parsed.scheme() != "https" && parsed.scheme() != "http" {
+        return Err(AppError::Validation("Webhook URL must use http or https".to_string()));
+    }
+
+    if !allow_private && crate::http::is_private_host(&parsed) {
+        return Err(AppError::Validation("Webhook URL must be publicly reachable".to_string()));
+    }
+
+    Ok(())
+}
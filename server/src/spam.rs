@@ -0,0 +1,400 @@
+//! Inbound spam scoring. Classifiers implement [`SpamClassifier`] and their
+//! scores are summed; the built-in heuristics need no external services and
+//! a spamd-protocol daemon (SpamAssassin or rspamd) can be added on top.
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::{AppError, Result};
+
+/// What the scorer sees of an inbound message
+pub struct InboundMessage<'a> {
+    pub sender: &'a str,
+    pub recipient: &'a str,
+    pub subject: &'a str,
+    pub body_plain: Option<&'a str>,
+    pub body_html: Option<&'a str>,
+    pub message_id: Option<&'a str>,
+    /// Original headers as `(name, value)` pairs, when the provider sent them
+    pub headers: &'a [(String, String)],
+    pub reputation: SenderReputation,
+}
+
+impl InboundMessage<'_> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// What we have seen from this sender before
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SenderReputation {
+    /// Messages from the sender to this alias that were forwarded
+    pub forwarded: i64,
+    /// Messages from the sender, to any alias, that scored as spam
+    pub flagged: i64,
+}
+
+/// Score contribution from one classifier, with the rules that fired
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SpamScore {
+    pub score: f64,
+    pub rules: Vec<String>,
+}
+
+impl SpamScore {
+    fn add(&mut self, rule: &str, points: f64) {
+        self.score += points;
+        self.rules.push(rule.to_string());
+    }
+}
+
+#[axum::async_trait]
+pub trait SpamClassifier: Send + Sync {
+    /// Short name recorded alongside the score
+    fn name(&self) -> &'static str;
+
+    async fn score(&self, message: &InboundMessage<'_>) -> Result<SpamScore>;
+}
+
+/// Rules based on headers, authentication results and sender history
+pub struct HeuristicClassifier;
+
+#[axum::async_trait]
+impl SpamClassifier for HeuristicClassifier {
+    fn name(&self) -> &'static str {
+        "heuristics"
+    }
+
+    async fn score(&self, message: &InboundMessage<'_>) -> Result<SpamScore> {
+        let mut result = SpamScore::default();
+
+        // Header anomalies
+        if message.message_id.is_none_or(|id| id.trim().is_empty()) {
+            result.add("MISSING_MESSAGE_ID", 1.0);
+        }
+        if !message.headers.is_empty() && message.header("Date").is_none() {
+            result.add("MISSING_DATE", 0.5);
+        }
+        if message.subject.trim().is_empty() {
+            result.add("EMPTY_SUBJECT", 0.5);
+        }
+        let letters: Vec<char> = message.subject.chars().filter(|c| c.is_alphabetic()).collect();
+        if letters.len() >= 10 && letters.iter().all(|c| c.is_uppercase()) {
+            result.add("SUBJECT_ALL_CAPS", 1.0);
+        }
+        if message.body_html.is_some() && message.body_plain.is_none_or(|t| t.trim().is_empty()) {
+            result.add("HTML_ONLY", 0.5);
+        }
+        if let Some(reply_to) = message.header("Reply-To") {
+            if domain(reply_to) != domain(message.sender) {
+                result.add("REPLY_TO_DOMAIN_MISMATCH", 1.0);
+            }
+        }
+
+        // Sender authentication
+        let auth_results: String = message
+            .headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("Authentication-Results"))
+            .map(|(_, v)| v.to_ascii_lowercase())
+            .collect::<Vec<_>>()
+            .join(";");
+        if auth_results.contains("dmarc=fail") {
+            result.add("DMARC_FAIL", 3.0);
+        }
+        if auth_results.contains("spf=fail") {
+            result.add("SPF_FAIL", 2.0);
+        } else if auth_results.contains("spf=softfail") {
+            result.add("SPF_SOFTFAIL", 1.0);
+        }
+        if auth_results.contains("dkim=fail") {
+            result.add("DKIM_FAIL", 1.5);
+        }
+        if !message.headers.is_empty() && auth_results.is_empty() {
+            result.add("NO_AUTH_RESULTS", 0.5);
+        }
+
+        // Sender reputation
+        let reputation = message.reputation;
+        if reputation.flagged > 0 {
+            result.add("SENDER_PREVIOUSLY_FLAGGED", 0.5 * reputation.flagged.min(6) as f64);
+        } else if reputation.forwarded >= 3 {
+            result.add("KNOWN_SENDER", -1.0);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Client for the spamd protocol spoken by SpamAssassin and rspamd
+pub struct SpamdClassifier {
+    pub address: String,
+    pub timeout: Duration,
+}
+
+#[axum::async_trait]
+impl SpamClassifier for SpamdClassifier {
+    fn name(&self) -> &'static str {
+        "spamd"
+    }
+
+    async fn score(&self, message: &InboundMessage<'_>) -> Result<SpamScore> {
+        tokio::time::timeout(self.timeout, self.check(message))
+            .await
+            .map_err(|_| AppError::Internal("spamd timed out".to_string()))?
+    }
+}
+
+impl SpamdClassifier {
+    async fn check(&self, message: &InboundMessage<'_>) -> Result<SpamScore> {
+        let raw = raw_message(message);
+
+        let mut stream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| AppError::Internal(format!("spamd connection failed: {}", e)))?;
+
+        let request = format!(
+            "SYMBOLS SPAMC/1.5\r\nContent-length: {}\r\nUser: hush\r\n\r\n",
+            raw.len()
+        );
+        stream.write_all(request.as_bytes()).await.map_err(spamd_io)?;
+        stream.write_all(raw.as_bytes()).await.map_err(spamd_io)?;
+        stream.shutdown().await.map_err(spamd_io)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.map_err(spamd_io)?;
+
+        parse_spamd_response(&response)
+    }
+}
+
+fn spamd_io(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("spamd I/O failed: {}", e))
+}
+
+/// Parse `SPAMD/1.1 0 EX_OK`, `Spam: True ; 7.5 / 5.0` and the symbol list
+fn parse_spamd_response(response: &str) -> Result<SpamScore> {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response, ""));
+    let mut lines = head.lines();
+
+    let status = lines.next().unwrap_or_default();
+    if !status.starts_with("SPAMD/") || !status.contains("EX_OK") {
+        return Err(AppError::Internal(format!("spamd returned: {}", status.trim())));
+    }
+
+    let score = lines
+        .find_map(|line| line.strip_prefix("Spam:"))
+        .and_then(|value| value.split(';').nth(1))
+        .and_then(|value| value.split('/').next())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .ok_or_else(|| AppError::Internal("spamd response has no score".to_string()))?;
+
+    let rules = body
+        .trim()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    Ok(SpamScore { score, rules })
+}
+
+/// Reassemble enough of an RFC 5322 message for spamd to analyse
+fn raw_message(message: &InboundMessage<'_>) -> String {
+    let mut raw = String::new();
+
+    if message.headers.is_empty() {
+        raw.push_str(&format!("From: {}\r\n", message.sender));
+        raw.push_str(&format!("To: {}\r\n", message.recipient));
+        raw.push_str(&format!("Subject: {}\r\n", message.subject));
+        if let Some(id) = message.message_id {
+            raw.push_str(&format!("Message-Id: {}\r\n", id));
+        }
+    } else {
+        for (name, value) in message.headers {
+            // Body parts are re-encoded below
+            if name.eq_ignore_ascii_case("Content-Type")
+                || name.eq_ignore_ascii_case("Content-Transfer-Encoding")
+            {
+                continue;
+            }
+            raw.push_str(&format!("{}: {}\r\n", name, value.replace(['\r', '\n'], " ")));
+        }
+    }
+
+    let (content_type, body) = match (message.body_plain, message.body_html) {
+        (Some(text), _) if !text.trim().is_empty() => ("text/plain", text),
+        (_, Some(html)) => ("text/html", html),
+        _ => ("text/plain", ""),
+    };
+    raw.push_str(&format!("Content-Type: {}; charset=utf-8\r\n\r\n", content_type));
+    raw.push_str(body);
+
+    raw
+}
+
+fn domain(address: &str) -> String {
+    address
+        .rsplit('@')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('>')
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    fn message<'a>(headers: &'a [(String, String)]) -> InboundMessage<'a> {
+        InboundMessage {
+            sender: "news@example.com",
+            recipient: "me@hush.test",
+            subject: "Weekly update",
+            body_plain: Some("Hello"),
+            body_html: Some("<p>Hello</p>"),
+            message_id: Some("<1@example.com>"),
+            headers,
+            reputation: SenderReputation::default(),
+        }
+    }
+
+    async fn heuristics(message: &InboundMessage<'_>) -> SpamScore {
+        HeuristicClassifier.score(message).await.unwrap()
+    }
+
+    const PASSING: &[(&str, &str)] = &[
+        ("Date", "Mon, 1 Jan 2024 00:00:00 +0000"),
+        ("Authentication-Results", "mx.hush.test; spf=pass; dkim=pass; dmarc=pass"),
+    ];
+
+    #[tokio::test]
+    async fn clean_messages_score_zero() {
+        let headers = headers(PASSING);
+        let score = heuristics(&message(&headers)).await;
+        assert_eq!(score.score, 0.0);
+        assert!(score.rules.is_empty());
+
+        // Without headers from the provider only the message itself is judged
+        assert_eq!(heuristics(&message(&[])).await.score, 0.0);
+    }
+
+    #[tokio::test]
+    async fn scores_header_anomalies() {
+        let headers = headers(&[
+            ("Authentication-Results", "mx; spf=pass"),
+            ("Reply-To", "Winner <claims@other.example>"),
+        ]);
+        let mut message = message(&headers);
+        message.message_id = Some(" ");
+        message.subject = "YOU HAVE WON A PRIZE!!";
+        message.body_plain = None;
+
+        let score = heuristics(&message).await;
+        assert_eq!(
+            score.rules,
+            ["MISSING_MESSAGE_ID", "MISSING_DATE", "SUBJECT_ALL_CAPS", "HTML_ONLY", "REPLY_TO_DOMAIN_MISMATCH"]
+        );
+        assert_eq!(score.score, 4.0);
+
+        message.subject = "  ";
+        assert!(heuristics(&message).await.rules.contains(&"EMPTY_SUBJECT".to_string()));
+    }
+
+    #[tokio::test]
+    async fn scores_authentication_results() {
+        let case = |results: &'static str| headers(&[("Date", "x"), ("Authentication-Results", results)]);
+
+        let failing = case("mx; SPF=fail smtp.mailfrom=x; dkim=fail; DMARC=fail");
+        let score = heuristics(&message(&failing)).await;
+        assert_eq!(score.rules, ["DMARC_FAIL", "SPF_FAIL", "DKIM_FAIL"]);
+        assert_eq!(score.score, 6.5);
+
+        let softfail = case("mx; spf=softfail; dkim=pass");
+        assert_eq!(heuristics(&message(&softfail)).await.rules, ["SPF_SOFTFAIL"]);
+
+        let missing = headers(&[("Date", "x")]);
+        assert_eq!(heuristics(&message(&missing)).await.rules, ["NO_AUTH_RESULTS"]);
+
+        // Results split over several headers are all read
+        let split = headers(&[
+            ("Date", "x"),
+            ("authentication-results", "mx; spf=pass"),
+            ("Authentication-Results", "mx; dmarc=fail"),
+        ]);
+        assert_eq!(heuristics(&message(&split)).await.rules, ["DMARC_FAIL"]);
+    }
+
+    #[tokio::test]
+    async fn scores_sender_reputation() {
+        let headers = headers(PASSING);
+        let mut message = message(&headers);
+
+        message.reputation = SenderReputation { forwarded: 3, flagged: 0 };
+        let score = heuristics(&message).await;
+        assert_eq!((score.score, score.rules.as_slice()), (-1.0, &["KNOWN_SENDER".to_string()][..]));
+
+        message.reputation = SenderReputation { forwarded: 2, flagged: 0 };
+        assert_eq!(heuristics(&message).await.score, 0.0);
+
+        // Earlier spam outweighs forwarded history, up to a cap
+        message.reputation = SenderReputation { forwarded: 10, flagged: 2 };
+        assert_eq!(heuristics(&message).await.rules, ["SENDER_PREVIOUSLY_FLAGGED"]);
+        assert_eq!(heuristics(&message).await.score, 1.0);
+        message.reputation.flagged = 40;
+        assert_eq!(heuristics(&message).await.score, 3.0);
+    }
+
+    #[test]
+    fn parses_spamd_replies() {
+        let spam = parse_spamd_response(
+            "SPAMD/1.1 0 EX_OK\r\nContent-length: 32\r\nSpam: True ; 7.5 / 5.0\r\n\r\nBAYES_99,HTML_MESSAGE, URIBL_BLACK\r\n",
+        )
+        .unwrap();
+        assert_eq!(spam.score, 7.5);
+        assert_eq!(spam.rules, ["BAYES_99", "HTML_MESSAGE", "URIBL_BLACK"]);
+
+        // The score is used whichever way spamd judged it
+        let ham = parse_spamd_response("SPAMD/1.5 0 EX_OK\r\nSpam: False ; -1.2 / 5.0\r\n\r\n").unwrap();
+        assert_eq!(ham.score, -1.2);
+        assert!(ham.rules.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_spamd_replies() {
+        for response in [
+            "",
+            "HTTP/1.1 200 OK\r\n\r\n",
+            "SPAMD/1.1 76 EX_PROTOCOL\r\nSpam: True ; 7.5 / 5.0\r\n\r\n",
+            "SPAMD/1.1 0 EX_OK\r\nContent-length: 0\r\n\r\n",
+            "SPAMD/1.1 0 EX_OK\r\nSpam: True ; lots / 5.0\r\n\r\n",
+            "SPAMD/1.1 0 EX_OK\r\nSpam: True\r\n\r\n",
+        ] {
+            assert!(parse_spamd_response(response).is_err(), "{:?} should be rejected", response);
+        }
+    }
+
+    #[test]
+    fn rebuilds_a_raw_message() {
+        let raw = raw_message(&message(&[]));
+        assert!(raw.starts_with("From: news@example.com\r\nTo: me@hush.test\r\nSubject: Weekly update\r\n"));
+        assert!(raw.ends_with("Content-Type: text/plain; charset=utf-8\r\n\r\nHello"));
+
+        let headers = headers(&[("Subject", "multi\r\n line"), ("Content-Type", "multipart/mixed")]);
+        let mut message = message(&headers);
+        message.body_plain = None;
+        let raw = raw_message(&message);
+        assert!(raw.starts_with("Subject: multi   line\r\nContent-Type: text/html"));
+        assert!(raw.ends_with("<p>Hello</p>"));
+    }
+}
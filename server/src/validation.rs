@@ -0,0 +1,275 @@
+use crate::error::{AppError, Result};
+use lettre::Address;
+
+/// Maximum length of an address in a forward/reverse path (RFC 5321 4.5.3.1.3)
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+
+/// Validate an email address supplied by a user and return it in the
+/// canonical form we store (trimmed, lowercase).
+pub fn normalize_email(input: &str) -> Result<String> {
+    let email = input.trim().to_lowercase();
+
+    if email.is_empty() {
+        return Err(AppError::Validation("Email cannot be empty".to_string()));
+    }
+
+    if email.len() > MAX_EMAIL_LENGTH {
+        return Err(AppError::Validation("Email address is too long".to_string()));
+    }
+
+    let address: Address = email
+        .parse()
+        .map_err(|_| AppError::Validation("Invalid email format".to_string()))?;
+
+    if address.user().len() > MAX_LOCAL_PART_LENGTH {
+        return Err(AppError::Validation("Email address is too long".to_string()));
+    }
+
+    // Require a dotted hostname; bare hosts and address literals are not deliverable targets
+    let domain = address.domain();
+    if domain.starts_with('[')
+        || !domain.contains('.')
+        || domain.split('.').any(|label| label.is_empty() || label.len() > 63)
+    {
+        return Err(AppError::Validation("Invalid email domain".to_string()));
+    }
+
+    Ok(email)
+}
+
+/// Characters RFC 5322 allows in an atom besides letters and digits
+const ATEXT_SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
+
+/// What a custom alias must satisfy beyond being a valid local part
+pub struct AliasPolicy {
+    pub min_length: usize,
+    /// Subaddress separator, which would make the alias unreachable
+    pub separator: Option<String>,
+    /// Names only the operator may use, matched exactly
+    pub reserved: Vec<String>,
+    /// Words that may not appear as a word of an alias
+    pub profanity: Vec<String>,
+}
+
+fn invalid(field: &'static str, code: &'static str, message: impl Into<String>) -> AppError {
+    AppError::InvalidField {
+        field,
+        code,
+        message: message.into(),
+    }
+}
+
+/// Check the local part of a custom alias against RFC 5321 and return it
+/// trimmed and lower-cased. Errors name `field` as the rejected input.
+///
+/// Only letters, digits, `.`, `-` and `_` are accepted, as other characters
+/// are awkward to type or special to mail clients.
+pub fn normalize_local_part(input: &str, field: &'static str) -> Result<String> {
+    let local = input.trim().to_lowercase();
+
+    if local.is_empty() {
+        return Err(invalid(field, "empty", "Alias cannot be empty"));
+    }
+
+    if local.len() > MAX_LOCAL_PART_LENGTH {
+        return Err(invalid(
+            field,
+            "too_long",
+            format!("Alias can be at most {} characters", MAX_LOCAL_PART_LENGTH),
+        ));
+    }
+
+    if let Some(c) = local.chars().find(|c| confusable(*c).is_some()) {
+        return Err(invalid(
+            field,
+            "confusable",
+            format!("Alias contains '{}', which imitates an ASCII character", c),
+        ));
+    }
+
+    // Dot-string from RFC 5321 4.1.2; quoted strings are not accepted
+    if local.starts_with('"') {
+        return Err(invalid(field, "invalid_format", "Quoted aliases are not supported"));
+    }
+    if local.split('.').any(|atom| atom.is_empty()) {
+        return Err(invalid(
+            field,
+            "invalid_format",
+            "Alias cannot start or end with a dot or contain consecutive dots",
+        ));
+    }
+    if let Some(c) = local
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '.' && !ATEXT_SPECIALS.contains(*c))
+    {
+        return Err(invalid(
+            field,
+            "invalid_format",
+            format!("'{}' is not allowed in an email address", c),
+        ));
+    }
+
+    if let Some(c) = local
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '.' | '-' | '_'))
+    {
+        return Err(invalid(
+            field,
+            "invalid_characters",
+            format!("Alias may only contain letters, digits, '.', '-' and '_', not '{}'", c),
+        ));
+    }
+
+    Ok(local)
+}
+
+/// Apply the operator's policy to a normalized local part. Reserved and
+/// profanity lists are compared by skeleton so look-alike spellings such
+/// as `p0stmaster` are caught too; they only apply on the system domains.
+/// A profanity entry must match a whole word of the alias, or a run of
+/// words, so names that merely contain it (`scunthorpe`) are allowed.
+pub fn check_alias_policy(
+    local: &str,
+    field: &'static str,
+    policy: &AliasPolicy,
+    system_domain: bool,
+) -> Result<()> {
+    if local.chars().count() < policy.min_length {
+        return Err(invalid(
+            field,
+            "too_short",
+            format!("Alias must be at least {} characters", policy.min_length),
+        ));
+    }
+
+    if let Some(separator) = policy.separator.as_deref() {
+        if local.contains(separator) {
+            return Err(invalid(
+                field,
+                "separator",
+                format!("Alias cannot contain '{}'", separator),
+            ));
+        }
+    }
+
+    if !system_domain {
+        return Ok(());
+    }
+
+    let shape = skeleton(local);
+
+    if policy.reserved.iter().any(|name| skeleton(name) == shape) {
+        return Err(invalid(field, "reserved", "This alias is reserved"));
+    }
+
+    // Skeletons of every run of consecutive words, split on separators
+    let words: Vec<&str> = local.split(['.', '-', '_']).collect();
+    let runs: Vec<String> = (0..words.len())
+        .flat_map(|start| (start + 1..=words.len()).map(move |end| (start, end)))
+        .map(|(start, end)| skeleton(&words[start..end].concat()))
+        .collect();
+
+    if policy
+        .profanity
+        .iter()
+        .map(|word| skeleton(word))
+        .any(|word| !word.is_empty() && runs.contains(&word))
+    {
+        return Err(invalid(field, "profanity", "This alias is not allowed"));
+    }
+
+    Ok(())
+}
+
+/// Reduce a name to the letters it looks like: separators are dropped,
+/// digits and symbols mapped to the letters they resemble, and letter
+/// pairs that read as one letter (`rn`, `vv`) merged.
+pub fn skeleton(name: &str) -> String {
+    let mapped: String = name
+        .chars()
+        .filter(|c| !matches!(c, '.' | '-' | '_'))
+        .map(|c| {
+            let c = confusable(c).unwrap_or(c).to_ascii_lowercase();
+            match c {
+                '0' => 'o',
+                '1' | 'i' | '!' | '|' => 'l',
+                '3' => 'e',
+                '4' | '@' => 'a',
+                '5' | '$' => 's',
+                '7' => 't',
+                '8' => 'b',
+                '9' => 'g',
+                c => c,
+            }
+        })
+        .collect();
+
+    mapped.replace("rn", "m").replace("vv", "w")
+}
+
+/// ASCII character a non-ASCII look-alike imitates: Cyrillic and Greek
+/// letters that render like Latin ones, and full-width forms
+fn confusable(c: char) -> Option<char> {
+    let ascii = match c {
+        'а' | 'α' => 'a',
+        'в' | 'β' => 'b',
+        'с' | 'ϲ' => 'c',
+        'е' | 'ε' => 'e',
+        'һ' | 'н' => 'h',
+        'і' | 'ι' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'м' => 'm',
+        'η' => 'n',
+        'о' | 'ο' | 'σ' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'т' | 'τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'ѡ' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'ᴢ' => 'z',
+        '\u{ff10}'..='\u{ff19}' => (b'0' + (c as u32 - 0xff10) as u8) as char,
+        '\u{ff21}'..='\u{ff3a}' => (b'a' + (c as u32 - 0xff21) as u8) as char,
+        '\u{ff41}'..='\u{ff5a}' => (b'a' + (c as u32 - 0xff41) as u8) as char,
+        _ => return None,
+    };
+
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(local: &str) -> bool {
+        let policy = AliasPolicy {
+            min_length: 1,
+            separator: None,
+            reserved: vec!["postmaster".to_string()],
+            profanity: vec!["cunt".to_string(), "ass".to_string()],
+        };
+        check_alias_policy(local, "custom", &policy, true).is_ok()
+    }
+
+    #[test]
+    fn profanity_matches_whole_words_only() {
+        for local in ["scunthorpe", "classic", "assistant", "bass.guitar", "grasshopper", "passage_2024"] {
+            assert!(allowed(local), "{} should be allowed", local);
+        }
+        for local in ["ass", "kick.ass", "a.s.s", "a-ss.hat", "my_4ss", "cunt", "c.u.n.t"] {
+            assert!(!allowed(local), "{} should be refused", local);
+        }
+    }
+
+    #[test]
+    fn reserved_names_match_by_skeleton() {
+        assert!(!allowed("p0stmaster"));
+        assert!(!allowed("post.master"));
+        assert!(allowed("postmaster2"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Web Push protocol support: payload encryption (RFC 8291, `aes128gcm`
+//! content coding from RFC 8188) and VAPID authentication (RFC 8292).
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes128Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hkdf::Hkdf;
+use p256::{
+    ecdh::diffie_hellman,
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    PublicKey, SecretKey,
+};
+use rand::{rngs::OsRng, Rng};
+use reqwest::StatusCode;
+use sha2::Sha256;
+
+use crate::error::{AppError, Result};
+
+/// Record size advertised in the content coding header. Payloads are always
+/// sent as a single record, so this only needs to exceed the payload size.
+const RECORD_SIZE: u32 = 4096;
+
+/// Push services accept at most 4096 bytes of ciphertext; leave room for the
+/// header, padding delimiter and AEAD tag.
+pub const MAX_PAYLOAD_SIZE: usize = 3993;
+
+/// Application server key pair used to sign VAPID tokens
+pub struct VapidKey {
+    signing_key: SigningKey,
+    public_key: String,
+}
+
+impl VapidKey {
+    /// Load the private key from its base64url-encoded 32-byte scalar, the
+    /// format produced by common `web-push generate-vapid-keys` tools.
+    pub fn from_base64(private_key: &str) -> Result<Self> {
+        let bytes = decode_base64(private_key)
+            .map_err(|_| AppError::Internal("VAPID private key is not valid base64".to_string()))?;
+        let secret = SecretKey::from_slice(&bytes)
+            .map_err(|_| AppError::Internal("VAPID private key is not a P-256 scalar".to_string()))?;
+
+        let public_key = URL_SAFE_NO_PAD.encode(secret.public_key().to_sec1_bytes());
+
+        Ok(Self {
+            signing_key: SigningKey::from(&secret),
+            public_key,
+        })
+    }
+
+    /// Public key in the form browsers expect as `applicationServerKey`
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Build the `Authorization` header value for a push endpoint
+    pub fn authorization(&self, endpoint: &str, subject: &str) -> Result<String> {
+        let url = reqwest::Url::parse(endpoint)
+            .map_err(|_| AppError::Validation("Invalid push endpoint".to_string()))?;
+        let audience = url.origin().ascii_serialization();
+        let exp = chrono::Utc::now().timestamp() + 12 * 3600;
+
+        let header = URL_SAFE_NO_PAD.encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            serde_json::json!({ "aud": audience, "exp": exp, "sub": subject }).to_string(),
+        );
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self.signing_key.sign(signing_input.as_bytes());
+
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key
+        ))
+    }
+}
+
+/// Encrypt a payload for a subscription given its `p256dh` and `auth` keys
+pub fn encrypt(payload: &[u8], p256dh: &str, auth: &str) -> Result<Vec<u8>> {
+    let as_secret = SecretKey::random(&mut OsRng);
+    let salt: [u8; 16] = rand::thread_rng().gen();
+
+    encrypt_with(payload, p256dh, auth, &as_secret, &salt)
+}
+
+/// Encryption with a given application server key pair and salt, which
+/// `encrypt` picks at random for every message
+fn encrypt_with(payload: &[u8], p256dh: &str, auth: &str, as_secret: &SecretKey, salt: &[u8; 16]) -> Result<Vec<u8>> {
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return Err(AppError::Internal("Push payload is too large".to_string()));
+    }
+
+    let ua_public_bytes = decode_base64(p256dh)
+        .map_err(|_| AppError::Validation("Invalid p256dh key".to_string()))?;
+    let ua_public = PublicKey::from_sec1_bytes(&ua_public_bytes)
+        .map_err(|_| AppError::Validation("Invalid p256dh key".to_string()))?;
+    let auth_secret = decode_base64(auth)
+        .map_err(|_| AppError::Validation("Invalid auth secret".to_string()))?;
+
+    let as_public = as_secret.public_key().to_sec1_bytes();
+    let shared = diffie_hellman(as_secret.to_nonzero_scalar(), ua_public.as_affine());
+
+    // IKM = HKDF(auth_secret, ecdh_secret, "WebPush: info" || 0x00 || ua_public || as_public)
+    let mut key_info = Vec::with_capacity(14 + 65 + 65);
+    key_info.extend_from_slice(b"WebPush: info\0");
+    key_info.extend_from_slice(&ua_public_bytes);
+    key_info.extend_from_slice(&as_public);
+
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&auth_secret), shared.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .map_err(|_| AppError::Internal("HKDF expand failed".to_string()))?;
+
+    let prk = Hkdf::<Sha256>::new(Some(salt), &ikm);
+
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .and_then(|_| prk.expand(b"Content-Encoding: nonce\0", &mut nonce))
+        .map_err(|_| AppError::Internal("HKDF expand failed".to_string()))?;
+
+    // Single record: payload followed by the last-record padding delimiter
+    let mut plaintext = Vec::with_capacity(payload.len() + 1);
+    plaintext.extend_from_slice(payload);
+    plaintext.push(0x02);
+
+    let cipher = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| AppError::Internal("Invalid content encryption key".to_string()))?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+        .map_err(|_| AppError::Internal("Push payload encryption failed".to_string()))?;
+
+    // Header: salt || record size || key id length || key id (as_public)
+    let mut body = Vec::with_capacity(16 + 4 + 1 + as_public.len() + ciphertext.len());
+    body.extend_from_slice(salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.len() as u8);
+    body.extend_from_slice(&as_public);
+    body.extend_from_slice(&ciphertext);
+
+    Ok(body)
+}
+
+/// Result of delivering one push message
+pub enum PushOutcome {
+    Delivered,
+    /// The subscription no longer exists and should be removed
+    Gone,
+    Failed(String),
+}
+
+/// POST an encrypted message to a push service endpoint
+pub async fn send(
+    client: &reqwest::Client,
+    endpoint: &str,
+    authorization: &str,
+    body: Vec<u8>,
+    ttl_secs: u32,
+) -> PushOutcome {
+    let response = client
+        .post(endpoint)
+        .header("Authorization", authorization)
+        .header("Content-Encoding", "aes128gcm")
+        .header("Content-Type", "application/octet-stream")
+        .header("TTL", ttl_secs.to_string())
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(resp) if resp.status().is_success() => PushOutcome::Delivered,
+        Ok(resp) if resp.status() == StatusCode::NOT_FOUND || resp.status() == StatusCode::GONE => {
+            PushOutcome::Gone
+        }
+        Ok(resp) => PushOutcome::Failed(format!("push service returned {}", resp.status())),
+        Err(e) => PushOutcome::Failed(e.to_string()),
+    }
+}
+
+/// Browsers encode keys as base64url without padding, but some clients pad
+/// or use the standard alphabet
+pub fn decode_base64(input: &str) -> std::result::Result<Vec<u8>, base64::DecodeError> {
+    let normalized: String = input
+        .trim()
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+
+    URL_SAFE_NO_PAD.decode(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Example from RFC 8291, Appendix A
+    #[test]
+    fn encrypts_rfc8291_example() {
+        let as_private = decode_base64("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw").unwrap();
+        let as_secret = SecretKey::from_slice(&as_private).unwrap();
+        let salt: [u8; 16] = decode_base64("DGv6ra1nlYgDCS1FRnbzlw").unwrap().try_into().unwrap();
+
+        let body = encrypt_with(
+            b"When I grow up, I want to be a watermelon",
+            "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+            "BTBZMqHH6r4Tts7J_aSIgg",
+            &as_secret,
+            &salt,
+        )
+        .unwrap();
+
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(body),
+            "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_payload() {
+        let payload = vec![0u8; MAX_PAYLOAD_SIZE + 1];
+        let result = encrypt(
+            &payload,
+            "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+            "BTBZMqHH6r4Tts7J_aSIgg",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn decodes_padded_standard_base64() {
+        assert_eq!(decode_base64("+/8=").unwrap(), decode_base64("-_8").unwrap());
+    }
+}